@@ -0,0 +1,273 @@
+use thiserror::Error;
+
+use crate::{config::Config, ext::StrExt as _};
+
+/// the maximum number of aliases that may expand into each other
+const MAX_ALIAS_DEPTH: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum AliasErr {
+    #[error("alias {} expands into itself", .0)]
+    Recursive(String),
+    #[error("alias {} is nested more than {} aliases deep", .0, MAX_ALIAS_DEPTH)]
+    TooDeep(String),
+    #[error("alias {} expected at least {} args, found {}", .0, .1, .2)]
+    MissingArg(String, usize, usize),
+    #[error("alias {} uses $chan outside of a channel", .0)]
+    NoChannel(String),
+}
+
+/// the values that can be substituted into an alias besides its arguments
+#[derive(Debug)]
+pub struct AliasContext<'a> {
+    /// our own nick, `$nick`
+    pub nick: &'a str,
+    /// the channel of the current window, `$chan`
+    pub channel: Option<&'a str>,
+}
+
+/// expands the alias `name` with the arguments `args` into the lines of input it represents.
+/// aliases in the expansion are expanded too, so none of the returned lines use an alias.
+///
+/// an expansion may contain multiple commands separated by `;`, and these substitutions:
+/// - `$1`, `$2`, ...: a single argument
+/// - `$2-`: an argument and every argument after it
+/// - `$*`: every argument
+/// - `$nick` and `$chan`: our nick and the channel of the current window
+/// - `$$`: a literal `$`
+pub fn expand(
+    config: &Config,
+    name: &str,
+    args: &str,
+    ctx: &AliasContext,
+) -> Result<Vec<String>, AliasErr> {
+    let mut lines = Vec::new();
+    expand_inner(config, name, args, ctx, &mut Vec::new(), &mut lines)?;
+    Ok(lines)
+}
+
+fn expand_inner(
+    config: &Config,
+    name: &str,
+    args: &str,
+    ctx: &AliasContext,
+    stack: &mut Vec<String>,
+    lines: &mut Vec<String>,
+) -> Result<(), AliasErr> {
+    let name = name.to_lowercase();
+    if stack.contains(&name) {
+        return Err(AliasErr::Recursive(name));
+    }
+    if stack.len() >= MAX_ALIAS_DEPTH {
+        return Err(AliasErr::TooDeep(name));
+    }
+    let Some(template) = config.alias(&name) else {
+        // callers only expand names that are aliases
+        return Ok(());
+    };
+
+    let args = args
+        .split(' ')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    stack.push(name.clone());
+    // split before substituting so that arguments can't add more commands
+    for part in template.split(';') {
+        let line = substitute(&name, part.trim(), &args, ctx)?;
+        if line.is_empty() {
+            continue;
+        }
+
+        match line.split_prefix('/') {
+            Some((_, cmd)) => {
+                let (inner, inner_args) = cmd.split_once(' ').unwrap_or((cmd, ""));
+                // an alias that uses its own name wraps the built in command with that name, like
+                // `join` expanding to `/join $1 key`
+                if inner.to_lowercase() != name && config.alias(inner).is_some() {
+                    expand_inner(config, inner, inner_args, ctx, stack, lines)?;
+                } else {
+                    lines.push(line);
+                }
+            }
+            None => lines.push(line),
+        }
+    }
+    stack.pop();
+
+    Ok(())
+}
+
+fn substitute(
+    name: &str,
+    template: &str,
+    args: &[&str],
+    ctx: &AliasContext,
+) -> Result<String, AliasErr> {
+    let missing = |n: usize| AliasErr::MissingArg(name.to_string(), n, args.len());
+
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(idx) = rest.find('$') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits > 0 {
+            // positional arguments are 1 based
+            let n = rest[..digits].parse::<usize>().unwrap_or(0);
+            if n == 0 || n > args.len() {
+                return Err(missing(n));
+            }
+            rest = &rest[digits..];
+
+            if let Some((_, after)) = rest.split_prefix('-') {
+                out.push_str(args[n - 1..].join(" ").as_str());
+                rest = after;
+            } else {
+                out.push_str(args[n - 1]);
+            }
+        } else if let Some((_, after)) = rest.split_prefix('*') {
+            out.push_str(args.join(" ").as_str());
+            rest = after;
+        } else if let Some((_, after)) = rest.split_prefix('$') {
+            out.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("nick") {
+            out.push_str(ctx.nick);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("chan") {
+            let Some(channel) = ctx.channel else {
+                return Err(AliasErr::NoChannel(name.to_string()));
+            };
+            out.push_str(channel);
+            rest = after;
+        } else {
+            // not a substitution, keep the `$`
+            out.push('$');
+        }
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CTX: AliasContext = AliasContext {
+        nick: "me",
+        channel: Some("#chan"),
+    };
+
+    fn config(aliases: &[(&str, &str)]) -> Config {
+        let mut config = Config::default();
+        config.aliases = aliases
+            .iter()
+            .map(|(name, expansion)| (name.to_string(), expansion.to_string()))
+            .collect();
+        config
+    }
+
+    #[test]
+    fn positional_args() {
+        let config = config(&[("greet", "/msg $1 hi $2, from $nick in $chan")]);
+        assert_eq!(
+            expand(&config, "greet", "bob  alice", &CTX).unwrap(),
+            ["/msg bob hi alice, from me in #chan"]
+        );
+        assert!(matches!(
+            expand(&config, "greet", "bob", &CTX),
+            Err(AliasErr::MissingArg(name, 2, 1)) if name == "greet"
+        ));
+        let no_channel = AliasContext {
+            nick: "me",
+            channel: None,
+        };
+        assert!(matches!(
+            expand(&config, "greet", "bob alice", &no_channel),
+            Err(AliasErr::NoChannel(_))
+        ));
+    }
+
+    #[test]
+    fn rest_of_args() {
+        let config = config(&[("rest", "/say $2-"), ("all", "/say [$*]")]);
+        assert_eq!(
+            expand(&config, "rest", "a b c", &CTX).unwrap(),
+            ["/say b c"]
+        );
+        assert!(matches!(
+            expand(&config, "rest", "a", &CTX),
+            Err(AliasErr::MissingArg(_, 2, 1))
+        ));
+        assert_eq!(
+            expand(&config, "all", "a  b", &CTX).unwrap(),
+            ["/say [a b]"]
+        );
+        assert_eq!(expand(&config, "all", "", &CTX).unwrap(), ["/say []"]);
+    }
+
+    #[test]
+    fn literal_dollars() {
+        let config = config(&[("price", "/say $$1 costs $$$1, $ and $x stay")]);
+        assert_eq!(
+            expand(&config, "price", "5", &CTX).unwrap(),
+            ["/say $1 costs $5, $ and $x stay"]
+        );
+    }
+
+    #[test]
+    fn chained_commands() {
+        let config = config(&[("both", "/say $1; ; /say $2")]);
+        // a `;` in an argument doesn't start another command
+        assert_eq!(
+            expand(&config, "both", "a;b c", &CTX).unwrap(),
+            ["/say a;b", "/say c"]
+        );
+    }
+
+    #[test]
+    fn nested_aliases() {
+        let config = config(&[
+            ("j", "/join $1"),
+            ("join", "/join $1 key; /hello $1"),
+            ("hello", "/say hi $1"),
+        ]);
+        // an alias with the name of a command wraps the command instead of expanding itself
+        assert_eq!(
+            expand(&config, "J", "#c", &CTX).unwrap(),
+            ["/join #c key", "/say hi #c"]
+        );
+    }
+
+    #[test]
+    fn recursion() {
+        let config = config(&[("a", "/b $*"), ("b", "/say; /a $*")]);
+        assert!(matches!(
+            expand(&config, "a", "x", &CTX),
+            Err(AliasErr::Recursive(name)) if name == "a"
+        ));
+
+        // a chain of different aliases that is one longer than the limit
+        let chain = (0..=MAX_ALIAS_DEPTH)
+            .map(|n| (format!("a{}", n), format!("/a{}", n + 1)))
+            .collect::<Vec<_>>();
+        let chain = chain
+            .iter()
+            .map(|(name, expansion)| (name.as_str(), expansion.as_str()))
+            .collect::<Vec<_>>();
+        let config = self::config(&chain);
+        assert!(matches!(
+            expand(&config, "a0", "", &CTX),
+            Err(AliasErr::TooDeep(name)) if name == format!("a{}", MAX_ALIAS_DEPTH)
+        ));
+        // the last alias expands into a command that isn't an alias
+        assert_eq!(
+            expand(&config, "a1", "", &CTX).unwrap(),
+            [format!("/a{}", MAX_ALIAS_DEPTH + 1)]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::{HashMap, HashSet};
+
+/// the capabilities that the client knows how to use, which are requested whenever the server
+/// offers them
+const WANTED: &[&str] = &[
+    "away-notify",
+    "batch",
+    "draft/chathistory",
+    "echo-message",
+    "invite-notify",
+    "labeled-response",
+    "message-tags",
+    "server-time",
+];
+
+/// the state of capability negotiation with the server
+#[derive(Debug, Default)]
+pub struct Caps {
+    /// the capabilities that the server offers, with their values
+    available: HashMap<String, Option<String>>,
+    /// the capabilities that the server acknowledged
+    enabled: HashSet<String>,
+}
+
+impl Caps {
+    /// adds capabilities that the server offered with CAP LS or CAP NEW
+    pub fn add_available(&mut self, caps: &[String]) {
+        for cap in caps {
+            match cap.split_once('=') {
+                Some((name, value)) => {
+                    self.available
+                        .insert(name.to_string(), Some(value.to_string()));
+                }
+                None => {
+                    self.available.insert(cap.to_string(), None);
+                }
+            }
+        }
+    }
+
+    /// removes capabilities that the server no longer offers, from CAP DEL
+    pub fn remove(&mut self, caps: &[String]) {
+        for cap in caps {
+            self.available.remove(cap);
+            self.enabled.remove(cap);
+        }
+    }
+
+    /// the capabilities to request, which are the ones that the client wants and the server
+    /// offers, but aren't enabled yet
+    pub fn to_request(&self) -> Vec<String> {
+        WANTED
+            .iter()
+            .filter(|cap| self.available.contains_key(**cap) && !self.enabled.contains(**cap))
+            .map(|cap| cap.to_string())
+            .collect()
+    }
+
+    pub fn is_enabled(&self, cap: &str) -> bool {
+        self.enabled.contains(cap)
+    }
+
+    /// marks capabilities as enabled after CAP ACK. a `-` prefix means that it was disabled.
+    pub fn acknowledge(&mut self, caps: &[String]) {
+        for cap in caps {
+            match cap.strip_prefix('-') {
+                Some(cap) => {
+                    self.enabled.remove(cap);
+                }
+                None => {
+                    self.enabled.insert(cap.to_string());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+/// how the server decides whether two nicks or channel names are the same, from the CASEMAPPING
+/// ISUPPORT token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Casemapping {
+    /// only `A` to `Z` are uppercase letters
+    Ascii,
+    /// ascii, with `[]\~` as the uppercase of `{}|^`
+    #[default]
+    Rfc1459,
+    /// rfc1459, without `~` and `^`
+    Rfc1459Strict,
+}
+
+impl Casemapping {
+    /// the casemapping from the value of the CASEMAPPING token, which is rfc1459 if the server
+    /// didn't send one
+    // NOTE: rfc7613 and other unicode casemappings are treated as rfc1459, which only differs
+    // from them for nicks that aren't ascii
+    pub fn parse(value: Option<&str>) -> Self {
+        match value {
+            Some(value) if value.eq_ignore_ascii_case("ascii") => Casemapping::Ascii,
+            Some(value) if value.eq_ignore_ascii_case("rfc1459-strict") => {
+                Casemapping::Rfc1459Strict
+            }
+            _ => Casemapping::Rfc1459,
+        }
+    }
+
+    /// lowercases a nick or channel name
+    pub fn lower(self, name: &str) -> String {
+        name.chars().map(|c| self.lower_char(c)).collect()
+    }
+
+    /// whether two nicks or channel names are the same to the server
+    pub fn same(self, a: &str, b: &str) -> bool {
+        a.len() == b.len()
+            && a.chars()
+                .zip(b.chars())
+                .all(|(a, b)| self.lower_char(a) == self.lower_char(b))
+    }
+
+    fn lower_char(self, c: char) -> char {
+        match (self, c) {
+            (Casemapping::Rfc1459 | Casemapping::Rfc1459Strict, '[') => '{',
+            (Casemapping::Rfc1459 | Casemapping::Rfc1459Strict, ']') => '}',
+            (Casemapping::Rfc1459 | Casemapping::Rfc1459Strict, '\\') => '|',
+            (Casemapping::Rfc1459, '~') => '^',
+            (_, c) => c.to_ascii_lowercase(),
+        }
+    }
+}
@@ -1,64 +1,198 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use thiserror::Error;
+use chrono::{DateTime, Utc};
 
-use crate::{channel::ChannelName, ui::text::Line};
-
-#[derive(Debug, Error)]
-pub enum ChannelCreationErr {
-    #[error("the empty string is not a valid channel name")]
-    NoChannelName,
-    #[error("channel {} had an invalid kind", .0)]
-    InvalidKind(String),
-}
+use crate::{
+    casemap::Casemapping,
+    channel::{
+        mode::{ChannelModes, ModeChange, ModeKind, ModeRules},
+        ChannelName, Nickname,
+    },
+    ui::text::Line,
+};
 
 #[derive(Debug)]
 pub struct Channel {
     // the name of the channel, **including** the channel kind prefix (so it is suitable for direct
     // use as a target)
     name: ChannelName,
-    pub modes: String,
-    topic: String,
-    // TODO: represent users better, may need to be `HashSet<Arc<User>>`?
-    pub users: HashSet<String>,
+    pub modes: ChannelModes,
+    topic: Option<Topic>,
+    /// whether we are currently in the channel. the window for a channel stays open after being
+    /// kicked or failing to join, so this may be false.
+    pub joined: bool,
+    pub users: HashMap<Nickname, Member>,
     pub messages: VecDeque<Line<'static>>,
+    /// the msgids of the lines in `messages`
+    pub msgids: HashSet<String>,
 }
 
-impl Channel {
-    /// creates a channel from the name of the channel, using default values for the modes, topic,
-    /// and users
-    pub fn new(name: impl Into<String>) -> Result<Self, ChannelCreationErr> {
-        let name: String = name.into();
-        if name.len() == 0 {
-            return Err(ChannelCreationErr::NoChannelName);
-        }
+#[derive(Debug, Clone)]
+pub struct Topic {
+    pub text: String,
+    /// who set the topic, which may be a nick or a full `nick!user@host`
+    pub setter: Option<String>,
+    pub time: Option<DateTime<Utc>>,
+}
 
-        // UNWRAP: there exists at least one character because of the len check above
-        let kind = ChannelKind::parse(name.chars().next().unwrap())
-            .ok_or_else(|| ChannelCreationErr::InvalidKind(name.clone()))?;
+/// a user in a channel
+#[derive(Debug, Clone, Default)]
+pub struct Member {
+    /// the membership prefixes of the user, like `@` for an operator
+    pub prefixes: String,
+    pub user: Option<String>,
+    pub host: Option<String>,
+    /// the account that the user is logged in to, if they are known to be logged in
+    pub account: Option<String>,
+    pub realname: Option<String>,
+    pub away: bool,
+}
 
-        Ok(Self {
-            name: ChannelName { name, kind },
-            modes: String::new(),
-            topic: String::new(),
-            users: HashSet::new(),
-            messages: VecDeque::new(),
-        })
+/// which part of a user's hostmask a ban matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanMask {
+    /// `*!*@host`
+    Host,
+    /// `*!user@*`
+    User,
+    /// `nick!*@*`
+    Nick,
+}
+
+impl Member {
+    /// the full `nick!user@host` of the member, using `*` for any part that isn't known
+    pub fn hostmask(&self, nick: &Nickname) -> String {
+        format!(
+            "{}!{}@{}",
+            nick.as_str(),
+            self.user.as_deref().unwrap_or("*"),
+            self.host.as_deref().unwrap_or("*")
+        )
     }
 
+    /// a mask that bans the member, falling back to banning their nick if the part of the
+    /// hostmask to ban isn't known
+    pub fn ban_mask(&self, nick: &Nickname, kind: BanMask) -> String {
+        match (kind, &self.user, &self.host) {
+            (BanMask::Host, _, Some(host)) => format!("*!*@{}", host),
+            (BanMask::User, Some(user), _) => format!("*!{}@*", user),
+            _ => format!("{}!*@*", nick.as_str()),
+        }
+    }
+}
+
+impl Channel {
     pub fn from_name(name: ChannelName) -> Self {
         Self {
             name,
-            modes: String::new(),
-            topic: String::new(),
-            users: HashSet::new(),
+            modes: ChannelModes::default(),
+            topic: None,
+            joined: false,
+            users: HashMap::new(),
             messages: VecDeque::new(),
+            msgids: HashSet::new(),
         }
     }
 
     pub fn name(&self) -> &ChannelName {
         &self.name
     }
+
+    /// compares the name of the channel and its users with a different casemapping
+    pub fn rekey(&mut self, casemapping: Casemapping) {
+        self.name = self.name.rekey(casemapping);
+        self.users = self
+            .users
+            .drain()
+            .map(|(nick, member)| (nick.rekey(casemapping), member))
+            .collect();
+    }
+
+    pub fn topic(&self) -> Option<&Topic> {
+        self.topic.as_ref()
+    }
+
+    /// sets the text of the topic, or clears the topic if the text is empty. the setter and time
+    /// are reset, since they are sent separately from the text when joining.
+    pub fn set_topic(&mut self, text: String) {
+        self.topic = (!text.is_empty()).then_some(Topic {
+            text,
+            setter: None,
+            time: None,
+        });
+    }
+
+    /// sets who set the topic and when, if there is a topic
+    pub fn set_topic_who_time(&mut self, setter: String, time: Option<DateTime<Utc>>) {
+        if let Some(topic) = &mut self.topic {
+            topic.setter = Some(setter);
+            topic.time = time;
+        }
+    }
+
+    /// applies a mode change, including changes to the membership prefixes of users
+    pub fn apply_mode(&mut self, rules: &ModeRules, change: &ModeChange, setter: Option<&str>) {
+        let ModeKind::Prefix(prefix) = change.kind else {
+            self.modes.apply(change, setter, rules.casemapping());
+            return;
+        };
+        let Some(member) = change
+            .param
+            .as_ref()
+            .and_then(|nick| Nickname::new(nick, rules.casemapping()))
+            .and_then(|nick| self.users.get_mut(&nick))
+        else {
+            return;
+        };
+
+        if change.adding {
+            if !member.prefixes.contains(prefix) {
+                member.prefixes.push(prefix);
+            }
+            // keep the highest prefix first, so that it can be shown before the nick
+            let mut prefixes = member.prefixes.chars().collect::<Vec<_>>();
+            prefixes.sort_by_key(|prefix| rules.prefix_rank(*prefix).unwrap_or(usize::MAX));
+            member.prefixes = prefixes.into_iter().collect();
+        } else {
+            member.prefixes.retain(|p| p != prefix);
+        }
+    }
+
+    /// adds the users from a param of a reply to NAMES, where they are separated by spaces
+    pub fn add_names(&mut self, names: &str, rules: &ModeRules) {
+        for name in names.split_whitespace() {
+            self.add_name(name, rules);
+        }
+    }
+
+    /// adds a user from a reply to NAMES, which may have membership prefixes and, with
+    /// userhost-in-names, the user and host of the nick
+    fn add_name(&mut self, name: &str, rules: &ModeRules) {
+        let prefix_len = name
+            .find(|c| !rules.prefix_chars().any(|prefix| prefix == c))
+            .unwrap_or(name.len());
+        let (prefixes, rest) = name.split_at(prefix_len);
+        let (nick, user, host) = match rest.split_once('!') {
+            Some((nick, userhost)) => match userhost.split_once('@') {
+                Some((user, host)) => (nick, Some(user.to_string()), Some(host.to_string())),
+                None => (nick, Some(userhost.to_string()), None),
+            },
+            None => (rest, None, None),
+        };
+        let Some(nick) = Nickname::new(nick, rules.casemapping()) else {
+            return;
+        };
+
+        // keep anything that is already known about the user, like their host from JOIN
+        let member = self.users.entry(nick).or_default();
+        member.prefixes = prefixes.to_string();
+        if user.is_some() {
+            member.user = user;
+        }
+        if host.is_some() {
+            member.host = host;
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -0,0 +1,551 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    ops::Range,
+};
+
+use chrono::Local;
+
+use crate::{
+    casemap::Casemapping,
+    channel::{
+        mode::{self, ListEntry},
+        ChannelName,
+    },
+    isupport::ISupport,
+    ui::{text::Line, theme::Role},
+    util::unicode_width,
+};
+
+/// the widest that the channel column can be, so that long names don't hide the topics
+const MAX_NAME_WIDTH: usize = 30;
+/// the widest that the mask column of a list mode can be, so that long masks don't hide the setters
+const MAX_MASK_WIDTH: usize = 40;
+
+/// a channel from a RPL_LIST reply
+#[derive(Debug, Clone)]
+pub struct ListedChannel {
+    pub name: String,
+    pub users: usize,
+    pub topic: String,
+    /// the lowercase name and topic, for searching and sorting without lowercasing every entry on
+    /// each key
+    search_text: String,
+    /// where the lowercase name ends in `search_text`
+    name_end: usize,
+}
+
+impl ListedChannel {
+    pub fn new(name: String, users: usize, topic: String) -> Self {
+        let name_lower = name.to_lowercase();
+        let name_end = name_lower.len();
+        let search_text = format!("{} {}", name_lower, topic.to_lowercase());
+        Self {
+            name,
+            users,
+            topic,
+            search_text,
+            name_end,
+        }
+    }
+
+    fn name_key(&self) -> &str {
+        &self.search_text[..self.name_end]
+    }
+
+    fn topic_key(&self) -> &str {
+        &self.search_text[self.name_end + 1..]
+    }
+}
+
+/// a filter for `/list`. everything except channel names needs the server to support it in
+/// ELIST, though some filters can be applied by the client instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListFilter {
+    /// a channel name without wildcards
+    Channel(ChannelName),
+    /// `*mask*`, only channels that match (ELIST M)
+    Mask(String),
+    /// `!*mask*`, only channels that don't match (ELIST N)
+    NotMask(String),
+    /// `>n` or `<n`, by the number of users (ELIST U)
+    Users { more: bool, count: usize },
+    /// `C>n` or `C<n`, by how many minutes ago the channel was created (ELIST C)
+    Created { more: bool, minutes: u64 },
+    /// `T>n` or `T<n`, by how many minutes ago the topic was changed (ELIST T)
+    TopicChanged { more: bool, minutes: u64 },
+}
+
+impl ListFilter {
+    /// parses a filter like `>10`, `C<60`, `!*spam*` or a channel name
+    pub fn parse(s: &str, casemapping: Casemapping) -> Option<Self> {
+        let number = |n: &str| n.parse::<u64>().ok();
+        fn comparison(s: &str) -> Option<(bool, &str)> {
+            match s.split_at_checked(1) {
+                Some((">", n)) => Some((true, n)),
+                Some(("<", n)) => Some((false, n)),
+                _ => None,
+            }
+        }
+
+        if let Some((more, count)) = comparison(s) {
+            let count = usize::try_from(number(count)?).unwrap_or(usize::MAX);
+            return Some(ListFilter::Users { more, count });
+        }
+        if let Some((more, minutes)) = s.strip_prefix('C').and_then(comparison) {
+            return Some(ListFilter::Created {
+                more,
+                minutes: number(minutes)?,
+            });
+        }
+        if let Some((more, minutes)) = s.strip_prefix('T').and_then(comparison) {
+            return Some(ListFilter::TopicChanged {
+                more,
+                minutes: number(minutes)?,
+            });
+        }
+        if let Some(mask) = s.strip_prefix('!') {
+            return Some(ListFilter::NotMask(mask.to_string()));
+        }
+        if s.contains(['*', '?']) {
+            return Some(ListFilter::Mask(s.to_string()));
+        }
+        ChannelName::new(s, casemapping).map(ListFilter::Channel)
+    }
+
+    /// the ELIST token that the server needs to support the filter, if it needs one
+    pub fn elist_token(&self) -> Option<char> {
+        match self {
+            ListFilter::Channel(_) => None,
+            ListFilter::Mask(_) => Some('M'),
+            ListFilter::NotMask(_) => Some('N'),
+            ListFilter::Users { .. } => Some('U'),
+            ListFilter::Created { .. } => Some('C'),
+            ListFilter::TopicChanged { .. } => Some('T'),
+        }
+    }
+
+    /// whether the server said that it supports the filter
+    pub fn supported(&self, isupport: &ISupport) -> bool {
+        match self.elist_token() {
+            Some(token) => isupport
+                .value("ELIST")
+                .is_some_and(|elist| elist.to_uppercase().contains(token)),
+            None => true,
+        }
+    }
+
+    /// whether the client can apply the filter itself, if the server doesn't support it. the
+    /// replies don't say when channels were created or when their topics were changed.
+    pub fn applies_locally(&self) -> bool {
+        !matches!(
+            self,
+            ListFilter::Created { .. } | ListFilter::TopicChanged { .. }
+        )
+    }
+
+    /// whether a channel passes the filter. filters that can't be applied locally always pass.
+    pub fn matches(&self, channel: &ListedChannel, casemapping: Casemapping) -> bool {
+        match self {
+            ListFilter::Channel(name) => casemapping.same(name.as_str(), &channel.name),
+            ListFilter::Mask(mask) => mode::mask_matches(mask, &channel.name, casemapping),
+            ListFilter::NotMask(mask) => !mode::mask_matches(mask, &channel.name, casemapping),
+            ListFilter::Users { more: true, count } => channel.users > *count,
+            ListFilter::Users { more: false, count } => channel.users < *count,
+            ListFilter::Created { .. } | ListFilter::TopicChanged { .. } => true,
+        }
+    }
+
+    pub fn to_irc_string(&self) -> String {
+        let sign = |more: bool| if more { '>' } else { '<' };
+        match self {
+            ListFilter::Channel(channel) => channel.as_str().to_string(),
+            ListFilter::Mask(mask) => mask.clone(),
+            ListFilter::NotMask(mask) => format!("!{}", mask),
+            ListFilter::Users { more, count } => format!("{}{}", sign(*more), count),
+            ListFilter::Created { more, minutes } => format!("C{}{}", sign(*more), minutes),
+            ListFilter::TopicChanged { more, minutes } => format!("T{}{}", sign(*more), minutes),
+        }
+    }
+}
+
+/// how the LIST window is sorted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListSort {
+    /// the most users first
+    #[default]
+    Users,
+    Name,
+    Topic,
+}
+
+impl ListSort {
+    pub fn next(self) -> Self {
+        match self {
+            ListSort::Users => ListSort::Name,
+            ListSort::Name => ListSort::Topic,
+            ListSort::Topic => ListSort::Users,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ListSort::Users => "users",
+            ListSort::Name => "name",
+            ListSort::Topic => "topic",
+        }
+    }
+}
+
+/// what the LIST window shows
+#[derive(Debug)]
+pub enum ListWindow {
+    Channels(ChannelList),
+    /// the entries of a list mode like bans, from `/banlist` and the like
+    Modes(ModeList),
+}
+
+impl ListWindow {
+    /// whether the window changed since the last call, so it needs to be rendered
+    pub fn take_changed(&mut self) -> bool {
+        match self {
+            ListWindow::Channels(channel_list) => channel_list.take_changed(),
+            ListWindow::Modes(_) => false,
+        }
+    }
+
+    pub fn cycle_sort(&mut self) {
+        if let ListWindow::Channels(channel_list) = self {
+            channel_list.cycle_sort();
+        }
+    }
+
+    /// moves the selection by `offset` rows, staying within the list
+    pub fn move_selection(&mut self, offset: isize) {
+        match self {
+            ListWindow::Channels(channel_list) => channel_list.move_selection(offset),
+            ListWindow::Modes(mode_list) => mode_list.cursor.move_by(offset, mode_list.view.len()),
+        }
+    }
+
+    /// the lines to show in the LIST window, which has `height` rows of `width` columns
+    pub fn rows(&mut self, search: &str, width: u16, height: u16) -> Vec<Line<'static>> {
+        match self {
+            ListWindow::Channels(channel_list) => channel_list.rows(search, width, height),
+            ListWindow::Modes(mode_list) => mode_list.rows(search, width, height),
+        }
+    }
+}
+
+/// the selected row of a list and the first row that is on the screen
+#[derive(Debug, Default)]
+struct Cursor {
+    selected: usize,
+    scroll: usize,
+}
+
+impl Cursor {
+    /// moves the selection by `offset` rows, staying within a list of `len` rows
+    fn move_by(&mut self, offset: isize, len: usize) {
+        let last = len.saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(offset).min(last);
+    }
+
+    /// the rows of a list of `len` rows that fit in `height` rows, scrolling to keep the selected
+    /// row on the screen
+    fn visible(&mut self, len: usize, height: usize) -> Range<usize> {
+        let height = height.max(1);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + height {
+            self.scroll = self.selected + 1 - height;
+        }
+        let start = self.scroll.min(len);
+        start..(start + height).min(len)
+    }
+}
+
+/// the channels in the LIST window. replies can have tens of thousands of channels, so only the
+/// rows on the screen are ever formatted, and the sorted and searched view is only recalculated
+/// when something changes.
+#[derive(Debug, Default)]
+pub struct ChannelList {
+    channels: Vec<ListedChannel>,
+    /// filters that the server didn't support, which are applied to the replies instead
+    local_filters: Vec<ListFilter>,
+    /// how the channel filters compare names
+    casemapping: Casemapping,
+    /// whether RPL_LISTEND has been received
+    pub complete: bool,
+    sort: ListSort,
+    /// indexes into `channels` that match the search, in sorted order
+    view: Vec<usize>,
+    /// the search that `view` was made for, or None if it needs to be recalculated
+    view_search: Option<String>,
+    /// the selected row in `view` and the first one on the screen
+    cursor: Cursor,
+    /// whether channels were added since the window was last rendered
+    changed: bool,
+}
+
+impl ChannelList {
+    pub fn new(local_filters: Vec<ListFilter>, casemapping: Casemapping) -> Self {
+        Self {
+            local_filters,
+            casemapping,
+            ..Self::default()
+        }
+    }
+
+    /// adds a channel from a RPL_LIST reply, unless a local filter excludes it
+    pub fn push(&mut self, channel: ListedChannel) {
+        if self
+            .local_filters
+            .iter()
+            .all(|filter| filter.matches(&channel, self.casemapping))
+        {
+            self.channels.push(channel);
+            self.insert_into_view(self.channels.len() - 1);
+            self.changed = true;
+        }
+    }
+
+    /// adds a channel to the sorted view if it matches the search, instead of recalculating the
+    /// whole view for every reply
+    fn insert_into_view(&mut self, idx: usize) {
+        let Some(search) = &self.view_search else {
+            return;
+        };
+        if !self.channels[idx].search_text.contains(search.as_str()) {
+            return;
+        }
+        // after any equal channels, so that the order is the same as sorting the whole view
+        let pos = self
+            .view
+            .partition_point(|other| self.compare(*other, idx) != Ordering::Greater);
+        self.view.insert(pos, idx);
+        // keep the same channel selected
+        if pos <= self.cursor.selected && self.view.len() > 1 {
+            self.cursor.selected += 1;
+        }
+    }
+
+    /// the order of two channels in the view
+    fn compare(&self, a: usize, b: usize) -> Ordering {
+        let (a, b) = (&self.channels[a], &self.channels[b]);
+        match self.sort {
+            ListSort::Users => (Reverse(a.users), &a.name).cmp(&(Reverse(b.users), &b.name)),
+            ListSort::Name => a.name_key().cmp(b.name_key()),
+            ListSort::Topic => a.topic_key().cmp(b.topic_key()),
+        }
+    }
+
+    /// whether channels were added since the last call, so the window needs to be rendered
+    pub fn take_changed(&mut self) -> bool {
+        core::mem::take(&mut self.changed)
+    }
+
+    pub fn cycle_sort(&mut self) {
+        self.sort = self.sort.next();
+        self.view_search = None;
+    }
+
+    /// moves the selection by `offset` rows, staying within the list
+    pub fn move_selection(&mut self, offset: isize) {
+        self.cursor.move_by(offset, self.view.len());
+    }
+
+    /// the selected channel, if it is a channel that can be joined
+    pub fn selected(&mut self, search: &str, casemapping: Casemapping) -> Option<ChannelName> {
+        self.update_view(search);
+        let idx = *self.view.get(self.cursor.selected)?;
+        ChannelName::new(self.channels[idx].name.as_str(), casemapping)
+    }
+
+    fn update_view(&mut self, search: &str) {
+        let search = search.to_lowercase();
+        if self.view_search.as_ref() == Some(&search) {
+            return;
+        }
+
+        let channels = &self.channels;
+        let mut view = (0..channels.len())
+            .filter(|idx| channels[*idx].search_text.contains(search.as_str()))
+            .collect::<Vec<_>>();
+        view.sort_by(|a, b| self.compare(*a, *b));
+
+        self.view = view;
+        self.view_search = Some(search);
+        self.move_selection(0);
+    }
+
+    /// the lines to show in the LIST window, which has `height` rows of `width` columns
+    pub fn rows(&mut self, search: &str, width: u16, height: u16) -> Vec<Line<'static>> {
+        self.update_view(search);
+
+        let mut header = format!(
+            "{} of {} channels, sorted by {}",
+            self.view.len(),
+            self.channels.len(),
+            self.sort.name()
+        );
+        if !search.is_empty() {
+            header.push_str(format!(", matching {:?}", search).as_str());
+        }
+        if !self.complete {
+            header.push_str(" (loading)");
+        }
+        let mut rows = vec![Line::default().push_themed(Role::Info, header)];
+
+        let visible = self
+            .cursor
+            .visible(self.view.len(), usize::from(height).saturating_sub(1));
+        let first = visible.start;
+        let shown = &self.view[visible];
+        let name_width = shown
+            .iter()
+            .map(|idx| unicode_width::display_width(self.channels[*idx].name.as_str()))
+            .max()
+            .unwrap_or(0)
+            .min(MAX_NAME_WIDTH);
+
+        for (row, idx) in shown.iter().enumerate() {
+            let channel = &self.channels[*idx];
+            let name = pad(channel.name.as_str(), name_width);
+            let text = format!("{} {:>6}  {}", name, channel.users, channel.topic);
+            let line = if first + row == self.cursor.selected {
+                // pad the selected row to the full width so the highlight covers it
+                let text = pad(text.as_str(), usize::from(width));
+                Line::default().push_themed(Role::Selected, text)
+            } else {
+                Line::default()
+                    .push_themed(Role::Channel, name)
+                    .push_unstyled(format!(" {:>6}  {}", channel.users, channel.topic))
+            };
+            rows.push(line);
+        }
+
+        rows
+    }
+}
+
+/// the entries of a list mode of a channel in the LIST window. they are shown in the order that
+/// the server sent them, numbered so that they can be removed with commands like `/unban 2`.
+#[derive(Debug)]
+pub struct ModeList {
+    channel: ChannelName,
+    mode: char,
+    entries: Vec<ListEntry>,
+    /// the lowercase mask and setter of each entry, for searching
+    search_text: Vec<String>,
+    /// indexes into `entries` that match the search
+    view: Vec<usize>,
+    /// the search that `view` was made for, or None if it needs to be recalculated
+    view_search: Option<String>,
+    cursor: Cursor,
+}
+
+impl ModeList {
+    pub fn new(channel: ChannelName, mode: char, entries: Vec<ListEntry>) -> Self {
+        let search_text = entries
+            .iter()
+            .map(|entry| {
+                let setter = entry.setter.as_deref().unwrap_or("");
+                format!("{} {}", entry.mask, setter).to_lowercase()
+            })
+            .collect();
+        Self {
+            channel,
+            mode,
+            entries,
+            search_text,
+            view: Vec::new(),
+            view_search: None,
+            cursor: Cursor::default(),
+        }
+    }
+
+    fn update_view(&mut self, search: &str) {
+        let search = search.to_lowercase();
+        if self.view_search.as_ref() == Some(&search) {
+            return;
+        }
+        self.view = (0..self.entries.len())
+            .filter(|idx| self.search_text[*idx].contains(search.as_str()))
+            .collect();
+        self.view_search = Some(search);
+        self.cursor.move_by(0, self.view.len());
+    }
+
+    fn rows(&mut self, search: &str, width: u16, height: u16) -> Vec<Line<'static>> {
+        self.update_view(search);
+
+        let mut header = format!(
+            "{} for {}, {} of {} entries",
+            mode::list_name(self.mode),
+            self.channel.as_str(),
+            self.view.len(),
+            self.entries.len()
+        );
+        if !search.is_empty() {
+            header.push_str(format!(", matching {:?}", search).as_str());
+        }
+        let mut rows = vec![Line::default().push_themed(Role::Info, header)];
+
+        let visible = self
+            .cursor
+            .visible(self.view.len(), usize::from(height).saturating_sub(1));
+        let first = visible.start;
+        let shown = &self.view[visible];
+        let mask_width = shown
+            .iter()
+            .map(|idx| unicode_width::display_width(self.entries[*idx].mask.as_str()))
+            .max()
+            .unwrap_or(0)
+            .min(MAX_MASK_WIDTH);
+
+        for (row, idx) in shown.iter().enumerate() {
+            let entry = &self.entries[*idx];
+            let number = format!("{:>4}. ", idx + 1);
+            let mask = pad(entry.mask.as_str(), mask_width);
+            let mut details = String::new();
+            if let Some(setter) = &entry.setter {
+                details.push_str(format!("  set by {}", setter).as_str());
+            }
+            if let Some(time) = entry.time {
+                let time = time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S");
+                details.push_str(format!(" at {}", time).as_str());
+            }
+
+            let line = if first + row == self.cursor.selected {
+                // pad the selected row to the full width so the highlight covers it
+                let text = format!("{}{}{}", number, mask, details);
+                let text = pad(text.as_str(), usize::from(width));
+                Line::default().push_themed(Role::Selected, text)
+            } else {
+                Line::default()
+                    .push_unstyled(number)
+                    .push_themed(Role::Channel, mask)
+                    .push_unstyled(details)
+            };
+            rows.push(line);
+        }
+
+        rows
+    }
+}
+
+/// pads or cuts off text to exactly `width` columns
+fn pad(text: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let c_width = unicode_width::display_width(c.encode_utf8(&mut [0; 4]));
+        if used + c_width > width {
+            break;
+        }
+        out.push(c);
+        used += c_width;
+    }
+    out.push_str(" ".repeat(width - used).as_str());
+    out
+}
@@ -1,30 +1,77 @@
+use core::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+};
+
+use crate::casemap::Casemapping;
+
 mod channel;
-mod mode;
+pub mod list;
+pub mod mode;
 mod user;
 
-pub use channel::{Channel, ChannelCreationErr, ChannelKind};
+pub use channel::{BanMask, Channel, ChannelKind, Member};
 pub use user::{Nickname, UserMessages};
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-/// the name of a channel, including the channel type character (typically `#`)
+#[derive(Debug, Clone)]
+/// the name of a channel, including the channel type character (typically `#`). names are
+/// compared with the server's casemapping, so `#Rust` and `#rust` are the same channel.
 pub struct ChannelName {
     /// the full name of the channel, including the kind character
     name: String,
-    kind: ChannelKind,
+    /// the name lowercased with the server's casemapping
+    key: String,
 }
 
 impl ChannelName {
-    pub fn new(name: impl Into<String>) -> Option<Self> {
+    pub fn new(name: impl Into<String>, casemapping: Casemapping) -> Option<Self> {
         let name: String = name.into();
 
         // if no kind character, not valid
         let kind_char = name.chars().next()?;
-        let kind = ChannelKind::parse(kind_char)?;
+        ChannelKind::parse(kind_char)?;
 
-        Some(Self { name, kind })
+        Some(Self {
+            key: casemapping.lower(&name),
+            name,
+        })
     }
 
     pub fn as_str(&self) -> &str {
         self.name.as_str()
     }
+
+    /// the same name, compared with a different casemapping
+    pub fn rekey(&self, casemapping: Casemapping) -> Self {
+        Self {
+            name: self.name.clone(),
+            key: casemapping.lower(&self.name),
+        }
+    }
+}
+
+impl PartialEq for ChannelName {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for ChannelName {}
+
+impl Hash for ChannelName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl PartialOrd for ChannelName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ChannelName {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
 }
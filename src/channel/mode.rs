@@ -1 +1,450 @@
-pub struct ChannelMode {}
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::{casemap::Casemapping, isupport::ISupport};
+
+/// the modes that servers are assumed to support if they don't send CHANMODES
+const DEFAULT_CHANMODES: &str = "beI,k,l,imnpst";
+/// the membership modes that servers are assumed to support if they don't send PREFIX
+const DEFAULT_PREFIX: &str = "(ov)@+";
+
+/// how a channel mode is set, and whether it takes a param
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeKind {
+    /// type A, a list of masks like bans. always takes a param.
+    List,
+    /// type B, a setting that always takes a param, like the channel key
+    AlwaysParam,
+    /// type C, a setting that only takes a param when it's set, like the user limit
+    ParamWhenSet,
+    /// type D, a flag that never takes a param
+    NoParam,
+    /// a membership mode like op, which gives the user in the param a prefix
+    Prefix(char),
+}
+
+/// the types of channel modes that the server supports, from ISUPPORT CHANMODES and PREFIX. type
+/// D modes aren't stored, since any mode that isn't another type is treated as one.
+#[derive(Debug, Clone)]
+pub struct ModeRules {
+    list: String,
+    always_param: String,
+    param_when_set: String,
+    /// membership modes and their prefixes, from the highest rank to the lowest
+    prefixes: Vec<(char, char)>,
+    /// how the nicks in the params of membership modes are compared
+    casemapping: Casemapping,
+}
+
+impl ModeRules {
+    pub fn new(isupport: &ISupport) -> Self {
+        let chanmodes = isupport.value("CHANMODES").unwrap_or(DEFAULT_CHANMODES);
+        let mut types = chanmodes.split(',').map(str::to_string);
+        let mut next = || types.next().unwrap_or_default();
+        let (list, always_param, param_when_set) = (next(), next(), next());
+
+        let prefix = isupport.value("PREFIX").unwrap_or(DEFAULT_PREFIX);
+        let prefixes = prefix
+            .strip_prefix('(')
+            .and_then(|prefix| prefix.split_once(')'))
+            .map(|(modes, prefixes)| modes.chars().zip(prefixes.chars()).collect())
+            .unwrap_or_default();
+
+        Self {
+            list,
+            always_param,
+            param_when_set,
+            prefixes,
+            casemapping: isupport.casemapping(),
+        }
+    }
+
+    pub fn casemapping(&self) -> Casemapping {
+        self.casemapping
+    }
+
+    pub fn kind(&self, mode: char) -> ModeKind {
+        if let Some((_, prefix)) = self.prefixes.iter().find(|(m, _)| *m == mode) {
+            ModeKind::Prefix(*prefix)
+        } else if self.list.contains(mode) {
+            ModeKind::List
+        } else if self.always_param.contains(mode) {
+            ModeKind::AlwaysParam
+        } else if self.param_when_set.contains(mode) {
+            ModeKind::ParamWhenSet
+        } else {
+            // NOTE: unknown modes can't be parsed correctly, so they are assumed to not have a param
+            // like type D modes, since that's the most common kind
+            ModeKind::NoParam
+        }
+    }
+
+    /// the rank of a membership prefix, where lower is a higher rank
+    pub fn prefix_rank(&self, prefix: char) -> Option<usize> {
+        self.prefixes.iter().position(|(_, p)| *p == prefix)
+    }
+
+    /// the membership prefixes, from the highest rank to the lowest
+    pub fn prefix_chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.prefixes.iter().map(|(_, prefix)| *prefix)
+    }
+}
+
+/// a single mode being set or unset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeChange {
+    pub adding: bool,
+    pub mode: char,
+    pub kind: ModeKind,
+    pub param: Option<String>,
+}
+
+/// parses a mode string like `+o-v alice bob` into the changes it makes
+pub fn parse_changes(rules: &ModeRules, modestring: &str, params: &[&str]) -> Vec<ModeChange> {
+    let mut params = params.iter();
+    let mut adding = true;
+    let mut changes = Vec::new();
+    for mode in modestring.chars() {
+        match mode {
+            '+' => adding = true,
+            '-' => adding = false,
+            mode => {
+                let kind = rules.kind(mode);
+                let takes_param = match kind {
+                    ModeKind::List | ModeKind::AlwaysParam | ModeKind::Prefix(_) => true,
+                    ModeKind::ParamWhenSet => adding,
+                    ModeKind::NoParam => false,
+                };
+                let param = if takes_param {
+                    params.next().map(|param| param.to_string())
+                } else {
+                    None
+                };
+
+                changes.push(ModeChange {
+                    adding,
+                    mode,
+                    kind,
+                    param,
+                });
+            }
+        }
+    }
+    changes
+}
+
+/// applies changes to our own user modes, which never have params
+pub fn apply_user_changes(modes: &mut String, changes: &[ModeChange]) {
+    for change in changes {
+        if change.adding {
+            if !modes.contains(change.mode) {
+                modes.push(change.mode);
+            }
+        } else {
+            modes.retain(|mode| mode != change.mode);
+        }
+    }
+}
+
+/// an entry in a list mode like bans
+#[derive(Debug, Clone)]
+pub struct ListEntry {
+    pub mask: String,
+    /// who added the entry, which may be a nick or a full `nick!user@host`
+    pub setter: Option<String>,
+    pub time: Option<DateTime<Utc>>,
+}
+
+impl ListEntry {
+    /// whether the mask of the entry matches a full `nick!user@host`
+    pub fn matches(&self, hostmask: &str, casemapping: Casemapping) -> bool {
+        mask_matches(&self.mask, hostmask, casemapping)
+    }
+}
+
+/// whether a mask with wildcards matches a string, comparing them with the server's casemapping
+pub fn mask_matches(mask: &str, s: &str, casemapping: Casemapping) -> bool {
+    let mask = casemapping.lower(mask).chars().collect::<Vec<_>>();
+    let s = casemapping.lower(s).chars().collect::<Vec<_>>();
+    wildcard_match(&mask, &s)
+}
+
+/// matches a mask where `*` matches any number of characters and `?` matches any one character
+fn wildcard_match(mask: &[char], s: &[char]) -> bool {
+    let (mut m, mut i) = (0, 0);
+    // the position of the last `*` in the mask, and where in `s` it started matching
+    let mut star = None;
+    while i < s.len() {
+        match mask.get(m) {
+            Some('*') => {
+                star = Some((m, i));
+                m += 1;
+            }
+            Some(c) if *c == '?' || *c == s[i] => {
+                m += 1;
+                i += 1;
+            }
+            // backtrack, making the last `*` match one more character
+            _ => match star {
+                Some((star_m, star_i)) => {
+                    m = star_m + 1;
+                    i = star_i + 1;
+                    star = Some((star_m, star_i + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    mask[m..].iter().all(|c| *c == '*')
+}
+
+/// the name of a list mode's list, for showing to the user
+pub fn list_name(mode: char) -> String {
+    match mode {
+        'b' => String::from("ban list"),
+        'e' => String::from("ban exception list"),
+        'I' => String::from("invite exception list"),
+        mode => format!("+{} list", mode),
+    }
+}
+
+/// the modes of a channel, not including membership modes, which are stored on each member
+#[derive(Debug, Default)]
+pub struct ChannelModes {
+    /// the settings and flags that are set, with their param if they have one
+    pub settings: BTreeMap<char, Option<String>>,
+    /// list modes like bans, from the mode to its entries
+    pub lists: BTreeMap<char, Vec<ListEntry>>,
+}
+
+impl ChannelModes {
+    /// applies a change that isn't a membership mode. masks in list modes are compared with the
+    /// server's casemapping.
+    pub fn apply(&mut self, change: &ModeChange, setter: Option<&str>, casemapping: Casemapping) {
+        match (change.kind, change.adding) {
+            (ModeKind::Prefix(_), _) => {}
+            (ModeKind::List, adding) => {
+                // a list mode without a mask is a request for the list, not a change
+                let Some(mask) = &change.param else {
+                    return;
+                };
+                let list = self.lists.entry(change.mode).or_default();
+                list.retain(|entry| !casemapping.same(&entry.mask, mask));
+                if adding {
+                    list.push(ListEntry {
+                        mask: mask.clone(),
+                        setter: setter.map(str::to_string),
+                        time: Some(Utc::now()),
+                    });
+                }
+            }
+            (_, true) => {
+                self.settings.insert(change.mode, change.param.clone());
+            }
+            (_, false) => {
+                self.settings.remove(&change.mode);
+            }
+        }
+    }
+
+    /// formats the settings like `+ntk key`
+    pub fn settings_string(&self) -> String {
+        let modes = self.settings.keys().collect::<String>();
+        let params = self
+            .settings
+            .values()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if params.is_empty() {
+            format!("+{}", modes)
+        } else {
+            format!("+{} {}", modes, params.join(" "))
+        }
+    }
+}
+
+/// describes a change as the action of whoever made it, like `gives op to alice`
+pub fn describe(change: &ModeChange) -> String {
+    let sign = if change.adding { '+' } else { '-' };
+    let param = change.param.as_deref().unwrap_or("");
+    match (change.mode, change.kind, change.adding) {
+        (_, ModeKind::Prefix(_), adding) => {
+            let name = match change.mode {
+                'q' => "owner",
+                'a' => "admin",
+                'o' => "op",
+                'h' => "halfop",
+                'v' => "voice",
+                _ => return format!("sets mode {}{} on {}", sign, change.mode, param),
+            };
+            if adding {
+                format!("gives {} to {}", name, param)
+            } else {
+                format!("removes {} from {}", name, param)
+            }
+        }
+        ('b', ModeKind::List, true) => format!("bans {}", param),
+        ('b', ModeKind::List, false) => format!("unbans {}", param),
+        ('e', ModeKind::List, true) => format!("adds ban exception {}", param),
+        ('e', ModeKind::List, false) => format!("removes ban exception {}", param),
+        ('I', ModeKind::List, true) => format!("adds invite exception {}", param),
+        ('I', ModeKind::List, false) => format!("removes invite exception {}", param),
+        (_, ModeKind::List, true) => format!("adds {} to the +{} list", param, change.mode),
+        (_, ModeKind::List, false) => format!("removes {} from the +{} list", param, change.mode),
+        ('k', _, true) => format!("sets the channel key to {}", param),
+        ('k', _, false) => String::from("removes the channel key"),
+        ('l', _, true) => format!("sets the user limit to {}", param),
+        ('l', _, false) => String::from("removes the user limit"),
+        (mode, _, _) => {
+            let mut line = format!("sets mode {}{}", sign, mode);
+            if let Some(name) = flag_name(mode) {
+                line.push_str(format!(" ({})", name).as_str());
+            }
+            if !param.is_empty() {
+                line.push(' ');
+                line.push_str(param);
+            }
+            line
+        }
+    }
+}
+
+/// the meaning of common flags, which are mostly the same across servers
+fn flag_name(mode: char) -> Option<&'static str> {
+    match mode {
+        'i' => Some("invite only"),
+        'm' => Some("moderated"),
+        'n' => Some("no external messages"),
+        'p' => Some("private"),
+        's' => Some("secret"),
+        't' => Some("only ops can change the topic"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(modestring: &str, params: &[&str]) -> Vec<(bool, char, ModeKind, Option<String>)> {
+        let rules = ModeRules::new(&ISupport::default());
+        parse_changes(&rules, modestring, params)
+            .into_iter()
+            .map(|change| (change.adding, change.mode, change.kind, change.param))
+            .collect()
+    }
+
+    fn param(s: &str) -> Option<String> {
+        Some(s.to_string())
+    }
+
+    fn matches(mask: &str, s: &str) -> bool {
+        let mask = mask.chars().collect::<Vec<_>>();
+        let s = s.chars().collect::<Vec<_>>();
+        wildcard_match(&mask, &s)
+    }
+
+    #[test]
+    fn params_by_kind() {
+        assert_eq!(
+            parse("+o-v+kb", &["alice", "bob", "key", "*!*@host"]),
+            [
+                (true, 'o', ModeKind::Prefix('@'), param("alice")),
+                (false, 'v', ModeKind::Prefix('+'), param("bob")),
+                (true, 'k', ModeKind::AlwaysParam, param("key")),
+                (true, 'b', ModeKind::List, param("*!*@host")),
+            ]
+        );
+        // type B and list modes take a param when they're unset, but type C modes don't
+        assert_eq!(
+            parse("-kbl+l", &["key", "mask", "5"]),
+            [
+                (false, 'k', ModeKind::AlwaysParam, param("key")),
+                (false, 'b', ModeKind::List, param("mask")),
+                (false, 'l', ModeKind::ParamWhenSet, None),
+                (true, 'l', ModeKind::ParamWhenSet, param("5")),
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_and_unknown_modes() {
+        // unknown modes are assumed to not take a param
+        assert_eq!(
+            parse("+nX-t", &["ignored"]),
+            [
+                (true, 'n', ModeKind::NoParam, None),
+                (true, 'X', ModeKind::NoParam, None),
+                (false, 't', ModeKind::NoParam, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn too_few_params() {
+        assert_eq!(
+            parse("+ovl", &["alice"]),
+            [
+                (true, 'o', ModeKind::Prefix('@'), param("alice")),
+                (true, 'v', ModeKind::Prefix('+'), None),
+                (true, 'l', ModeKind::ParamWhenSet, None),
+            ]
+        );
+        // a list mode without a mask asks for the list
+        assert_eq!(parse("b", &[]), [(true, 'b', ModeKind::List, None)]);
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("a*b*c", "abc"));
+        assert!(matches("a*b*c", "aXXbYbYc"));
+        assert!(!matches("a*b*c", "acb"));
+        assert!(!matches("a*b*c", "abcd"));
+        assert!(matches("*?", "a"));
+        assert!(matches("*?", "abc"));
+        assert!(!matches("*?", ""));
+        assert!(matches("abc*", "abc"));
+        assert!(matches("abc*", "abcdef"));
+        assert!(!matches("abc*", "ab"));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "ac"));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+        assert!(matches("*", ""));
+        assert!(matches("**", "abc"));
+    }
+
+    #[test]
+    fn masks_use_casemapping() {
+        assert!(mask_matches(
+            "*!*@Host",
+            "nick!user@host",
+            Casemapping::Rfc1459
+        ));
+        assert!(mask_matches("[a]*!*@*", "{A}b!u@h", Casemapping::Rfc1459));
+        assert!(!mask_matches("[a]*!*@*", "{A}b!u@h", Casemapping::Ascii));
+    }
+
+    #[test]
+    fn list_entries_use_casemapping() {
+        let rules = ModeRules::new(&ISupport::default());
+        let mut modes = ChannelModes::default();
+        for change in parse_changes(&rules, "+bb-b", &["*!*@host", "[a]!*@*", "*!*@Host"]) {
+            modes.apply(&change, Some("op"), rules.casemapping());
+        }
+        let masks = modes.lists[&'b']
+            .iter()
+            .map(|entry| entry.mask.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(masks, ["[a]!*@*"]);
+
+        // adding a mask that is already in the list replaces it
+        for change in parse_changes(&rules, "+b", &["{A}!*@*"]) {
+            modes.apply(&change, Some("op"), rules.casemapping());
+        }
+        assert_eq!(modes.lists[&'b'].len(), 1);
+        assert_eq!(modes.lists[&'b'][0].mask, "{A}!*@*");
+    }
+}
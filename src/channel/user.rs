@@ -1,18 +1,29 @@
-use std::collections::VecDeque;
+use core::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+};
+use std::collections::{HashSet, VecDeque};
 
 use crate::{
+    casemap::Casemapping,
     constants::names::{
         CHANNEL_MEMBERSHIP_PREFIXES, INVALID_NICKNAME_CHARACTERS, INVALID_NICKNAME_START,
     },
+    state,
     ui::text::Line,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-/// the nickname of a user
-pub struct Nickname(String);
+#[derive(Debug, Clone)]
+/// the nickname of a user. nicks are compared with the server's casemapping, so `Alice` and
+/// `alice` are the same user.
+pub struct Nickname {
+    nick: String,
+    /// the nick lowercased with the server's casemapping
+    key: String,
+}
 
 impl Nickname {
-    pub fn new(nick: impl Into<String>) -> Option<Self> {
+    pub fn new(nick: impl Into<String>, casemapping: Casemapping) -> Option<Self> {
         let nick: String = nick.into();
 
         let Some(first) = nick.chars().next() else {
@@ -25,14 +36,51 @@ impl Nickname {
                 .chars()
                 .any(|c| INVALID_NICKNAME_CHARACTERS.contains(&c))
         {
-            Some(Self(nick))
+            Some(Self {
+                key: casemapping.lower(&nick),
+                nick,
+            })
         } else {
             None
         }
     }
 
     pub fn as_str(&self) -> &str {
-        self.0.as_str()
+        self.nick.as_str()
+    }
+
+    /// the same nick, compared with a different casemapping
+    pub fn rekey(&self, casemapping: Casemapping) -> Self {
+        Self {
+            nick: self.nick.clone(),
+            key: casemapping.lower(&self.nick),
+        }
+    }
+}
+
+impl PartialEq for Nickname {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Nickname {}
+
+impl Hash for Nickname {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl PartialOrd for Nickname {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Nickname {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
     }
 }
 
@@ -40,6 +88,11 @@ impl Nickname {
 pub struct UserMessages {
     nick: Nickname,
     pub messages: VecDeque<Line<'static>>,
+    /// the msgids of the lines in `messages`
+    msgids: HashSet<String>,
+    /// the away message that was last shown for the user, so that it isn't repeated for every
+    /// message sent to them
+    pub away_message: Option<String>,
 }
 
 impl UserMessages {
@@ -47,11 +100,13 @@ impl UserMessages {
         Self {
             nick,
             messages: VecDeque::new(),
+            msgids: HashSet::new(),
+            away_message: None,
         }
     }
 
     pub fn add_line(&mut self, line: Line<'static>) {
-        self.messages.push_back(line);
+        state::push_line(&mut self.messages, &mut self.msgids, line);
     }
 
     pub fn iter_lines(&self) -> impl DoubleEndedIterator<Item = &Line<'_>> {
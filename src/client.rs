@@ -11,26 +11,35 @@ use std::{
         Arc, Mutex,
     },
     thread,
+    time::Instant,
 };
 
+use chrono::Utc;
 use eyre::{bail, eyre, Context};
 use log::*;
 use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore, StreamOwned};
 use thiserror::Error;
 
 use crate::{
-    command::Command,
+    alias::{self, AliasContext},
+    channel::list::ListWindow,
+    command::{Command, CommandParseErr},
+    config::Config,
     ext::*,
+    handlers::{batch, echo, notify, who},
     irc::{
         self,
         client::{ClientIrcCommand, ClientMessage},
         IrcMessage,
     },
     net::ServerIo,
-    state::{ClientState, ConnectedState, ConnectionState},
+    script::{self, ScriptHost},
+    state::{ClientState, ConnectedState, ConnectionState, RegistrationState},
     targets::Target,
     ui::{
+        format,
         layout::{Direction, Layout, Section, SectionKind},
+        template::Kind,
         term::TerminalUi,
     },
     util,
@@ -48,12 +57,16 @@ pub enum ExitReason {
 
 pub static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
 
+/// the directory inside the config directory that scripts are loaded from
+const SCRIPT_DIR: &str = "scripts";
+
 /// spawns threads for the reading and writing parts of the client and begins processing the
 /// connection.
 pub fn start(
     addr: &str,
     nick: &str,
     tls: bool,
+    config: Config,
     init: impl Fn(&Sender<ClientMessage>) -> eyre::Result<()>,
 ) -> Result<!, ExitReason> {
     let Some((name, _)) = addr.split_once(':') else {
@@ -89,6 +102,10 @@ pub fn start(
     let layout = Layout {
         direction: Direction::Vertical,
         sections: vec![
+            // topic bar
+            Section::Leaf {
+                kind: SectionKind::Exact(1),
+            },
             Section::Leaf {
                 kind: SectionKind::Fill(1),
             },
@@ -105,9 +122,13 @@ pub fn start(
         write_sender.clone(),
         TerminalUi::new(layout, io::stdout())?,
         nick.to_string(),
+        ScriptHost::new(config.dir().join(SCRIPT_DIR)),
+        config,
     );
     // draw the status page immediately
     state.render()?;
+    let actions = state.scripts.reload();
+    script::apply(&mut state, actions);
     let state = Arc::new(Mutex::new(state));
 
     // stream reader and writer thread
@@ -150,7 +171,9 @@ pub fn start(
                 match res {
                     Ok(()) => {}
                     Err(e) => {
-                        state.lock().unwrap().ui.error(e.to_string()).unwrap();
+                        let state = &mut *state.lock().unwrap();
+                        echo::disconnected(state);
+                        state.ui.error(e.to_string()).unwrap();
                         return;
                     }
                 }
@@ -202,6 +225,7 @@ pub fn start(
 
     // main code that processes state as messages come in
     // TODO: do processing on a thread too
+    let mut last_timer_poll = Instant::now();
     loop {
         if QUIT_REQUESTED.load(atomic::Ordering::Relaxed) {
             let ui = &mut state.lock().unwrap().ui;
@@ -209,6 +233,24 @@ pub fn start(
             return Err(ExitReason::Quit);
         }
 
+        // only check script timers, queued WHOs, the notify list, open batches, echoes, auto-away
+        // and the LIST window occasionally so that the state mutex isn't constantly locked
+        const TIMER_POLL_DELAY: Duration = Duration::from_millis(50);
+        if last_timer_poll.elapsed() >= TIMER_POLL_DELAY {
+            last_timer_poll = Instant::now();
+            let state = &mut *state.lock().unwrap();
+            let actions = state.scripts.poll_timers();
+            script::apply(state, actions);
+            who::send_queued(state);
+            notify::poll(state);
+            batch::expire(state)?;
+            echo::expire(state);
+            state.check_idle();
+            if state.list_window().is_some_and(ListWindow::take_changed) {
+                let _ = state.render();
+            }
+        }
+
         let msg = match msg_receiver.try_recv() {
             Ok(msg) => msg,
             Err(_) => continue,
@@ -216,11 +258,50 @@ pub fn start(
 
         let state = &mut *state.lock().unwrap();
         msg.handle(state)?;
+        // scripts see messages after the client has handled them, so that the state is up to date
+        let actions = state.scripts.on_message(&msg);
+        script::apply(state, actions);
         trace!("state after handling {:#?}", state.conn_state);
     }
 }
 
-fn handle_input(
+pub fn handle_input(
+    state: &mut ClientState,
+    sender: &Sender<ClientMessage>,
+    input: &str,
+) -> eyre::Result<()> {
+    // aliases are resolved before built in commands, so that they can replace them
+    if let Some((_, cmd)) = input.split_prefix('/') {
+        let (name, args) = cmd.split_once(' ').unwrap_or((cmd, ""));
+        if state.config.alias(name).is_some() {
+            let nick = match &state.conn_state {
+                ConnectionState::Registration(RegistrationState { requested_nick, .. }) => {
+                    requested_nick.as_str()
+                }
+                ConnectionState::Connected(ConnectedState { nick, .. }) => nick.as_str(),
+            };
+            let channel = match state.current_target() {
+                Target::Channel(channel) => Some(channel.as_str()),
+                _ => None,
+            };
+
+            match alias::expand(&state.config, name, args, &AliasContext { nick, channel }) {
+                Ok(lines) => {
+                    for line in lines {
+                        handle_expanded_input(state, sender, line.as_str())?;
+                    }
+                }
+                Err(e) => state.error(e.to_string()),
+            }
+            return Ok(());
+        }
+    }
+
+    handle_expanded_input(state, sender, input)
+}
+
+/// handles a line of input that does not use an alias
+fn handle_expanded_input(
     state: &mut ClientState,
     sender: &Sender<ClientMessage>,
     input: &str,
@@ -228,7 +309,7 @@ fn handle_input(
     // ui.debug(format!("input: {}", input))?;
 
     match input.split_prefix('/') {
-        Some((_, input)) => match Command::parse(input) {
+        Some((_, input)) => match Command::parse(input, state.casemapping()) {
             Ok(cmd) => {
                 match cmd.handle(state, sender) {
                     Ok(()) => {}
@@ -239,12 +320,46 @@ fn handle_input(
                 // even if the command cannot be handled, that's not a fatal error
                 Ok(())
             }
+            // commands that the client doesn't know may have been defined by a script
+            Err(CommandParseErr::UnknownCommand(name)) if state.scripts.has_command(&name) => {
+                let args = input.split_once(' ').map_or("", |(_, args)| args);
+                let actions = state.scripts.run_command(&name, args);
+                script::apply(state, actions);
+                Ok(())
+            }
             Err(e) => {
                 state.error(format!("failed to parse command: {}", e));
                 // failure to parse is never fatal
                 Ok(())
             }
         },
+        // in the LIST window, the input is a search and pressing enter joins the selected channel
+        None if *state.current_target() == Target::List => {
+            let casemapping = state.casemapping();
+            let selected = match state.list_window() {
+                Some(ListWindow::Channels(channel_list)) => {
+                    channel_list.selected(input, casemapping)
+                }
+                // the entries of list modes are removed with commands like `/unban 2`
+                Some(ListWindow::Modes(_)) => {
+                    state.error(String::from(
+                        "entries are removed by their number, like /unban 2",
+                    ));
+                    return Ok(());
+                }
+                None => None,
+            };
+            let Some(channel) = selected else {
+                state.error(String::from("no channel selected"));
+                return Ok(());
+            };
+            sender
+                .send(ClientMessage::from_command(ClientIrcCommand::Join(vec![(
+                    channel, None,
+                )])))
+                .wrap_err("failed to send join to writer thread")?;
+            Ok(())
+        }
         None => {
             let ConnectionState::Connected(ConnectedState { nick, .. }) = &mut state.conn_state
             else {
@@ -255,7 +370,7 @@ fn handle_input(
             let nick = nick.clone();
 
             let target = match state.current_target() {
-                Target::Status => {
+                Target::Status | Target::List => {
                     state.error(String::from("cannot send message to status"));
                     return Ok(());
                 }
@@ -264,19 +379,19 @@ fn handle_input(
             };
             debug!("sending to {:?}", target);
 
-            let line = util::line_now()
-                .join(util::message_nick_line(nick.as_str(), true))
-                .push_unstyled(input);
-            state.add_line(state.current_target().clone(), line);
-
-            sender
-                .send(ClientMessage::from_command(ClientIrcCommand::Privmsg {
-                    targets: vec![target],
-                    msg: input.to_string(),
-                }))
-                .wrap_err("failed to send privmsg to writer thread")?;
-
-            Ok(())
+            let line = util::message_line(
+                Kind::Message,
+                Utc::now(),
+                util::nick_line(&state.config.nick_colors, nick.as_str(), true),
+                format::parse(input, state.config.formatting),
+            );
+            let command = ClientIrcCommand::Privmsg {
+                targets: vec![target],
+                msg: input.to_string(),
+            };
+            let window = state.current_target().clone();
+            echo::send(state, sender, window, command, line)
+                .wrap_err("failed to send privmsg to writer thread")
         }
     }
 }
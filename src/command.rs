@@ -1,15 +1,36 @@
 use core::sync::atomic;
 use std::sync::mpsc::Sender;
 
+use chrono::Utc;
 use eyre::eyre;
 use log::*;
 use thiserror::Error;
 
 use crate::{
-    channel::{ChannelName, Nickname},
-    irc::client::{ClientIrcCommand, ClientMessage},
+    casemap::Casemapping,
+    channel::{
+        list::{ChannelList, ListFilter, ListWindow},
+        BanMask, ChannelName, Nickname,
+    },
+    constants::names::CHANNEL_TYPES,
+    handlers::{
+        ctcp::{self, CtcpCommand},
+        echo, notify, who,
+    },
+    irc::{
+        self,
+        client::{ClientIrcCommand, ClientMessage},
+    },
+    isupport::ISupport,
+    script,
     state::{ClientState, ConnectedState, ConnectionState},
     targets::Target,
+    ui::{
+        format,
+        template::Kind,
+        theme::{self, Role, Theme},
+    },
+    util,
 };
 
 macro_rules! expect_connected_state {
@@ -23,13 +44,107 @@ macro_rules! expect_connected_state {
 
 #[derive(Debug)]
 pub enum Command {
-    Join(String),
+    /// join channels, with the keys to join them with
+    Join(Vec<(ChannelName, Option<String>)>),
+    /// send a CTCP query to a target, with the command and its params
     Ctcp(Target, String),
     /// send raw text to the IRC server
     Raw(String),
-    /// start a private message with the specified user
-    Msg(Nickname),
+    /// send a message to a target
+    Msg(Target, String),
+    /// start a private message with the specified user, optionally sending them a message
+    Query(Nickname, Option<String>),
+    /// send a CTCP ACTION to the current window
+    Me(String),
+    Notice(Target, String),
     Quit,
+    /// manage the loaded scripts
+    Script(ScriptCommand),
+    /// list all aliases with no name, show an alias with only a name, or define an alias
+    Alias(Option<String>, Option<String>),
+    /// remove an alias
+    Unalias(String),
+    /// list the themes with no name, or switch to a theme
+    Theme(Option<String>),
+    /// leave a channel, defaulting to the current channel, with an optional reason
+    Part(Option<ChannelName>, Option<String>),
+    /// close the window for a target, defaulting to the current window, leaving it if it's a
+    /// joined channel
+    Close(Option<Target>),
+    /// show the topic of a channel, or set it if there's text. a topic can start with a channel
+    /// name, so the channel is only used if it has been joined.
+    Topic {
+        channel: Option<ChannelName>,
+        topic: Option<String>,
+        /// all of the text, for when the channel turns out to be the start of the topic
+        text: Option<String>,
+    },
+    Names(Option<ChannelName>),
+    /// open the LIST window with the channels matching all of the filters
+    List(Vec<ListFilter>),
+    Invite(Nickname, Option<ChannelName>),
+    /// join the channel that we were most recently invited to
+    Accept,
+    Notify(NotifyCommand),
+    Kick {
+        channel: Option<ChannelName>,
+        nick: Nickname,
+        reason: Option<String>,
+    },
+    /// look up a user, optionally asking a specific server
+    Whois(Option<String>, Nickname),
+    /// look up a user that has disconnected, with the max number of entries to show
+    Whowas(Nickname, Option<u16>),
+    /// mark ourselves as away with a message
+    Away(Option<String>),
+    /// mark ourselves as no longer away
+    Back,
+    /// list the users matching a mask, or in the current window if there is none
+    Who(Option<String>),
+    /// show or change the modes of a channel or user
+    Mode(Option<Target>, Option<String>),
+    /// ban users or masks. users are banned using their known hostmask.
+    Ban {
+        channel: Option<ChannelName>,
+        kind: BanMask,
+        targets: Vec<String>,
+    },
+    /// remove bans by their index in the ban list, their mask, or the user they match
+    Unban {
+        channel: Option<ChannelName>,
+        targets: Vec<String>,
+    },
+    /// request a list mode of a channel, like the ban list
+    ModeList {
+        channel: Option<ChannelName>,
+        mode: char,
+    },
+    /// add or remove a mode that takes a param for each of the params, for things like op
+    ChannelModes {
+        channel: Option<ChannelName>,
+        adding: bool,
+        mode: char,
+        params: Vec<String>,
+    },
+}
+
+#[derive(Debug)]
+pub enum ScriptCommand {
+    /// unload all scripts and load them from the script directory again
+    Reload,
+    /// list the scripts that are loaded
+    List,
+}
+
+/// a change or query of the notify list
+#[derive(Debug)]
+pub enum NotifyCommand {
+    /// show the nicks in the notify list and whether they're online
+    List,
+    Add(Vec<Nickname>),
+    Del(Vec<Nickname>),
+    /// check whether the nicks are online now
+    Refresh,
 }
 
 #[derive(Debug, Error)]
@@ -43,7 +158,7 @@ pub enum CommandParseErr {
 }
 
 impl Command {
-    pub fn parse<S: AsRef<str>>(s: S) -> Result<Self, CommandParseErr> {
+    pub fn parse<S: AsRef<str>>(s: S, casemapping: Casemapping) -> Result<Self, CommandParseErr> {
         let s = s.as_ref();
         let (cmd, args_str) = s.split_once(' ').unwrap_or((s, ""));
         let args = args_str
@@ -53,24 +168,40 @@ impl Command {
 
         match cmd.to_lowercase().as_str() {
             "join" => {
-                if args.len() != 1 {
-                    return Err(CommandParseErr::IncorrectArgCount(1, args.len() as u8));
-                }
-
-                // note: channels.len() is at least one, split always returns at least one element
-                let channels = args[0].split(',').collect::<Vec<_>>();
-                if channels.len() > 1 {
-                    // TODO
-                }
+                let (channels, keys) = match args.as_slice() {
+                    [channels] => (*channels, None),
+                    [channels, keys] => (*channels, Some(*keys)),
+                    _ => return Err(CommandParseErr::IncorrectArgCount(1, args.len() as u8)),
+                };
 
-                let channel = channels[0];
-                if channel.is_empty() {
-                    // NOTE: users are required to put the channel prefix, so an empty channel name
-                    // is a bug
-                    // TODO: err
+                // NOTE: users are required to put the channel prefix, so an empty channel name is
+                // always a mistake
+                let channels = channels
+                    .split(',')
+                    .map(|channel| parse_channel(channel, casemapping))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // keys are matched to channels by their position
+                let keys = keys.map_or(Vec::new(), |keys| keys.split(',').collect::<Vec<_>>());
+                if keys.len() > channels.len() {
+                    return Err(CommandParseErr::InvalidArg(
+                        keys.join(","),
+                        format!("at most {} keys", channels.len()),
+                    ));
                 }
 
-                Ok(Command::Join(channels[0].to_string()))
+                let channels = channels
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, channel)| {
+                        // an empty key can be used to skip a channel that has no key
+                        let key = keys
+                            .get(idx)
+                            .filter(|key| !key.is_empty())
+                            .map(|key| key.to_string());
+                        (channel, key)
+                    })
+                    .collect();
+                Ok(Command::Join(channels))
             }
             "raw" => {
                 if args.len() == 0 {
@@ -80,20 +211,292 @@ impl Command {
                 Ok(Command::Raw(args_str.to_string()))
             }
             "msg" => {
-                let &[nick] = args.as_slice() else {
+                let [target, _, ..] = args.as_slice() else {
+                    return Err(CommandParseErr::IncorrectArgCount(2, args.len() as u8));
+                };
+                Ok(Command::Msg(
+                    parse_target(target, casemapping)?,
+                    text_after(args_str, 1).to_string(),
+                ))
+            }
+            "query" => {
+                let Some(nick) = args.first() else {
+                    return Err(CommandParseErr::IncorrectArgCount(1, 0));
+                };
+                Ok(Command::Query(
+                    parse_nick(nick, casemapping)?,
+                    optional_text(text_after(args_str, 1)),
+                ))
+            }
+            "me" => match optional_text(args_str) {
+                Some(action) => Ok(Command::Me(action)),
+                None => Err(CommandParseErr::IncorrectArgCount(1, 0)),
+            },
+            "notice" => {
+                let [target, _, ..] = args.as_slice() else {
+                    return Err(CommandParseErr::IncorrectArgCount(2, args.len() as u8));
+                };
+                Ok(Command::Notice(
+                    parse_target(target, casemapping)?,
+                    text_after(args_str, 1).to_string(),
+                ))
+            }
+            "ctcp" => {
+                let [target, command, ..] = args.as_slice() else {
+                    return Err(CommandParseErr::IncorrectArgCount(2, args.len() as u8));
+                };
+                let params = text_after(args_str, 2);
+                let query = if params.is_empty() {
+                    command.to_uppercase()
+                } else {
+                    format!("{} {}", command.to_uppercase(), params)
+                };
+                Ok(Command::Ctcp(parse_target(target, casemapping)?, query))
+            }
+            "quit" => Ok(Command::Quit),
+            "alias" => {
+                let Some(name) = args.first() else {
+                    return Ok(Command::Alias(None, None));
+                };
+                // the expansion is everything after the name, including its spacing
+                let expansion = args_str
+                    .trim_start()
+                    .split_once(' ')
+                    .map(|(_, expansion)| expansion.trim().to_string())
+                    .filter(|expansion| !expansion.is_empty());
+                Ok(Command::Alias(Some(alias_name(name)?), expansion))
+            }
+            "unalias" => {
+                let &[name] = args.as_slice() else {
                     return Err(CommandParseErr::IncorrectArgCount(1, args.len() as u8));
                 };
-
-                let Some(nick) = Nickname::new(nick) else {
-                    return Err(CommandParseErr::InvalidArg(
-                        nick.to_string(),
-                        String::from("a nickname"),
+                Ok(Command::Unalias(alias_name(name)?))
+            }
+            "theme" => match args.as_slice() {
+                [] => Ok(Command::Theme(None)),
+                [name] => Ok(Command::Theme(Some(name.to_string()))),
+                _ => Err(CommandParseErr::IncorrectArgCount(1, args.len() as u8)),
+            },
+            "part" => {
+                let (channel, used) = leading_channel(&args, casemapping)?;
+                let reason = optional_text(text_after(args_str, used));
+                Ok(Command::Part(channel, reason))
+            }
+            "close" => match args.as_slice() {
+                [] => Ok(Command::Close(None)),
+                [target] => Ok(Command::Close(Some(parse_target(target, casemapping)?))),
+                _ => Err(CommandParseErr::IncorrectArgCount(1, args.len() as u8)),
+            },
+            "topic" => {
+                let (channel, used) = leading_channel(&args, casemapping)?;
+                Ok(Command::Topic {
+                    channel,
+                    topic: optional_text(text_after(args_str, used)),
+                    text: optional_text(text_after(args_str, 0)),
+                })
+            }
+            "names" => {
+                let (channel, used) = leading_channel(&args, casemapping)?;
+                if args.len() > used {
+                    return Err(CommandParseErr::IncorrectArgCount(1, args.len() as u8));
+                }
+                Ok(Command::Names(channel))
+            }
+            "list" => {
+                // filters may be given separated by spaces or commas
+                let filters = args
+                    .iter()
+                    .flat_map(|arg| arg.split(','))
+                    .filter(|filter| !filter.is_empty())
+                    .map(|filter| {
+                        ListFilter::parse(filter, casemapping).ok_or_else(|| {
+                            CommandParseErr::InvalidArg(
+                                filter.to_string(),
+                                String::from("a channel, mask or ELIST condition"),
+                            )
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Command::List(filters))
+            }
+            "notify" => match args.as_slice() {
+                [] => Ok(Command::Notify(NotifyCommand::List)),
+                ["refresh"] => Ok(Command::Notify(NotifyCommand::Refresh)),
+                ["add", nicks @ ..] if !nicks.is_empty() => {
+                    Ok(Command::Notify(NotifyCommand::Add(
+                        nicks
+                            .iter()
+                            .map(|n| parse_nick(n, casemapping))
+                            .collect::<Result<_, _>>()?,
+                    )))
+                }
+                ["del", nicks @ ..] if !nicks.is_empty() => {
+                    Ok(Command::Notify(NotifyCommand::Del(
+                        nicks
+                            .iter()
+                            .map(|n| parse_nick(n, casemapping))
+                            .collect::<Result<_, _>>()?,
+                    )))
+                }
+                [subcommand, ..] => Err(CommandParseErr::InvalidArg(
+                    subcommand.to_string(),
+                    String::from("add, del or refresh"),
+                )),
+            },
+            "accept" => match args.as_slice() {
+                [] => Ok(Command::Accept),
+                _ => Err(CommandParseErr::IncorrectArgCount(0, args.len() as u8)),
+            },
+            "invite" => match args.as_slice() {
+                [nick] => Ok(Command::Invite(parse_nick(nick, casemapping)?, None)),
+                [nick, channel] => Ok(Command::Invite(
+                    parse_nick(nick, casemapping)?,
+                    Some(parse_channel(channel, casemapping)?),
+                )),
+                _ => Err(CommandParseErr::IncorrectArgCount(2, args.len() as u8)),
+            },
+            "kick" => {
+                let (channel, used) = leading_channel(&args, casemapping)?;
+                let Some(nick) = args.get(used) else {
+                    return Err(CommandParseErr::IncorrectArgCount(
+                        used as u8 + 1,
+                        args.len() as u8,
                     ));
                 };
-
-                Ok(Command::Msg(nick))
+                Ok(Command::Kick {
+                    channel,
+                    nick: parse_nick(nick, casemapping)?,
+                    reason: optional_text(text_after(args_str, used + 1)),
+                })
             }
-            "quit" => Ok(Command::Quit),
+            "mode" => match args.first() {
+                None => Ok(Command::Mode(None, None)),
+                // a mode change without a target applies to the current window
+                Some(first) if first.starts_with(['+', '-']) => {
+                    Ok(Command::Mode(None, optional_text(args_str)))
+                }
+                Some(target) => {
+                    let Some(target) = Target::new(*target, casemapping) else {
+                        return Err(CommandParseErr::InvalidArg(
+                            target.to_string(),
+                            String::from("a channel or nickname"),
+                        ));
+                    };
+                    Ok(Command::Mode(
+                        Some(target),
+                        optional_text(text_after(args_str, 1)),
+                    ))
+                }
+            },
+            cmd @ ("op" | "deop" | "voice" | "devoice") => {
+                let (channel, used) = leading_channel(&args, casemapping)?;
+                let nicks = args[used..]
+                    .iter()
+                    .map(|nick| parse_nick(nick, casemapping).map(|nick| nick.as_str().to_string()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if nicks.is_empty() {
+                    return Err(CommandParseErr::IncorrectArgCount(
+                        used as u8 + 1,
+                        args.len() as u8,
+                    ));
+                }
+                Ok(Command::ChannelModes {
+                    channel,
+                    adding: !cmd.starts_with("de"),
+                    mode: if cmd.ends_with("op") { 'o' } else { 'v' },
+                    params: nicks,
+                })
+            }
+            "ban" => {
+                let (channel, used) = leading_channel(&args, casemapping)?;
+                let (kind, used) = match args.get(used) {
+                    Some(&"-h") => (BanMask::Host, used + 1),
+                    Some(&"-u") => (BanMask::User, used + 1),
+                    Some(&"-n") => (BanMask::Nick, used + 1),
+                    _ => (BanMask::Host, used),
+                };
+                let targets = args[used..]
+                    .iter()
+                    .map(|target| target.to_string())
+                    .collect::<Vec<_>>();
+                if targets.is_empty() {
+                    return Err(CommandParseErr::IncorrectArgCount(
+                        used as u8 + 1,
+                        args.len() as u8,
+                    ));
+                }
+                Ok(Command::Ban {
+                    channel,
+                    kind,
+                    targets,
+                })
+            }
+            "unban" => {
+                let (channel, used) = leading_channel(&args, casemapping)?;
+                let targets = args[used..]
+                    .iter()
+                    .map(|target| target.to_string())
+                    .collect::<Vec<_>>();
+                if targets.is_empty() {
+                    return Err(CommandParseErr::IncorrectArgCount(
+                        used as u8 + 1,
+                        args.len() as u8,
+                    ));
+                }
+                Ok(Command::Unban { channel, targets })
+            }
+            cmd @ ("banlist" | "exceptlist" | "invexlist") => {
+                let (channel, used) = leading_channel(&args, casemapping)?;
+                if args.len() > used {
+                    return Err(CommandParseErr::IncorrectArgCount(
+                        used as u8,
+                        args.len() as u8,
+                    ));
+                }
+                let mode = match cmd {
+                    "banlist" => 'b',
+                    "exceptlist" => 'e',
+                    _ => 'I',
+                };
+                Ok(Command::ModeList { channel, mode })
+            }
+            "whois" => match args.as_slice() {
+                [nick] => Ok(Command::Whois(None, parse_nick(nick, casemapping)?)),
+                [server, nick] => Ok(Command::Whois(
+                    Some(server.to_string()),
+                    parse_nick(nick, casemapping)?,
+                )),
+                _ => Err(CommandParseErr::IncorrectArgCount(1, args.len() as u8)),
+            },
+            "who" => match args.as_slice() {
+                [] => Ok(Command::Who(None)),
+                [mask] => Ok(Command::Who(Some(mask.to_string()))),
+                _ => Err(CommandParseErr::IncorrectArgCount(1, args.len() as u8)),
+            },
+            "away" => Ok(Command::Away(optional_text(args_str))),
+            "back" => match args.as_slice() {
+                [] => Ok(Command::Back),
+                _ => Err(CommandParseErr::IncorrectArgCount(0, args.len() as u8)),
+            },
+            "whowas" => match args.as_slice() {
+                [nick] => Ok(Command::Whowas(parse_nick(nick, casemapping)?, None)),
+                [nick, count] => {
+                    let count = count.parse().map_err(|_| {
+                        CommandParseErr::InvalidArg(count.to_string(), String::from("a count"))
+                    })?;
+                    Ok(Command::Whowas(parse_nick(nick, casemapping)?, Some(count)))
+                }
+                _ => Err(CommandParseErr::IncorrectArgCount(1, args.len() as u8)),
+            },
+            "script" => match args.as_slice() {
+                ["reload"] => Ok(Command::Script(ScriptCommand::Reload)),
+                ["list"] => Ok(Command::Script(ScriptCommand::List)),
+                [arg] => Err(CommandParseErr::InvalidArg(
+                    arg.to_string(),
+                    String::from("reload or list"),
+                )),
+                _ => Err(CommandParseErr::IncorrectArgCount(1, args.len() as u8)),
+            },
             _ => Err(CommandParseErr::UnknownCommand(cmd.to_string())),
         }
     }
@@ -104,21 +507,76 @@ impl Command {
         sender: &Sender<ClientMessage>,
     ) -> eyre::Result<()> {
         match self {
-            Command::Join(channel) => {
-                let ConnectedState { .. } = expect_connected_state!(state, "JOIN")?;
+            Command::Join(channels) => {
+                let ConnectedState {
+                    channels: joined,
+                    isupport,
+                    channel_keys,
+                    ..
+                } = expect_connected_state!(state, "JOIN")?;
 
-                let channel = ChannelName::new(channel)
-                    .ok_or_else(|| eyre!("join was invalid channel {:?}", channel))?;
+                let mut to_join = Vec::new();
+                let mut over_limit = Vec::new();
+                for (channel, key) in channels {
+                    // a key is only needed the first time a channel is joined
+                    let key = match key {
+                        Some(key) => {
+                            channel_keys.insert(channel.clone(), key.clone());
+                            Some(key.clone())
+                        }
+                        None => channel_keys.get(channel).cloned(),
+                    };
 
-                sender.send(ClientMessage::from_command(ClientIrcCommand::Join(vec![(
-                    channel.clone(),
-                    None,
-                )])))?;
+                    // channels with any of the prefixes in a CHANLIMIT group count towards its limit
+                    let is_joined = |c: &ChannelName| joined.get(c).is_some_and(|c| c.joined);
+                    let kind = channel.as_str().chars().next().unwrap_or('#');
+                    let over = isupport.chanlimit(kind).is_some_and(|(prefixes, limit)| {
+                        let same_group = joined
+                            .keys()
+                            .filter(|c| is_joined(c))
+                            .chain(to_join.iter().map(|(c, _)| c).filter(|c| !is_joined(c)))
+                            .filter(|c| c.as_str().starts_with(|c| prefixes.contains(c)))
+                            .count();
+                        same_group >= limit
+                    });
+                    if !is_joined(channel) && over {
+                        over_limit.push(channel.as_str().to_string());
+                        continue;
+                    }
+                    to_join.push((channel.clone(), key));
+                }
+
+                for batch in join_batches(to_join.clone(), isupport.targmax("JOIN")) {
+                    sender.send(ClientMessage::from_command(ClientIrcCommand::Join(batch)))?;
+                }
+                for (channel, _) in to_join {
+                    state.ensure_target_exists(Target::Channel(channel));
+                }
 
-                state.ensure_target_exists(Target::Channel(channel));
+                if !over_limit.is_empty() {
+                    return Err(eyre!(
+                        "not joining {}, the server's channel limit would be exceeded",
+                        over_limit.join(", ")
+                    ));
+                }
             }
-            Command::Ctcp(target, command) => {
-                todo!("handle CTCP command from user")
+            Command::Ctcp(target, query) => {
+                let _ = expect_connected_state!(state, "CTCP")?;
+                let Some(irc_target) = target.irc_target() else {
+                    return Err(eyre!("cannot send CTCP to status"));
+                };
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Privmsg {
+                    targets: vec![irc_target],
+                    msg: ctcp::encode(query),
+                }))?;
+
+                let line = util::line_now()
+                    .push_unstyled(" ")
+                    .push_themed(Role::Info, "CTCP ")
+                    .push_unstyled(query)
+                    .push_unstyled(" -> ")
+                    .push_themed(Role::Channel, target.as_str().to_string());
+                state.add_line(state.current_target().clone(), line);
             }
             Command::Raw(text) => {
                 // don't need to access the state here, just need to ensure connected
@@ -128,19 +586,803 @@ impl Command {
                     text.to_string(),
                 )))?;
             }
-            Command::Msg(nick) => {
+            Command::Msg(target, text) => {
+                privmsg(state, sender, target.clone(), text)?;
+            }
+            Command::Query(nick, text) => {
                 let ConnectedState { .. } = expect_connected_state!(state, "PRIVMSG")?;
                 debug!("nick: {:?}", nick);
-                // just create the channel and switch to it, no message
-                state.ensure_target_exists(Target::Nickname(nick.clone()));
-                state.render()?;
+                // create the window and switch to it, even if it already existed
+                let target = Target::Nickname(nick.clone());
+                state.ensure_target_exists(target.clone());
+                state.select_target(&target);
+                match text {
+                    Some(text) => privmsg(state, sender, target, text)?,
+                    None => state.render()?,
+                }
+            }
+            Command::Me(action) => {
+                let ConnectedState { nick, .. } = expect_connected_state!(state, "ACTION")?;
+                let nick = nick.clone();
+                let target = state.current_target().clone();
+                let Some(irc_target) = target.irc_target() else {
+                    return Err(eyre!("cannot send an action to status"));
+                };
+
+                let line = util::message_line(
+                    Kind::Action,
+                    Utc::now(),
+                    util::nick_line(&state.config.nick_colors, nick.as_str(), true),
+                    format::parse(action, state.config.formatting),
+                );
+                let command = CtcpCommand::Action(action.to_string()).to_command(vec![irc_target]);
+                echo::send(state, sender, target, command, line)?;
+            }
+            Command::Notice(target, text) => {
+                let ConnectedState { nick, .. } = expect_connected_state!(state, "NOTICE")?;
+                let nick = nick.clone();
+                let Some(irc_target) = target.irc_target() else {
+                    return Err(eyre!("cannot send a notice to status"));
+                };
+
+                let line = util::message_line(
+                    Kind::Notice,
+                    Utc::now(),
+                    util::nick_line(&state.config.nick_colors, nick.as_str(), true),
+                    format::parse(text, state.config.formatting),
+                );
+                let command = ClientIrcCommand::Notice {
+                    targets: vec![irc_target],
+                    msg: text.to_string(),
+                };
+                echo::send(state, sender, target.clone(), command, line)?;
             }
             Command::Quit => {
                 sender.send(ClientMessage::from_command(ClientIrcCommand::Quit(None)))?;
                 crate::client::QUIT_REQUESTED.store(true, atomic::Ordering::Relaxed);
             }
+            Command::Alias(None, _) => {
+                let target = state.current_target().clone();
+                let aliases = state
+                    .config
+                    .alias_packs
+                    .iter()
+                    .filter(|(name, _)| !state.config.aliases.contains_key(*name))
+                    .chain(state.config.aliases.iter())
+                    .map(|(name, expansion)| format!(" /{} = {}", name, expansion))
+                    .collect::<Vec<_>>();
+                if aliases.is_empty() {
+                    state.add_line(
+                        target.clone(),
+                        util::line_now().push_unstyled(" no aliases"),
+                    );
+                }
+                for alias in aliases {
+                    state.add_line(target.clone(), util::line_now().push_unstyled(alias));
+                }
+            }
+            Command::Alias(Some(name), None) => {
+                let Some(expansion) = state.config.alias(name) else {
+                    return Err(eyre!("no alias named {}", name));
+                };
+                let line = util::line_now().push_unstyled(format!(" /{} = {}", name, expansion));
+                state.add_line(state.current_target().clone(), line);
+            }
+            Command::Alias(Some(name), Some(expansion)) => {
+                state
+                    .config
+                    .aliases
+                    .insert(name.to_string(), expansion.to_string());
+                state.config.save("aliases")?;
+                let line = util::line_now()
+                    .push_unstyled(format!(" defined alias /{} = {}", name, expansion));
+                state.add_line(state.current_target().clone(), line);
+            }
+            Command::Unalias(name) => {
+                if state.config.aliases.remove(name).is_none() {
+                    if state.config.alias_packs.contains_key(name) {
+                        return Err(eyre!("alias {} is from an alias pack", name));
+                    }
+                    return Err(eyre!("no alias named {}", name));
+                }
+                state.config.save("aliases")?;
+                let line = util::line_now().push_unstyled(format!(" removed alias /{}", name));
+                state.add_line(state.current_target().clone(), line);
+            }
+            Command::Theme(None) => {
+                let target = state.current_target().clone();
+                let current = theme::current();
+                for name in theme::available(state.config.dir()) {
+                    let line = if name == current.name {
+                        util::line_now()
+                            .push_themed(Role::Highlight, format!(" {} (current)", name))
+                    } else {
+                        util::line_now().push_unstyled(format!(" {}", name))
+                    };
+                    state.add_line(target.clone(), line);
+                }
+            }
+            Command::Theme(Some(name)) => {
+                let new_theme = Theme::load(state.config.dir(), name, state.config.color_depth())?;
+                theme::set(new_theme);
+                state.config.theme = Some(name.to_string());
+                state.config.save("theme")?;
+                let line =
+                    util::line_now().push_unstyled(format!(" switched to the {} theme", name));
+                state.add_line(state.current_target().clone(), line);
+                // lines look up their theme styles when drawn, so everything changes at once
+                state.render()?;
+            }
+            Command::Part(channel, reason) => {
+                let _ = expect_connected_state!(state, "PART")?;
+                let channel = channel_or_current(state, channel)?;
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Part(
+                    vec![channel],
+                    reason.clone(),
+                )))?;
+            }
+            Command::Close(target) => {
+                let target = target
+                    .clone()
+                    .unwrap_or_else(|| state.current_target().clone());
+                let ConnectedState {
+                    channels,
+                    user_messages,
+                    ..
+                } = expect_connected_state!(state, "CLOSE")?;
+                match &target {
+                    Target::Status => return Err(eyre!("the status window cannot be closed")),
+                    Target::List => {}
+                    Target::Channel(channel_name) => {
+                        let Some(channel) = channels.get(channel_name) else {
+                            return Err(eyre!("no window for {}", channel_name.as_str()));
+                        };
+                        // the window is closed when the PART comes back, so that it's shown
+                        if channel.joined {
+                            sender.send(ClientMessage::from_command(ClientIrcCommand::Part(
+                                vec![channel_name.clone()],
+                                None,
+                            )))?;
+                            return Ok(());
+                        }
+                    }
+                    Target::Nickname(nick) => {
+                        if !user_messages.contains_key(nick) {
+                            return Err(eyre!("no window for {}", nick.as_str()));
+                        }
+                    }
+                }
+                state.remove_target(&target);
+            }
+            Command::Topic {
+                channel,
+                topic,
+                text,
+            } => {
+                let ConnectedState { channels, .. } = expect_connected_state!(state, "TOPIC")?;
+                let joined = channel
+                    .as_ref()
+                    .and_then(|channel| channels.get(channel))
+                    .is_some_and(|channel| channel.joined);
+                let (channel, topic) = match state.current_target() {
+                    // setting the topic of a channel that we aren't in can't work, so the text is
+                    // the topic of the current channel, like `/topic #rust is great`
+                    Target::Channel(current) if !joined && topic.is_some() => {
+                        (current.clone(), text.clone())
+                    }
+                    _ => (channel_or_current(state, channel)?, topic.clone()),
+                };
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Topic(
+                    channel, topic,
+                )))?;
+            }
+            Command::Names(channel) => {
+                let _ = expect_connected_state!(state, "NAMES")?;
+                let channel = channel_or_current(state, channel)?;
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Names(vec![
+                    channel,
+                ])))?;
+            }
+            Command::List(filters) => {
+                let ConnectedState { isupport, .. } = expect_connected_state!(state, "LIST")?;
+                // filters that the server doesn't support are applied to the replies instead, if
+                // the replies have the information needed
+                let (supported, local): (Vec<_>, Vec<_>) = filters
+                    .iter()
+                    .cloned()
+                    .partition(|filter| filter.supported(isupport));
+                if let Some(filter) = local.iter().find(|filter| !filter.applies_locally()) {
+                    return Err(eyre!(
+                        "the server does not support the filter {} (ELIST {})",
+                        filter.to_irc_string(),
+                        filter.elist_token().unwrap_or('?'),
+                    ));
+                }
+
+                sender.send(ClientMessage::from_command(ClientIrcCommand::List(
+                    supported.iter().map(ListFilter::to_irc_string).collect(),
+                )))?;
+                state.ensure_target_exists(Target::List);
+                state.select_target(&Target::List);
+                let casemapping = state.casemapping();
+                if let ConnectionState::Connected(ConnectedState { list_window, .. }) =
+                    &mut state.conn_state
+                {
+                    *list_window = Some(ListWindow::Channels(ChannelList::new(local, casemapping)));
+                }
+                state.render()?;
+            }
+            Command::Invite(nick, channel) => {
+                let _ = expect_connected_state!(state, "INVITE")?;
+                let channel = channel_or_current(state, channel)?;
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Invite {
+                    nick: nick.clone(),
+                    channel,
+                }))?;
+            }
+            Command::Accept => state.accept_invite(),
+            Command::Notify(NotifyCommand::List) => {
+                let _ = expect_connected_state!(state, "MONITOR")?;
+                notify::list(state);
+            }
+            Command::Notify(NotifyCommand::Add(nicks)) => notify::add(state, nicks)?,
+            Command::Notify(NotifyCommand::Del(nicks)) => notify::remove(state, nicks)?,
+            Command::Notify(NotifyCommand::Refresh) => {
+                let _ = expect_connected_state!(state, "MONITOR")?;
+                notify::refresh(state);
+            }
+            Command::Kick {
+                channel,
+                nick,
+                reason,
+            } => {
+                let _ = expect_connected_state!(state, "KICK")?;
+                let channel = channel_or_current(state, channel)?;
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Kick {
+                    channel,
+                    users: vec![nick.clone()],
+                    comment: reason.clone(),
+                }))?;
+            }
+            Command::Mode(target, mode) => {
+                let casemapping = state.casemapping();
+                let ConnectedState { nick, .. } = expect_connected_state!(state, "MODE")?;
+                let nick = nick.clone();
+                // only channels have modes of their own, so the modes of other windows are ours
+                let target = match (target, state.current_target()) {
+                    (Some(target), _) | (None, target @ Target::Channel(_)) => target.irc_target(),
+                    (None, _) => Nickname::new(nick.as_str(), casemapping).map(irc::Target::User),
+                };
+                let Some(target) = target else {
+                    return Err(eyre!("there is no target for MODE in this window"));
+                };
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Mode {
+                    target,
+                    mode: mode.clone(),
+                }))?;
+            }
+            Command::Ban {
+                channel,
+                kind,
+                targets,
+            } => {
+                let channel_name = channel_or_current(state, channel)?;
+                let casemapping = state.casemapping();
+                let ConnectedState {
+                    channels, isupport, ..
+                } = expect_connected_state!(state, "MODE")?;
+                let channel = channels.get(&channel_name);
+                let masks = targets
+                    .iter()
+                    .map(|target| match Nickname::new(target.as_str(), casemapping) {
+                        Some(nick) if !is_mask(target) => channel
+                            .and_then(|channel| channel.users.get(&nick))
+                            .map_or_else(
+                                || format!("{}!*@*", nick.as_str()),
+                                |member| member.ban_mask(&nick, *kind),
+                            ),
+                        _ => target.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                send_modes(sender, isupport, &channel_name, true, 'b', &masks)?;
+            }
+            Command::Unban { channel, targets } => {
+                let channel_name = channel_or_current(state, channel)?;
+                let casemapping = state.casemapping();
+                let ConnectedState {
+                    channels, isupport, ..
+                } = expect_connected_state!(state, "MODE")?;
+                let channel = channels.get(&channel_name);
+                let bans = channel
+                    .and_then(|channel| channel.modes.lists.get(&'b'))
+                    .map_or([].as_slice(), Vec::as_slice);
+
+                let mut masks = Vec::new();
+                for target in targets {
+                    if let Ok(idx) = target.parse::<usize>() {
+                        // indexes are shown starting at 1
+                        let Some(entry) = idx.checked_sub(1).and_then(|idx| bans.get(idx)) else {
+                            return Err(eyre!(
+                                "there is no ban {}, use /banlist to see the bans",
+                                idx
+                            ));
+                        };
+                        masks.push(entry.mask.clone());
+                    } else if is_mask(target) {
+                        masks.push(target.clone());
+                    } else {
+                        // remove every ban that matches the user, as far as they are known
+                        let nick = parse_nick(target, casemapping)?;
+                        let hostmask = channel
+                            .and_then(|channel| channel.users.get(&nick))
+                            .map_or_else(
+                                || format!("{}!*@*", nick.as_str()),
+                                |member| member.hostmask(&nick),
+                            );
+                        let matching = bans
+                            .iter()
+                            .filter(|entry| entry.matches(&hostmask, casemapping))
+                            .map(|entry| entry.mask.clone())
+                            .collect::<Vec<_>>();
+                        if matching.is_empty() {
+                            masks.push(format!("{}!*@*", nick.as_str()));
+                        } else {
+                            masks.extend(matching);
+                        }
+                    }
+                }
+                send_modes(sender, isupport, &channel_name, false, 'b', &masks)?;
+            }
+            Command::Whois(server, nick) => {
+                let _ = expect_connected_state!(state, "WHOIS")?;
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Whois {
+                    target: server.clone(),
+                    nick: nick.clone(),
+                }))?;
+            }
+            Command::Away(message) => {
+                let ConnectedState { auto_away, .. } = expect_connected_state!(state, "AWAY")?;
+                // being marked as away manually means that input won't mark us as back
+                *auto_away = false;
+                let message = message
+                    .clone()
+                    .unwrap_or_else(|| String::from(DEFAULT_AWAY_MESSAGE));
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Away {
+                    message: Some(message),
+                }))?;
+            }
+            Command::Back => {
+                let ConnectedState { auto_away, .. } = expect_connected_state!(state, "AWAY")?;
+                *auto_away = false;
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Away {
+                    message: None,
+                }))?;
+            }
+            Command::Whowas(nick, count) => {
+                let _ = expect_connected_state!(state, "WHOWAS")?;
+                sender.send(ClientMessage::from_command(ClientIrcCommand::WhoWas {
+                    nick: nick.clone(),
+                    count: *count,
+                }))?;
+            }
+            Command::Who(mask) => {
+                let mask = match (mask, state.current_target()) {
+                    (Some(mask), _) => mask.clone(),
+                    (None, Target::Channel(channel)) => channel.as_str().to_string(),
+                    (None, Target::Nickname(nick)) => nick.as_str().to_string(),
+                    (None, Target::Status | Target::List) => {
+                        return Err(eyre!("cannot WHO this window without a mask"))
+                    }
+                };
+                let ConnectedState { isupport, .. } = expect_connected_state!(state, "WHO")?;
+                sender.send(ClientMessage::from_command(who::query(
+                    isupport, mask, false,
+                )))?;
+            }
+            Command::ModeList { channel, mode } => {
+                let _ = expect_connected_state!(state, "MODE")?;
+                let channel = channel_or_current(state, channel)?;
+                sender.send(ClientMessage::from_command(ClientIrcCommand::Mode {
+                    target: irc::Target::Channel(channel),
+                    mode: Some(format!("+{}", mode)),
+                }))?;
+            }
+            Command::ChannelModes {
+                channel,
+                adding,
+                mode,
+                params,
+            } => {
+                let channel = channel_or_current(state, channel)?;
+                let ConnectedState { isupport, .. } = expect_connected_state!(state, "MODE")?;
+                send_modes(sender, isupport, &channel, *adding, *mode, params)?;
+            }
+            Command::Script(ScriptCommand::Reload) => {
+                let actions = state.scripts.reload();
+                script::apply(state, actions);
+                let count = state.scripts.script_names().count();
+                state.add_line(
+                    Target::Status,
+                    util::line_now().push_unstyled(format!(" reloaded {} scripts", count)),
+                );
+            }
+            Command::Script(ScriptCommand::List) => {
+                let names = state.scripts.script_names().collect::<Vec<_>>().join(", ");
+                state.add_line(
+                    Target::Status,
+                    util::line_now().push_unstyled(format!(" scripts: {}", names)),
+                );
+            }
         }
 
         Ok(())
     }
 }
+
+/// normalizes the name of an alias, allowing users to write it with the leading `/`
+fn alias_name(name: &str) -> Result<String, CommandParseErr> {
+    let name = name.trim_start_matches('/');
+    if name.is_empty() {
+        return Err(CommandParseErr::InvalidArg(
+            name.to_string(),
+            String::from("an alias name"),
+        ));
+    }
+    Ok(name.to_lowercase())
+}
+
+/// the longest list of channels and keys to send in one JOIN, leaving room for the rest of the 512
+/// byte message
+const MAX_JOIN_LEN: usize = 400;
+/// the away message for `/away` without a message, since AWAY without one means being back
+const DEFAULT_AWAY_MESSAGE: &str = "away";
+
+/// sends a PRIVMSG to a target and shows it in the target's window
+fn privmsg(
+    state: &mut ClientState,
+    sender: &Sender<ClientMessage>,
+    target: Target,
+    text: &str,
+) -> eyre::Result<()> {
+    let ConnectedState { nick, .. } = expect_connected_state!(state, "PRIVMSG")?;
+    let nick = nick.clone();
+    let Some(irc_target) = target.irc_target() else {
+        return Err(eyre!("cannot send message to status"));
+    };
+
+    let line = util::message_line(
+        Kind::Message,
+        Utc::now(),
+        util::nick_line(&state.config.nick_colors, nick.as_str(), true),
+        format::parse(text, state.config.formatting),
+    );
+    let command = ClientIrcCommand::Privmsg {
+        targets: vec![irc_target],
+        msg: text.to_string(),
+    };
+    echo::send(state, sender, target, command, line)
+}
+
+/// splits channels to join into messages with at most `max` channels, which also fit in a single
+/// IRC message
+fn join_batches(
+    channels: Vec<(ChannelName, Option<String>)>,
+    max: Option<usize>,
+) -> Vec<Vec<(ChannelName, Option<String>)>> {
+    let mut batches = Vec::new();
+    let mut batch: Vec<(ChannelName, Option<String>)> = Vec::new();
+    let mut len = 0;
+    for (channel, key) in channels {
+        // each channel and key is followed by a `,` or ` `
+        let channel_len = channel.as_str().len() + key.as_ref().map_or(0, |key| key.len() + 1) + 1;
+        let full = max.is_some_and(|max| batch.len() >= max);
+        if !batch.is_empty() && (full || len + channel_len > MAX_JOIN_LEN) {
+            batches.push(std::mem::take(&mut batch));
+            len = 0;
+        }
+        len += channel_len;
+        batch.push((channel, key));
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+/// the channel that a command applies to, defaulting to the channel of the current window
+fn channel_or_current(
+    state: &ClientState,
+    channel: &Option<ChannelName>,
+) -> eyre::Result<ChannelName> {
+    match (channel, state.current_target()) {
+        (Some(channel), _) => Ok(channel.clone()),
+        (None, Target::Channel(channel)) => Ok(channel.clone()),
+        (None, _) => Err(eyre!(
+            "no channel was given and the current window is not a channel"
+        )),
+    }
+}
+
+/// parses an optional channel as the first argument, returning it and the number of arguments
+/// that it used.
+fn leading_channel(
+    args: &[&str],
+    casemapping: Casemapping,
+) -> Result<(Option<ChannelName>, usize), CommandParseErr> {
+    match args.first() {
+        Some(arg) if arg.starts_with(CHANNEL_TYPES) => {
+            Ok((Some(parse_channel(arg, casemapping)?), 1))
+        }
+        _ => Ok((None, 0)),
+    }
+}
+
+fn parse_channel(channel: &str, casemapping: Casemapping) -> Result<ChannelName, CommandParseErr> {
+    ChannelName::new(channel, casemapping).ok_or_else(|| {
+        CommandParseErr::InvalidArg(channel.to_string(), String::from("a channel name"))
+    })
+}
+
+fn parse_target(target: &str, casemapping: Casemapping) -> Result<Target, CommandParseErr> {
+    Target::new(target, casemapping).ok_or_else(|| {
+        CommandParseErr::InvalidArg(target.to_string(), String::from("a channel or nickname"))
+    })
+}
+
+fn parse_nick(nick: &str, casemapping: Casemapping) -> Result<Nickname, CommandParseErr> {
+    Nickname::new(nick, casemapping)
+        .ok_or_else(|| CommandParseErr::InvalidArg(nick.to_string(), String::from("a nickname")))
+}
+
+/// returns the text after the first `n` words of `s`, keeping its spacing
+fn text_after(s: &str, n: usize) -> &str {
+    let mut rest = s.trim_start_matches(' ');
+    for _ in 0..n {
+        rest = rest
+            .split_once(' ')
+            .map_or("", |(_, rest)| rest)
+            .trim_start_matches(' ');
+    }
+    rest
+}
+
+fn optional_text(s: &str) -> Option<String> {
+    if s.trim().is_empty() {
+        None
+    } else {
+        Some(s.to_string())
+    }
+}
+
+/// whether a ban target is a mask rather than a nick
+fn is_mask(s: &str) -> bool {
+    s.contains(['!', '@', '*', '?'])
+}
+
+/// the mode changes that set or unset a mode once for each param, with at most `max` params in
+/// each change
+fn mode_changes(adding: bool, mode: char, params: &[String], max: Option<usize>) -> Vec<String> {
+    let sign = if adding { '+' } else { '-' };
+    params
+        .chunks(max.unwrap_or(params.len()).max(1))
+        .map(|params| {
+            format!(
+                "{}{} {}",
+                sign,
+                mode.to_string().repeat(params.len()),
+                params.join(" ")
+            )
+        })
+        .collect()
+}
+
+/// sets or unsets a mode once for each param, using as few messages as the server allows
+fn send_modes(
+    sender: &Sender<ClientMessage>,
+    isupport: &ISupport,
+    channel: &ChannelName,
+    adding: bool,
+    mode: char,
+    params: &[String],
+) -> eyre::Result<()> {
+    for mode in mode_changes(adding, mode, params, isupport.modes()) {
+        sender.send(ClientMessage::from_command(ClientIrcCommand::Mode {
+            target: irc::Target::Channel(channel.clone()),
+            mode: Some(mode),
+        }))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Command, CommandParseErr> {
+        Command::parse(s, Casemapping::default())
+    }
+
+    fn channel(name: &str) -> Option<ChannelName> {
+        ChannelName::new(name, Casemapping::default())
+    }
+
+    #[test]
+    fn topic_without_channel() {
+        let Ok(Command::Topic {
+            channel: None,
+            topic,
+            text,
+        }) = parse("topic a new  topic")
+        else {
+            panic!("expected a topic without a channel");
+        };
+        assert_eq!(topic.as_deref(), Some("a new  topic"));
+        assert_eq!(text.as_deref(), Some("a new  topic"));
+
+        assert!(matches!(
+            parse("topic"),
+            Ok(Command::Topic {
+                channel: None,
+                topic: None,
+                text: None
+            })
+        ));
+    }
+
+    #[test]
+    fn topic_with_channel() {
+        let Ok(Command::Topic {
+            channel: Some(name),
+            topic,
+            text,
+        }) = parse("topic #rust hello world")
+        else {
+            panic!("expected a topic with a channel");
+        };
+        assert_eq!(Some(name), channel("#rust"));
+        assert_eq!(topic.as_deref(), Some("hello world"));
+        // the full text is kept in case #rust is the start of the topic
+        assert_eq!(text.as_deref(), Some("#rust hello world"));
+
+        assert!(matches!(
+            parse("topic #rust"),
+            Ok(Command::Topic {
+                channel: Some(_),
+                topic: None,
+                text: Some(_)
+            })
+        ));
+    }
+
+    #[test]
+    fn mode_without_target() {
+        let Ok(Command::Mode(None, Some(mode))) = parse("mode +i") else {
+            panic!("expected a mode change without a target");
+        };
+        assert_eq!(mode, "+i");
+        let Ok(Command::Mode(None, Some(mode))) = parse("mode -o nick") else {
+            panic!("expected a mode change without a target");
+        };
+        assert_eq!(mode, "-o nick");
+        assert!(matches!(parse("mode"), Ok(Command::Mode(None, None))));
+    }
+
+    #[test]
+    fn mode_with_target() {
+        let Ok(Command::Mode(Some(Target::Channel(name)), Some(mode))) = parse("mode #rust +m")
+        else {
+            panic!("expected a mode change for a channel");
+        };
+        assert_eq!(Some(name), channel("#rust"));
+        assert_eq!(mode, "+m");
+        assert!(matches!(
+            parse("mode nick"),
+            Ok(Command::Mode(Some(Target::Nickname(_)), None))
+        ));
+    }
+
+    #[test]
+    fn op_more_than_modes() {
+        let Ok(Command::ChannelModes {
+            channel: None,
+            adding: true,
+            mode: 'o',
+            params,
+        }) = parse("op a b c d e")
+        else {
+            panic!("expected op without a channel");
+        };
+        assert_eq!(params, ["a", "b", "c", "d", "e"]);
+        assert_eq!(
+            mode_changes(true, 'o', &params, Some(3)),
+            ["+ooo a b c", "+oo d e"]
+        );
+        assert_eq!(mode_changes(true, 'o', &params, None), ["+ooooo a b c d e"]);
+    }
+
+    #[test]
+    fn deop_more_than_modes() {
+        let Ok(Command::ChannelModes {
+            channel: Some(name),
+            adding: false,
+            mode: 'o',
+            params,
+        }) = parse("deop #rust a b c d")
+        else {
+            panic!("expected deop with a channel");
+        };
+        assert_eq!(Some(name), channel("#rust"));
+        assert_eq!(
+            mode_changes(false, 'o', &params, Some(2)),
+            ["-oo a b", "-oo c d"]
+        );
+    }
+
+    #[test]
+    fn kick_with_reason() {
+        let Ok(Command::Kick {
+            channel: Some(name),
+            nick,
+            reason,
+        }) = parse("kick #rust spammer stop  that")
+        else {
+            panic!("expected a kick with a channel");
+        };
+        assert_eq!(Some(name), channel("#rust"));
+        assert_eq!(nick.as_str(), "spammer");
+        assert_eq!(reason.as_deref(), Some("stop  that"));
+    }
+
+    #[test]
+    fn kick_without_reason() {
+        let Ok(Command::Kick {
+            channel: None,
+            nick,
+            reason: None,
+        }) = parse("kick spammer")
+        else {
+            panic!("expected a kick without a reason");
+        };
+        assert_eq!(nick.as_str(), "spammer");
+    }
+
+    #[test]
+    fn incorrect_arg_count() {
+        for (s, expected, found) in [
+            ("kick", 1, 0),
+            ("kick #rust", 2, 1),
+            ("op", 1, 0),
+            ("deop #rust", 2, 1),
+            ("msg nick", 2, 1),
+            ("unalias", 1, 0),
+            ("names #rust #other", 1, 2),
+            ("whowas a 1 2", 1, 3),
+            ("accept now", 0, 1),
+        ] {
+            match parse(s) {
+                Err(CommandParseErr::IncorrectArgCount(e, f)) => {
+                    assert_eq!((e, f), (expected, found), "{}", s)
+                }
+                other => panic!("{}: expected IncorrectArgCount, got {:?}", s, other),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_arg() {
+        for (s, arg) in [
+            ("whowas nick many", "many"),
+            ("join rust", "rust"),
+            ("join #a,#b k1,k2,k3", "k1,k2,k3"),
+            ("kick #rust :nick", ":nick"),
+            ("notify remove nick", "remove"),
+            ("script run", "run"),
+            ("alias /", ""),
+        ] {
+            match parse(s) {
+                Err(CommandParseErr::InvalidArg(a, _)) => assert_eq!(a, arg, "{}", s),
+                other => panic!("{}: expected InvalidArg, got {:?}", s, other),
+            }
+        }
+    }
+}
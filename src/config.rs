@@ -0,0 +1,194 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use toml_edit::DocumentMut;
+
+use crate::ui::{
+    format::Formatting,
+    nick_colors::{ColorDepth, NickColors},
+    template::Templates,
+};
+
+/// the name of the main config file inside the config directory
+const CONFIG_FILE: &str = "config.toml";
+/// the directory inside the config directory that alias packs are loaded from
+const ALIAS_PACK_DIR: &str = "aliases";
+
+#[derive(Debug, Error)]
+pub enum ConfigErr {
+    #[error("could not access {}: {}", .0.display(), .1)]
+    Io(PathBuf, io::Error),
+    #[error("could not parse {}: {}", .0.display(), .1)]
+    Parse(PathBuf, toml::de::Error),
+    #[error("could not serialize config: {}", .0)]
+    Serialize(#[from] toml::ser::Error),
+    #[error("could not edit {}: {}", .0.display(), .1)]
+    Edit(PathBuf, toml_edit::TomlError),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// the directory that the config was loaded from
+    #[serde(skip)]
+    dir: PathBuf,
+    /// whether to join a channel again after being kicked from it
+    pub auto_rejoin: bool,
+    /// the number of minutes without input before being marked as away automatically. auto-away
+    /// is disabled if this isn't set.
+    pub auto_away_minutes: Option<u64>,
+    /// the away message used for auto-away
+    pub auto_away_message: Option<String>,
+    /// channels to join as soon as we're invited to them, which may use `*` and `?` wildcards
+    pub auto_join_invites: Vec<String>,
+    /// nicks to tell the user about when they come online or go offline
+    pub notify: Vec<String>,
+    /// how colours and other formatting in messages are shown
+    pub formatting: Formatting,
+    /// how nicks are coloured
+    pub nick_colors: NickColors,
+    /// the name of a built-in theme or a theme file in the theme directory. the dark theme is used
+    /// if this isn't set.
+    pub theme: Option<String>,
+    /// how lines and their timestamps are laid out
+    pub templates: Templates,
+    /// user defined command aliases, from the alias name (without the `/`) to its expansion
+    pub aliases: BTreeMap<String, String>,
+    /// aliases loaded from the files in the alias pack directory. these are shared between users,
+    /// so they are never written back, and are overridden by `aliases`.
+    #[serde(skip)]
+    pub alias_packs: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// loads the config from `dir`. a missing config file is not an error, the default config is
+    /// used instead.
+    pub fn load(dir: impl Into<PathBuf>) -> Result<Self, ConfigErr> {
+        let dir = dir.into();
+        let path = dir.join(CONFIG_FILE);
+        let mut config = match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| ConfigErr::Parse(path, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("no config at {:?}, using defaults", path);
+                Config::default()
+            }
+            Err(e) => return Err(ConfigErr::Io(path, e)),
+        };
+        config.nick_colors.resolve();
+        config.alias_packs = load_alias_packs(&dir.join(ALIAS_PACK_DIR))?;
+        config.dir = dir;
+        Ok(config)
+    }
+
+    /// writes one top level setting back to the config file, like `aliases`. the rest of the file,
+    /// including comments and settings that the client doesn't know about, is kept as it is.
+    pub fn save(&self, key: &str) -> Result<(), ConfigErr> {
+        let path = self.dir.join(CONFIG_FILE);
+        let mut doc = match fs::read_to_string(&path) {
+            Ok(text) => text
+                .parse::<DocumentMut>()
+                .map_err(|e| ConfigErr::Edit(path.clone(), e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => DocumentMut::new(),
+            Err(e) => return Err(ConfigErr::Io(path, e)),
+        };
+        let current = toml::to_string_pretty(self)?
+            .parse::<DocumentMut>()
+            .map_err(|e| ConfigErr::Edit(path.clone(), e))?;
+        // settings that aren't set, like a theme of None, aren't serialized
+        match current.get(key) {
+            Some(value) => doc[key] = value.clone(),
+            None => {
+                doc.remove(key);
+            }
+        }
+
+        fs::create_dir_all(&self.dir).map_err(|e| ConfigErr::Io(self.dir.clone(), e))?;
+        fs::write(&path, doc.to_string()).map_err(|e| ConfigErr::Io(path, e))
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.dir.as_path()
+    }
+
+    /// how many colours the terminal supports, from the config or guessed from the environment
+    pub fn color_depth(&self) -> ColorDepth {
+        self.nick_colors.depth.unwrap_or_else(ColorDepth::detect)
+    }
+
+    /// gets the expansion of an alias, preferring the user's own aliases over alias packs
+    pub fn alias(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.aliases
+            .get(&name)
+            .or_else(|| self.alias_packs.get(&name))
+            .map(String::as_str)
+    }
+}
+
+/// loads every `.toml` file in `dir` as a table of alias names to expansions. files are loaded in
+/// order of their name, with later files overriding earlier ones.
+fn load_alias_packs(dir: &Path) -> Result<BTreeMap<String, String>, ConfigErr> {
+    let mut paths = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect::<Vec<_>>(),
+        // no alias packs installed
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(ConfigErr::Io(dir.to_path_buf(), e)),
+    };
+    paths.sort();
+
+    let mut aliases = BTreeMap::new();
+    for path in paths {
+        let text = fs::read_to_string(&path).map_err(|e| ConfigErr::Io(path.clone(), e))?;
+        let pack: BTreeMap<String, String> =
+            toml::from_str(&text).map_err(|e| ConfigErr::Parse(path, e))?;
+        aliases.extend(
+            pack.into_iter()
+                .map(|(name, expansion)| (name.to_lowercase(), expansion)),
+        );
+    }
+    Ok(aliases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_keeps_the_rest_of_the_file() {
+        let dir = std::env::temp_dir().join(format!("config-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE);
+        fs::write(
+            &path,
+            "# my settings\nauto_rejoin = true # rejoin\nunknown = 1\n\n[aliases]\nold = \"/say old\"\n",
+        )
+        .unwrap();
+
+        let mut config = Config::load(&dir).unwrap();
+        config.aliases.remove("old");
+        config
+            .aliases
+            .insert(String::from("j"), String::from("/join $1"));
+        config.save("aliases").unwrap();
+        config.theme = None;
+        config.save("theme").unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(text.starts_with("# my settings\nauto_rejoin = true # rejoin\nunknown = 1\n"));
+        assert!(!text.contains("old"), "{}", text);
+        assert!(!text.contains("theme"), "{}", text);
+        let config = toml::from_str::<Config>(&text).unwrap();
+        assert_eq!(config.alias("J"), Some("/join $1"));
+        assert!(config.auto_rejoin);
+    }
+}
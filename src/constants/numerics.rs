@@ -38,6 +38,7 @@ numerics! {
     RPL_NONE 300
     RPL_AWAY 301
     RPL_USERHOST 302
+    RPL_ISON 303
     RPL_UNAWAY 305
     RPL_NOWAWAY 306
     RPL_WHOISREGNICK 307
@@ -70,6 +71,7 @@ numerics! {
     RPL_VERSION 351
     RPL_WHOREPLY 352
     RPL_NAMREPLY 353
+    RPL_WHOSPCRPL 354
     RPL_LINKS 364
     RPL_ENDOFLINKS 365
     RPL_ENDOFNAMES 366
@@ -133,6 +135,11 @@ numerics! {
     RPL_HELPTXT 705
     RPL_ENDOFHELP 706
     ERR_NOPRIVS 723
+    RPL_MONONLINE 730
+    RPL_MONOFFLINE 731
+    RPL_MONLIST 732
+    RPL_ENDOFMONLIST 733
+    ERR_MONLISTFULL 734
     RPL_LOGGEDIN 900
     RPL_LOGGEDOUT 901
     ERR_NICKLOCKED 902
@@ -0,0 +1,90 @@
+use crate::{
+    channel::Nickname,
+    irc::Param,
+    state::{ClientState, ConnectedState, ConnectionState},
+    targets::Target,
+    ui::{nick_colors::NickColors, text::Line, theme::Role},
+    util,
+};
+
+/// handles RPL_AWAY for a message that we sent, showing it in the query window unless the same
+/// away message was already shown there
+pub(super) fn reply(state: &mut ClientState, args: &[Param]) {
+    let [_, nick, message, ..] = args else {
+        state.warn_in(&Target::Status, String::from("RPL_AWAY missing params"));
+        return;
+    };
+    let casemapping = state.casemapping();
+    let (Some(nick), Some(message)) = (
+        nick.as_str()
+            .and_then(|nick| Nickname::new(nick, casemapping)),
+        message.as_str(),
+    ) else {
+        return;
+    };
+
+    let ConnectionState::Connected(ConnectedState { user_messages, .. }) = &mut state.conn_state
+    else {
+        return;
+    };
+    let target = match user_messages.get_mut(&nick) {
+        Some(messages) => {
+            if messages.away_message.as_deref() == Some(message) {
+                return;
+            }
+            messages.away_message = Some(message.to_string());
+            Target::Nickname(nick.clone())
+        }
+        None => state.current_target().clone(),
+    };
+
+    state.add_line(
+        target,
+        away_line(&state.config.nick_colors, &nick, Some(message)),
+    );
+}
+
+/// handles AWAY from `away-notify`, which is sent when a user in one of our channels becomes away
+/// or comes back
+pub(super) fn notify(state: &mut ClientState, nick: &str, message: Option<&str>) {
+    let Some(nick) = Nickname::new(nick, state.casemapping()) else {
+        return;
+    };
+    let ConnectionState::Connected(ConnectedState {
+        channels,
+        user_messages,
+        ..
+    }) = &mut state.conn_state
+    else {
+        return;
+    };
+
+    for channel in channels.values_mut() {
+        if let Some(member) = channel.users.get_mut(&nick) {
+            member.away = message.is_some();
+        }
+    }
+
+    // only the query window shows the change, since it would be too noisy in channels
+    let Some(messages) = user_messages.get_mut(&nick) else {
+        return;
+    };
+    if messages.away_message.as_deref() == message {
+        return;
+    }
+    messages.away_message = message.map(str::to_string);
+    state.add_line(
+        Target::Nickname(nick.clone()),
+        away_line(&state.config.nick_colors, &nick, message),
+    );
+}
+
+fn away_line(colors: &NickColors, nick: &Nickname, message: Option<&str>) -> Line<'static> {
+    let line = util::line_now().join(util::nick_line(colors, nick.as_str(), false));
+    match message {
+        Some(message) => line
+            .push_themed(Role::Dim, " is away: ")
+            .push_unstyled(message),
+        None => line.push_themed(Role::Dim, " is back"),
+    }
+}
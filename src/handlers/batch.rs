@@ -0,0 +1,362 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use log::*;
+
+use crate::{
+    channel::{ChannelName, Nickname},
+    handlers::{
+        history as chathistory,
+        msg::{self, MessageKind},
+    },
+    irc::{IrcCommand, IrcMessage, Source},
+    state::{ClientState, ConnectedState, ConnectionState},
+    targets::Target,
+    ui::{text::Line, theme::Role},
+    util,
+};
+
+/// the most nicks that are shown in the summary of a netsplit or netjoin
+const MAX_SUMMARY_NICKS: usize = 10;
+/// the most items that a batch can hold before its messages are handled as if they weren't batched
+const MAX_BATCH_ITEMS: usize = 10_000;
+/// how long a batch can stay open before its messages are handled as if they weren't batched
+const MAX_BATCH_AGE: Duration = Duration::from_secs(60);
+
+/// a batch of messages, which may contain other batches
+#[derive(Debug)]
+pub struct Batch {
+    kind: String,
+    params: Vec<String>,
+    /// the label of the command that the batch is the reply to
+    label: Option<String>,
+    items: Vec<Item>,
+    started: Instant,
+}
+
+#[derive(Debug)]
+enum Item {
+    Message(IrcMessage),
+    Batch(Batch),
+}
+
+/// the batches that the server has started but not ended yet
+#[derive(Debug, Default)]
+pub struct BatchState {
+    /// each batch by its reference, with the reference of the batch that it's in
+    open: HashMap<String, (Option<String>, Batch)>,
+    /// the references of batches that were flushed before they ended, so that their end is
+    /// expected
+    flushed: HashSet<String>,
+}
+
+/// handles BATCH, which starts or ends a batch. batches that are in another batch are handled when
+/// the outer batch ends.
+pub(super) fn handle(
+    state: &mut ClientState,
+    msg: &IrcMessage,
+    reference: &str,
+    kind: Option<&str>,
+    params: &[String],
+) -> eyre::Result<()> {
+    let ConnectionState::Connected(ConnectedState { batches, .. }) = &mut state.conn_state else {
+        return Ok(());
+    };
+
+    if let Some(kind) = kind {
+        let parent = msg
+            .tags
+            .get("batch")
+            .filter(|parent| batches.open.contains_key(*parent))
+            .map(str::to_string);
+        let batch = Batch {
+            kind: kind.to_string(),
+            params: params.to_vec(),
+            label: msg.tags.get("label").map(str::to_string),
+            items: Vec::new(),
+            started: Instant::now(),
+        };
+        batches.open.insert(reference.to_string(), (parent, batch));
+        return Ok(());
+    }
+
+    if batches.flushed.remove(reference) {
+        return Ok(());
+    }
+    let Some((parent, batch)) = batches.open.remove(reference) else {
+        state.warn_in(
+            &Target::Status,
+            format!("end of unknown batch {}", reference),
+        );
+        return Ok(());
+    };
+    match parent.and_then(|parent| batches.open.get_mut(&parent)) {
+        Some((_, parent)) => {
+            parent.items.push(Item::Batch(batch));
+            Ok(())
+        }
+        None => process(state, batch),
+    }
+}
+
+/// adds a message to the batch in its `batch` tag, returning false if it isn't in an open batch.
+/// a batch that is full is flushed instead, and the message is handled after its items.
+pub(super) fn add(state: &mut ClientState, msg: &IrcMessage) -> eyre::Result<bool> {
+    let Some(reference) = msg.tags.get("batch") else {
+        return Ok(false);
+    };
+    let ConnectionState::Connected(ConnectedState { batches, .. }) = &mut state.conn_state else {
+        return Ok(false);
+    };
+    match batches.open.get_mut(reference) {
+        Some((_, batch)) if batch.items.len() >= MAX_BATCH_ITEMS => {
+            flush(state, reference, "has too many messages")?;
+            Ok(false)
+        }
+        Some((_, batch)) => {
+            batch.items.push(Item::Message(msg.clone()));
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// flushes the batches that have been open for too long, since the server may never end them
+pub fn expire(state: &mut ClientState) -> eyre::Result<()> {
+    let ConnectionState::Connected(ConnectedState { batches, .. }) = &state.conn_state else {
+        return Ok(());
+    };
+    let expired = batches
+        .open
+        .iter()
+        .filter(|(_, (_, batch))| batch.started.elapsed() >= MAX_BATCH_AGE)
+        .map(|(reference, _)| reference.clone())
+        .collect::<Vec<_>>();
+    for reference in expired {
+        flush(state, &reference, "was open for too long")?;
+    }
+    Ok(())
+}
+
+/// handles the items of a batch as if they weren't batched, along with the rest of its messages as
+/// they arrive
+fn flush(state: &mut ClientState, reference: &str, reason: &str) -> eyre::Result<()> {
+    let ConnectionState::Connected(ConnectedState { batches, .. }) = &mut state.conn_state else {
+        return Ok(());
+    };
+    let Some((_, batch)) = batches.open.remove(reference) else {
+        return Ok(());
+    };
+    batches.flushed.insert(reference.to_string());
+    state.warn_in(
+        &Target::Status,
+        format!(
+            "{} batch {} {}, showing its messages without it",
+            batch.kind, reference, reason
+        ),
+    );
+    // NOTE: the label is still added to the messages, so that the replies to our own commands are
+    // matched to them
+    labeled(state, batch)
+}
+
+fn process(state: &mut ClientState, batch: Batch) -> eyre::Result<()> {
+    debug!(
+        "processing {} batch with {} items",
+        batch.kind,
+        batch.items.len()
+    );
+    match batch.kind.as_str() {
+        "netsplit" => netsplit(state, batch),
+        "netjoin" => netjoin(state, batch),
+        "chathistory" => history(state, batch),
+        "draft/chathistory-targets" => targets(state, batch),
+        "labeled-response" => labeled(state, batch),
+        // batches that the client doesn't know about are handled as if they weren't batched
+        _ => batch
+            .items
+            .into_iter()
+            .try_for_each(|item| handle_item(state, item)),
+    }
+}
+
+fn handle_item(state: &mut ClientState, item: Item) -> eyre::Result<()> {
+    match item {
+        Item::Message(msg) => msg.handle(state),
+        Item::Batch(batch) => process(state, batch),
+    }
+}
+
+/// handles the replies to a labelled command, which only have the label on the batch
+fn labeled(state: &mut ClientState, batch: Batch) -> eyre::Result<()> {
+    for item in batch.items {
+        match (item, &batch.label) {
+            (Item::Message(mut msg), Some(label)) if msg.tags.get("label").is_none() => {
+                msg.tags.insert("label", label.as_str());
+                msg.handle(state)?;
+            }
+            (item, _) => handle_item(state, item)?,
+        }
+    }
+    Ok(())
+}
+
+/// adds a nick to the nicks for a window, keeping the windows in the order that they were first
+/// seen
+fn add_nick(nicks: &mut Vec<(Target, Vec<String>)>, target: Target, nick: &str) {
+    match nicks.iter_mut().find(|(other, _)| *other == target) {
+        Some((_, nicks)) => nicks.push(nick.to_string()),
+        None => nicks.push((target, vec![nick.to_string()])),
+    }
+}
+
+/// shows a netsplit as one line in each window, instead of a QUIT for every user that was split
+fn netsplit(state: &mut ClientState, batch: Batch) -> eyre::Result<()> {
+    let mut quits = Vec::new();
+    let mut time = None;
+    for item in batch.items {
+        let Item::Message(
+            msg @ IrcMessage {
+                message: IrcCommand::Quit(_),
+                source: Some(Source::Nick(nick, _, _)),
+                ..
+            },
+        ) = &item
+        else {
+            handle_item(state, item)?;
+            continue;
+        };
+        time.get_or_insert_with(|| msg.time());
+        let Some(nick) = Nickname::new(nick.as_str(), state.casemapping()) else {
+            continue;
+        };
+        for target in super::remove_user(state, &nick) {
+            add_nick(&mut quits, target, nick.as_str());
+        }
+    }
+
+    let time = time.unwrap_or_else(Utc::now);
+    for (target, nicks) in quits {
+        let line = summary(state, Role::Quit, "netsplit", &batch.params, time, &nicks);
+        state.add_line(target, line);
+    }
+    Ok(())
+}
+
+/// shows the users that came back after a netsplit as one line in each channel
+fn netjoin(state: &mut ClientState, batch: Batch) -> eyre::Result<()> {
+    let mut joins = Vec::new();
+    let mut time = None;
+    for item in batch.items {
+        let Item::Message(
+            msg @ IrcMessage {
+                message: IrcCommand::Join(channels),
+                source: Some(source @ Source::Nick(nick, _, _)),
+                ..
+            },
+        ) = &item
+        else {
+            handle_item(state, item)?;
+            continue;
+        };
+        time.get_or_insert_with(|| msg.time());
+        let casemapping = state.casemapping();
+        let channels = channels
+            .iter()
+            .filter_map(|(channel, _)| ChannelName::new(channel.as_str(), casemapping));
+        for channel_name in channels {
+            super::add_member(state, &channel_name, source);
+            add_nick(&mut joins, Target::Channel(channel_name), nick.as_str());
+        }
+    }
+
+    let time = time.unwrap_or_else(Utc::now);
+    for (target, nicks) in joins {
+        let line = summary(state, Role::Join, "netjoin", &batch.params, time, &nicks);
+        state.add_line(target, line);
+    }
+    Ok(())
+}
+
+/// a line like `netsplit between a.example and b.example: alice, bob and 3 more`
+fn summary(
+    state: &ClientState,
+    role: Role,
+    name: &str,
+    params: &[String],
+    time: DateTime<Utc>,
+    nicks: &[String],
+) -> Line<'static> {
+    let heading = match params {
+        [from, to, ..] => format!("{} between {} and {}: ", name, from, to),
+        _ => format!("{}: ", name),
+    };
+    let mut line = util::line_at(time).push_themed(role, heading);
+    for (idx, nick) in nicks.iter().take(MAX_SUMMARY_NICKS).enumerate() {
+        if idx > 0 {
+            line = line.push_unstyled(", ");
+        }
+        line = line.join(util::nick_line(&state.config.nick_colors, nick, false));
+    }
+    if nicks.len() > MAX_SUMMARY_NICKS {
+        line = line.push_unstyled(format!(" and {} more", nicks.len() - MAX_SUMMARY_NICKS));
+    }
+    line
+}
+
+/// shows the messages from CHATHISTORY, which are put in order with the lines that are already in
+/// each window. other events aren't handled, since they would change channels back to how they
+/// were in the past.
+fn history(state: &mut ClientState, batch: Batch) -> eyre::Result<()> {
+    let count = batch.items.len();
+    if let Some(target) = batch.params.first() {
+        chathistory::received(state, target, count);
+    }
+    for item in batch.items {
+        let msg = match item {
+            Item::Message(msg) => msg,
+            Item::Batch(batch) => {
+                process(state, batch)?;
+                continue;
+            }
+        };
+        let (kind, targets, text) = match &msg.message {
+            IrcCommand::Privmsg { targets, msg } => (MessageKind::Privmsg, targets, msg),
+            IrcCommand::Notice { targets, msg } => (MessageKind::Notice, targets, msg),
+            other => {
+                debug!("not showing {:?} from history", other);
+                continue;
+            }
+        };
+        let casemapping = state.casemapping();
+        let targets = targets
+            .iter()
+            .filter_map(|target| Target::new(target.as_str(), casemapping));
+        for target in targets {
+            msg::handle_message(state, kind, &msg, &target, text.as_str(), true);
+        }
+    }
+    Ok(())
+}
+
+/// the conversations from CHATHISTORY TARGETS, where each item is like
+/// `CHATHISTORY TARGETS <target> <timestamp>`
+fn targets(state: &mut ClientState, batch: Batch) -> eyre::Result<()> {
+    let targets = batch
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Message(IrcMessage {
+                message: IrcCommand::Unknown(name, params),
+                ..
+            }) if name.eq_ignore_ascii_case("CHATHISTORY") => params.get(1)?.as_str(),
+            _ => None,
+        })
+        .map(str::to_string)
+        .collect();
+    chathistory::targets(state, targets);
+    Ok(())
+}
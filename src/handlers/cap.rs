@@ -0,0 +1,66 @@
+use crate::{
+    caps::Caps,
+    irc::client::{CapCommand, ClientIrcCommand, ClientMessage},
+    state::{ClientState, ConnectedState, ConnectionState, RegistrationState},
+    targets::Target,
+    ui::theme::Role,
+    util,
+};
+
+fn caps_mut<'s>(state: &'s mut ClientState) -> &'s mut Caps {
+    match &mut state.conn_state {
+        ConnectionState::Registration(RegistrationState { caps, .. }) => caps,
+        ConnectionState::Connected(ConnectedState { caps, .. }) => caps,
+    }
+}
+
+/// handles a CAP reply, requesting the capabilities that the client wants and finishing
+/// negotiation during registration
+pub(super) fn handle(state: &mut ClientState, subcommand: &str, more: bool, caps: &[String]) {
+    let registering = matches!(state.conn_state, ConnectionState::Registration(_));
+    let send_cap = |state: &ClientState, cap: CapCommand| {
+        state.send_msg(ClientMessage::from_command(ClientIrcCommand::Cap(cap)));
+    };
+
+    match subcommand {
+        // NOTE: NEW is only sent after registration, once cap-notify is enabled by CAP LS 302
+        "LS" | "NEW" => {
+            caps_mut(state).add_available(caps);
+            // wait for the rest of a multiline LS
+            if more {
+                return;
+            }
+            let to_request = caps_mut(state).to_request();
+            if !to_request.is_empty() {
+                send_cap(state, CapCommand::Req(to_request));
+            } else if registering {
+                send_cap(state, CapCommand::End);
+            }
+        }
+        "ACK" => {
+            caps_mut(state).acknowledge(caps);
+            let line = util::line_now()
+                .push_themed(Role::Info, "enabled capabilities: ")
+                .push_unstyled(caps.join(" "));
+            state.add_line(Target::Status, line);
+            if registering && !more {
+                send_cap(state, CapCommand::End);
+            }
+        }
+        "NAK" => {
+            state.warn_in(
+                &Target::Status,
+                format!("server refused capabilities: {}", caps.join(" ")),
+            );
+            if registering && !more {
+                send_cap(state, CapCommand::End);
+            }
+        }
+        "DEL" => caps_mut(state).remove(caps),
+        // LIST is only a reply to a request that the client doesn't make
+        _ => state.warn_in(
+            &Target::Status,
+            format!("unexpected CAP {} {}", subcommand, caps.join(" ")),
+        ),
+    }
+}
@@ -19,11 +19,11 @@ pub enum CtcpCommand {
 }
 
 impl CtcpCommand {
-    pub fn to_msg(self, targets: Vec<irc::Target>) -> ClientMessage {
-        ClientMessage::from_command(ClientIrcCommand::Privmsg {
+    pub fn to_command(self, targets: Vec<irc::Target>) -> ClientIrcCommand {
+        ClientIrcCommand::Privmsg {
             targets,
             msg: self.irc_string(),
-        })
+        }
     }
 
     fn irc_string(&self) -> String {
@@ -32,10 +32,15 @@ impl CtcpCommand {
             CtcpCommand::Clientinfo => String::from("CLIENTINFO"),
         };
 
-        format!("{}{}{}", CTCP_DELIM_STR, inner, CTCP_DELIM_STR)
+        encode(inner.as_str())
     }
 }
 
+/// wraps a CTCP command and its params in the CTCP delimiters, for sending in a message
+pub fn encode(inner: &str) -> String {
+    format!("{}{}{}", CTCP_DELIM_STR, inner, CTCP_DELIM_STR)
+}
+
 pub fn parse_ctcp(msg: &str) -> Option<CtcpCommand> {
     let mut bytes = &msg.as_bytes()[1..];
 
@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc::Sender,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    casemap::Casemapping,
+    irc::{
+        client::{ClientIrcCommand, ClientMessage},
+        IrcCommand, IrcMessage, Param,
+    },
+    state::{ClientState, ConnectedState, ConnectionState},
+    targets::Target,
+    ui::{text::Line, theme::Role},
+};
+
+/// how long to wait for the echo of a message before showing that it may not have been sent
+const ECHO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// our own messages that were sent with a label and haven't been echoed back yet
+#[derive(Debug, Default)]
+pub struct EchoState {
+    next_label: u64,
+    /// the window that each waiting message is shown in and when to stop waiting for its echo, by
+    /// its label
+    pending: HashMap<String, (Target, Instant)>,
+}
+
+impl EchoState {
+    /// compares the windows of waiting messages with a new casemapping
+    pub fn rekey(&mut self, casemapping: Casemapping) {
+        for (target, _) in self.pending.values_mut() {
+            *target = target.rekey(casemapping);
+        }
+    }
+}
+
+/// sends one of our own messages and shows it. when the server echoes messages with the label
+/// that they were sent with, the line is dimmed until the echo replaces it. when it echoes them
+/// without labels, only the echo is shown, since it can't be matched to the line.
+pub fn send(
+    state: &mut ClientState,
+    sender: &Sender<ClientMessage>,
+    target: Target,
+    command: ClientIrcCommand,
+    line: Line<'static>,
+) -> eyre::Result<()> {
+    let ConnectionState::Connected(ConnectedState { caps, echo, .. }) = &mut state.conn_state
+    else {
+        sender.send(ClientMessage::from_command(command))?;
+        state.add_line(target, line);
+        return Ok(());
+    };
+
+    if !caps.is_enabled("echo-message") {
+        sender.send(ClientMessage::from_command(command))?;
+        state.add_line(target, line);
+    } else if caps.is_enabled("labeled-response") {
+        let label = echo.next_label.to_string();
+        echo.next_label += 1;
+        echo.pending.insert(
+            label.clone(),
+            (target.clone(), Instant::now() + ECHO_TIMEOUT),
+        );
+        sender.send(ClientMessage::from_command(command).with_tag("label", label.as_str()))?;
+        state.add_line(target, line.with_label(label));
+    } else {
+        sender.send(ClientMessage::from_command(command))?;
+    }
+    Ok(())
+}
+
+/// the window of the line that is waiting for a label, forgetting about the label
+fn take(state: &mut ClientState, label: &str) -> Option<Target> {
+    let ConnectionState::Connected(ConnectedState { echo, .. }) = &mut state.conn_state else {
+        return None;
+    };
+    echo.pending.remove(label).map(|(target, _)| target)
+}
+
+/// replaces the label of the line that was waiting for an echo with a note about why it won't get
+/// one
+fn mark(state: &mut ClientState, target: &Target, label: &str, role: Role, note: String) {
+    let Some(line) = state
+        .lines_for(target)
+        .and_then(|lines| lines.iter_mut().find(|line| line.label() == Some(label)))
+    else {
+        return;
+    };
+    *line = core::mem::take(line)
+        .without_label()
+        .push_themed(role, note);
+}
+
+/// removes the line that was waiting for an echo, since the echo is shown instead
+pub(super) fn echoed(state: &mut ClientState, label: &str) {
+    let Some(target) = take(state, label) else {
+        return;
+    };
+    if let Some(lines) = state.lines_for(&target) {
+        lines.retain(|line| line.label() != Some(label));
+    }
+}
+
+/// marks our own message as not sent if the message is an error reply to it, which is a numeric
+/// error or a FAIL. returns whether it was.
+pub(super) fn failed(state: &mut ClientState, label: &str, msg: &IrcMessage) -> bool {
+    let args = match &msg.message {
+        IrcCommand::Numeric { num, args } if *num >= 400 => args,
+        IrcCommand::Unknown(name, args) if name == "FAIL" => args,
+        _ => return false,
+    };
+    let Some(target) = take(state, label) else {
+        return false;
+    };
+    let reason = args
+        .last()
+        .and_then(Param::as_str)
+        .unwrap_or("<MISSING>")
+        .to_string();
+    mark(
+        state,
+        &target,
+        label,
+        Role::Error,
+        format!(" (not sent: {})", reason),
+    );
+    let _ = state.render();
+    true
+}
+
+/// stops waiting for echoes that are taking too long, marking their lines as not confirmed. the
+/// server may still have sent the messages.
+pub fn expire(state: &mut ClientState) {
+    let ConnectionState::Connected(ConnectedState { echo, .. }) = &mut state.conn_state else {
+        return;
+    };
+    let now = Instant::now();
+    let expired = echo
+        .pending
+        .iter()
+        .filter(|(_, (_, deadline))| now >= *deadline)
+        .map(|(label, _)| label.clone())
+        .collect::<Vec<_>>();
+    if expired.is_empty() {
+        return;
+    }
+    for label in expired {
+        if let Some(target) = take(state, &label) {
+            mark(
+                state,
+                &target,
+                &label,
+                Role::Warning,
+                String::from(" (not confirmed)"),
+            );
+        }
+    }
+    let _ = state.render();
+}
+
+/// stops waiting for every echo after the connection is lost, marking their lines as not
+/// confirmed
+pub fn disconnected(state: &mut ClientState) {
+    let ConnectionState::Connected(ConnectedState { echo, .. }) = &mut state.conn_state else {
+        return;
+    };
+    let pending = echo.pending.drain().collect::<Vec<_>>();
+    for (label, (target, _)) in pending {
+        mark(
+            state,
+            &target,
+            &label,
+            Role::Warning,
+            String::from(" (not confirmed: disconnected)"),
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use log::*;
+
+use crate::{
+    casemap::Casemapping,
+    irc::{
+        client::{ClientIrcCommand, ClientMessage},
+        Param,
+    },
+    state::{ClientState, ConnectedState, ConnectionState},
+    targets::Target,
+};
+
+/// the most messages to ask for at once, which is lowered to the server's limit
+const HISTORY_LIMIT: usize = 50;
+/// how far back TARGETS looks for conversations that had activity while we were away
+// NOTE: the time that we were last connected isn't kept between runs, so this is a guess
+const TARGETS_SINCE: TimeDelta = TimeDelta::days(1);
+/// how long to wait for history before asking for it again is allowed
+const HISTORY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// the history that has been asked for with CHATHISTORY
+#[derive(Debug, Default)]
+pub struct HistoryState {
+    /// the targets that history was asked for but hasn't arrived yet, with how many messages were
+    /// asked for and when to stop waiting for them
+    pending: HashMap<Target, (usize, Instant)>,
+    /// the targets that have no more history before their oldest line
+    exhausted: HashSet<Target>,
+}
+
+impl HistoryState {
+    /// compares the targets with a new casemapping
+    pub fn rekey(&mut self, casemapping: Casemapping) {
+        self.pending = self
+            .pending
+            .drain()
+            .map(|(target, pending)| (target.rekey(casemapping), pending))
+            .collect();
+        self.exhausted = self
+            .exhausted
+            .drain()
+            .map(|target| target.rekey(casemapping))
+            .collect();
+    }
+}
+
+/// the most messages to ask for, from the CHATHISTORY ISUPPORT token where 0 means no limit
+fn limit(state: &ConnectedState) -> usize {
+    state
+        .isupport
+        .value("CHATHISTORY")
+        .and_then(|limit| limit.parse::<usize>().ok())
+        .filter(|limit| *limit > 0)
+        .map_or(HISTORY_LIMIT, |limit| limit.min(HISTORY_LIMIT))
+}
+
+/// the timestamp selector for a time, which must be in UTC with milliseconds
+fn timestamp(time: DateTime<Utc>) -> String {
+    format!(
+        "timestamp={}",
+        time.to_rfc3339_opts(SecondsFormat::Millis, true)
+    )
+}
+
+/// sends a CHATHISTORY request for a target, unless history is already on its way or isn't
+/// supported. a request that the server never replied to is made again after a while.
+fn request(state: &mut ClientState, target: &Target, subcommand: &'static str, selector: String) {
+    let ConnectionState::Connected(connected) = &mut state.conn_state else {
+        return;
+    };
+    if !connected.caps.is_enabled("draft/chathistory")
+        || connected
+            .history
+            .pending
+            .get(target)
+            .is_some_and(|(_, deadline)| Instant::now() < *deadline)
+    {
+        return;
+    }
+    let Some(irc_target) = target.irc_target() else {
+        return;
+    };
+    let limit = limit(connected);
+    connected
+        .history
+        .pending
+        .insert(target.clone(), (limit, Instant::now() + HISTORY_TIMEOUT));
+    debug!("fetching {} history for {}", subcommand, target.as_str());
+    state.send_msg(ClientMessage::from_command(ClientIrcCommand::Chathistory {
+        subcommand,
+        params: vec![irc_target.as_str().to_string(), selector, limit.to_string()],
+    }));
+}
+
+/// asks for the most recent messages of a target, like when we join a channel
+pub(super) fn fetch_latest(state: &mut ClientState, target: &Target) {
+    request(state, target, "LATEST", String::from("*"));
+}
+
+/// asks for the messages before the oldest line of a target, after scrolling past the top of it.
+/// the messages are put in order with the other lines, so they end up above what's on screen.
+pub fn fetch_before(state: &mut ClientState, target: &Target) {
+    if let ConnectionState::Connected(ConnectedState { history, .. }) = &state.conn_state
+        && history.exhausted.contains(target)
+    {
+        return;
+    }
+    let oldest = state.lines_for(target).and_then(|lines| {
+        lines
+            .iter()
+            .find_map(|line| line.time().map(|time| (time, line.msgid())))
+            .map(|(time, msgid)| match msgid {
+                Some(msgid) => format!("msgid={}", msgid),
+                None => timestamp(time),
+            })
+    });
+    match oldest {
+        Some(selector) => request(state, target, "BEFORE", selector),
+        None => fetch_latest(state, target),
+    }
+}
+
+/// asks for the conversations that had activity while we were away, once registration has
+/// finished
+pub(super) fn start(state: &mut ClientState) {
+    let ConnectionState::Connected(connected) = &state.conn_state else {
+        return;
+    };
+    if !connected.caps.is_enabled("draft/chathistory") {
+        return;
+    }
+    let now = Utc::now();
+    let limit = limit(connected);
+    state.send_msg(ClientMessage::from_command(ClientIrcCommand::Chathistory {
+        subcommand: "TARGETS",
+        params: vec![
+            timestamp(now),
+            timestamp(now - TARGETS_SINCE),
+            limit.to_string(),
+        ],
+    }));
+}
+
+/// notes that the history of a target has arrived with some number of messages. getting fewer
+/// messages than were asked for means that there are no more.
+pub(super) fn received(state: &mut ClientState, target: &str, count: usize) {
+    let Some(target) = Target::new(target, state.casemapping()) else {
+        return;
+    };
+    let ConnectionState::Connected(ConnectedState { history, .. }) = &mut state.conn_state else {
+        return;
+    };
+    let Some((limit, _)) = history.pending.remove(&target) else {
+        return;
+    };
+    if count < limit {
+        history.exhausted.insert(target);
+    }
+}
+
+/// handles `FAIL CHATHISTORY <code> [context...] <description>`. the context doesn't always say
+/// which target failed, so every request is forgotten and can be made again.
+pub(super) fn failed(state: &mut ClientState, params: &[Param]) {
+    if let ConnectionState::Connected(ConnectedState { history, .. }) = &mut state.conn_state {
+        history.pending.clear();
+    }
+    let description = params.last().and_then(Param::as_str).unwrap_or("<MISSING>");
+    state.warn_in(
+        &Target::Status,
+        format!("could not fetch history: {}", description),
+    );
+}
+
+/// handles an error that names the command that it's for, forgetting every request if it's
+/// CHATHISTORY like `failed` does. the error itself is shown as usual.
+pub(super) fn handle_error(state: &mut ClientState, command: &str) {
+    if command.eq_ignore_ascii_case("CHATHISTORY")
+        && let ConnectionState::Connected(ConnectedState { history, .. }) = &mut state.conn_state
+    {
+        history.pending.clear();
+    }
+}
+
+/// opens windows for the nicks that TARGETS says we talked to while we were away, with their
+/// latest messages. channels get their history when they're joined.
+pub(super) fn targets(state: &mut ClientState, targets: Vec<String>) {
+    let casemapping = state.casemapping();
+    for target in targets
+        .into_iter()
+        .filter_map(|target| Target::new(target, casemapping))
+    {
+        if matches!(target, Target::Nickname(_)) {
+            state.ensure_target_exists(target.clone());
+            fetch_latest(state, &target);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crossterm::style::Stylize;
+
+use crate::{
+    channel::{mode, ChannelName},
+    irc::{
+        client::{ClientIrcCommand, ClientMessage},
+        Param,
+    },
+    state::{ClientState, ConnectedState, ConnectionState},
+    targets::Target,
+    ui::theme::Role,
+    util,
+};
+
+/// handles INVITE, which is either an invite for us or, with `invite-notify`, an invite for
+/// someone else to a channel that we're in
+pub(super) fn received(state: &mut ClientState, inviter: &str, nick: &str, channel: &str) {
+    let casemapping = state.casemapping();
+    let ConnectionState::Connected(ConnectedState {
+        nick: our_nick,
+        last_invite,
+        ..
+    }) = &mut state.conn_state
+    else {
+        return;
+    };
+    let Some(channel_name) = ChannelName::new(channel, casemapping) else {
+        state.warn_in(
+            &Target::Status,
+            format!("INVITE to invalid channel {:?}", channel),
+        );
+        return;
+    };
+
+    if !casemapping.same(nick, our_nick) {
+        let line = util::line_now()
+            .join(util::nick_line(&state.config.nick_colors, inviter, false))
+            .push_unstyled(" invited ")
+            .join(util::nick_line(&state.config.nick_colors, nick, false))
+            .push_unstyled(" to ")
+            .push_themed(Role::Channel, channel.to_string());
+        state.add_line(Target::Channel(channel_name), line);
+        return;
+    }
+
+    let auto_join = state
+        .config
+        .auto_join_invites
+        .iter()
+        .any(|allowed| mode::mask_matches(allowed, channel, casemapping));
+    let hint = if auto_join {
+        String::from(", joining")
+    } else {
+        *last_invite = Some(channel_name.clone());
+        String::from(" (ctrl-y or /accept to join)")
+    };
+    let inviter_color = state.config.nick_colors.color(inviter);
+    let line = || {
+        util::line_now()
+            .push(inviter.to_string().with(inviter_color).bold())
+            .push_themed(Role::Highlight, " invited you to ")
+            .push_themed(Role::Highlight, channel)
+            .push_themed(Role::Highlight, hint.as_str())
+    };
+
+    // invites are shown in both the status window and the current window so they aren't missed
+    let current = state.current_target().clone();
+    if current != Target::Status {
+        state.add_line(current, line());
+    }
+    state.add_line(Target::Status, line());
+
+    if auto_join {
+        state.send_msg(ClientMessage::from_command(ClientIrcCommand::Join(vec![(
+            channel_name,
+            None,
+        )])));
+    }
+}
+
+/// handles RPL_INVITING, which confirms that an invite that we sent was delivered
+pub(super) fn inviting(state: &mut ClientState, args: &[Param]) {
+    let [_, nick, channel, ..] = args else {
+        state.warn_in(&Target::Status, String::from("RPL_INVITING missing params"));
+        return;
+    };
+    let (Some(nick), Some(channel)) = (nick.as_str(), channel.as_str()) else {
+        return;
+    };
+
+    let line = util::line_now()
+        .push_unstyled("invited ")
+        .join(util::nick_line(&state.config.nick_colors, nick, false))
+        .push_unstyled(" to ")
+        .push_themed(Role::Channel, channel.to_string());
+    let target = match ChannelName::new(channel, state.casemapping()) {
+        Some(channel_name) => Target::Channel(channel_name),
+        None => state.current_target().clone(),
+    };
+    state.add_line(target, line);
+}
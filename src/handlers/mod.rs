@@ -1,20 +1,38 @@
-use crossterm::style::Stylize as _;
+use chrono::{DateTime, Local};
 use eyre::{bail, eyre};
 
 use crate::{
-    channel::{Channel, ChannelName},
+    channel::{
+        list::{ListWindow, ListedChannel, ModeList},
+        mode::{self, ListEntry, ModeRules},
+        Channel, ChannelName, Member, Nickname,
+    },
     irc::{
         client::{ClientIrcCommand, ClientMessage},
         IrcCommand, IrcMessage, Param, Source,
     },
     state::{ClientState, ConnectedState, ConnectionState, NamesState, RegistrationState},
     targets::Target,
-    ui::text::Line,
+    ui::{
+        format,
+        template::{self, Kind},
+        text::Line,
+        theme::Role,
+    },
     util,
 };
 
+pub mod away;
+pub mod batch;
+pub mod cap;
 pub mod ctcp;
+pub mod echo;
+pub mod history;
+pub mod invite;
 mod msg;
+pub mod notify;
+pub mod who;
+pub mod whois;
 
 macro_rules! expect_connected_state {
     ($state:expr, $msg:expr) => {
@@ -25,6 +43,86 @@ macro_rules! expect_connected_state {
     };
 }
 
+/// gets a channel that has a window, whether or not it's joined
+fn channel_mut<'s>(state: &'s mut ClientState, name: &ChannelName) -> Option<&'s mut Channel> {
+    match &mut state.conn_state {
+        ConnectionState::Connected(ConnectedState { channels, .. }) => channels.get_mut(name),
+        ConnectionState::Registration(_) => None,
+    }
+}
+
+/// removes a user that quit from every channel, returning the windows that they could be seen in
+fn remove_user(state: &mut ClientState, nick: &Nickname) -> Vec<Target> {
+    let ConnectionState::Connected(ConnectedState {
+        channels,
+        user_messages,
+        ..
+    }) = &mut state.conn_state
+    else {
+        return Vec::new();
+    };
+    let mut targets = channels
+        .values_mut()
+        .filter_map(|channel| {
+            channel
+                .users
+                .remove(nick)
+                .map(|_| Target::Channel(channel.name().clone()))
+        })
+        .collect::<Vec<_>>();
+    if user_messages.contains_key(nick) {
+        targets.push(Target::Nickname(nick.clone()));
+    }
+    targets
+}
+
+/// adds a user that joined a channel to its members, which starts a fresh list of members if it's
+/// us that joined
+fn add_member(state: &mut ClientState, channel_name: &ChannelName, source: &Source) {
+    let ConnectionState::Connected(ConnectedState { nick, .. }) = &state.conn_state else {
+        return;
+    };
+    let casemapping = state.casemapping();
+    let join_nick = source.get_name();
+    let ours = casemapping.same(join_nick, nick);
+    let (user, host) = match source {
+        Source::Nick(_, user, host) => (user.clone(), host.clone()),
+        Source::Server(_) => (None, None),
+    };
+
+    state.ensure_target_exists(Target::Channel(channel_name.clone()));
+    if let Some(channel) = channel_mut(state, channel_name) {
+        // a fresh list of users will be sent with NAMES
+        if ours {
+            channel.joined = true;
+            channel.users.clear();
+        }
+        if let Some(join_nick) = Nickname::new(join_nick, casemapping) {
+            channel.users.insert(
+                join_nick,
+                Member {
+                    user,
+                    host,
+                    ..Member::default()
+                },
+            );
+        }
+    }
+
+    // the server sends NAMES before it handles the WHO, so there will be members to fill in
+    if ours
+        && let ConnectionState::Connected(ConnectedState {
+            messages_state,
+            isupport,
+            ..
+        }) = &mut state.conn_state
+    {
+        messages_state
+            .who_queue
+            .push(isupport, channel_name.clone());
+    }
+}
+
 impl IrcMessage {
     fn unhandled(&self, state: &mut ClientState) {
         state.warn(format!("unhandled msg {:?}", self));
@@ -34,9 +132,27 @@ impl IrcMessage {
     #[allow(unused_must_use)]
     pub fn handle(&self, state: &mut ClientState) -> eyre::Result<()> {
         use crate::constants::numerics::*;
+        // lines are shown with the time that the server says the message was sent, which can be
+        // long ago for backlog from a bouncer
+        let time = self.time();
+        let casemapping = state.casemapping();
+        // messages in a batch are handled when the batch ends
+        if !matches!(self.message, IrcCommand::Batch { .. }) && batch::add(state, self)? {
+            return Ok(());
+        }
+        // errors for our own messages are shown on the message instead
+        if let Some(label) = self.tags.get("label")
+            && echo::failed(state, label, self)
+        {
+            return Ok(());
+        }
         match &self.message {
-            IrcCommand::Cap => {
-                self.unhandled(state);
+            IrcCommand::Cap {
+                subcommand,
+                more,
+                caps,
+            } => {
+                cap::handle(state, subcommand, *more, caps);
             }
             IrcCommand::Authenticate => {
                 self.unhandled(state);
@@ -44,6 +160,14 @@ impl IrcMessage {
             IrcCommand::Nick(_) => {
                 self.unhandled(state);
             }
+            IrcCommand::Batch {
+                reference,
+                kind,
+                params,
+                ..
+            } => {
+                batch::handle(state, self, reference, kind.as_deref(), params)?;
+            }
             IrcCommand::Ping(token) => {
                 state
                     .msg_sender
@@ -57,43 +181,64 @@ impl IrcMessage {
                 };
                 // NOTE: servers SHOULD always send a reason, but make sure
                 let reason = reason.as_deref().unwrap_or("disconnected");
-                state.add_line(
-                    Target::Status,
-                    util::line_now()
-                        .push(name.magenta())
-                        .push_unstyled(" quit: ")
-                        .push_unstyled(reason),
-                );
+                let Some(nick) = Nickname::new(name, casemapping) else {
+                    return Ok(());
+                };
+
+                // show the quit everywhere that we could see the user, or in the status window if
+                // there's nowhere else
+                let _ = expect_connected_state!(state, self)?;
+                let mut targets = remove_user(state, &nick);
+                if targets.is_empty() {
+                    targets.push(Target::Status);
+                }
+                for target in targets {
+                    let line = template::render(
+                        Kind::Quit,
+                        time,
+                        vec![
+                            (
+                                "nick",
+                                util::nick_line(&state.config.nick_colors, name, false),
+                            ),
+                            ("reason", format::parse(reason, state.config.formatting)),
+                        ],
+                    );
+                    state.add_line(target, line);
+                }
             }
             IrcCommand::Join(join_channels) => {
-                let ClientState {
-                    conn_state: ConnectionState::Connected(ConnectedState { .. }),
-                    ..
-                } = state
-                else {
-                    bail!("JOIN messages can only be processed when connected to a server");
+                let Some(source) = self.source.as_ref() else {
+                    state.warn_in(&Target::Status, String::from("JOIN msg without a source"));
+                    return Ok(());
                 };
+                let join_nick = source.get_name();
+                let ConnectedState { nick, .. } = expect_connected_state!(state, self)?;
+                let ours = casemapping.same(join_nick, nick);
 
-                // if the source of the join is ourself, update the list of joined channels,
-                // otherwise announce a join
-                match self.source.as_ref().map(|source| source.get_name()) {
-                    Some(join_nick) => {
-                        let join_channels = join_channels
-                            .iter()
-                            .filter_map(|(channel, _)| Channel::new(channel).ok())
-                            .collect::<Vec<_>>();
-
-                        for channel in join_channels.into_iter() {
-                            let line = util::line_now()
-                                .push(join_nick.magenta())
-                                .push(" joined ".green())
-                                .push(channel.name().as_str().dark_blue());
+                let join_channels = join_channels
+                    .iter()
+                    .filter_map(|(channel, _)| ChannelName::new(channel.as_str(), casemapping));
+                for channel_name in join_channels {
+                    add_member(state, &channel_name, source);
 
-                            state.add_line(Target::Channel(channel.name().clone()), line);
-                        }
-                    }
-                    None => {
-                        state.warn_in(&Target::Status, String::from("JOIN msg without a source"));
+                    let line = template::render(
+                        Kind::Join,
+                        time,
+                        vec![
+                            (
+                                "nick",
+                                util::nick_line(&state.config.nick_colors, join_nick, false),
+                            ),
+                            (
+                                "channel",
+                                Line::default().push_themed(Role::Channel, channel_name.as_str()),
+                            ),
+                        ],
+                    );
+                    state.add_line(Target::Channel(channel_name.clone()), line);
+                    if ours {
+                        history::fetch_latest(state, &Target::Channel(channel_name));
                     }
                 }
             }
@@ -101,30 +246,184 @@ impl IrcMessage {
                 let Some(name) = self.source.as_ref().map(Source::get_name) else {
                     bail!("PART msg had no source");
                 };
+                let ConnectedState { nick, .. } = expect_connected_state!(state, self)?;
+                let ours = casemapping.same(name, nick);
 
-                let channels =
-                    channels
-                        .iter()
-                        .filter_map(|channel_name| match Target::new(channel_name) {
-                            t @ Some(Target::Channel(_)) => t,
-                            _ => None,
-                        });
+                let channels = channels.iter().filter_map(|channel_name| {
+                    ChannelName::new(channel_name.as_str(), casemapping)
+                });
+                for channel_name in channels {
+                    if ours {
+                        if let ConnectionState::Connected(ConnectedState {
+                            messages_state, ..
+                        }) = &mut state.conn_state
+                        {
+                            messages_state.who_queue.remove(&channel_name);
+                        }
+                        // the channel is no longer joined, so its window is closed
+                        state.remove_target(&Target::Channel(channel_name.clone()));
+                        let mut line = util::line_at(time)
+                            .push_unstyled("left ")
+                            .push_themed(Role::Channel, channel_name.as_str());
+                        if let Some(reason) = reason {
+                            line = line.push_unstyled(format!(": {}", reason));
+                        }
+                        state.add_line(Target::Status, line);
+                        continue;
+                    }
 
-                for channel in channels {
-                    let mut line = util::line_now().push(name.magenta()).push_unstyled(" left");
-                    // reasons are entirely optional
-                    if let Some(reason) = reason {
-                        line = line.push_unstyled(format!(": {}", reason));
+                    let Some(channel) = channel_mut(state, &channel_name) else {
+                        continue;
+                    };
+                    if let Some(nick) = Nickname::new(name, casemapping) {
+                        channel.users.remove(&nick);
                     }
 
-                    state.add_line(channel, line);
+                    // reasons are entirely optional
+                    let reason = match reason {
+                        Some(reason) => Line::default()
+                            .push_unstyled(": ")
+                            .join(format::parse(reason, state.config.formatting)),
+                        None => Line::default(),
+                    };
+                    let line = template::render(
+                        Kind::Part,
+                        time,
+                        vec![
+                            (
+                                "nick",
+                                util::nick_line(&state.config.nick_colors, name, false),
+                            ),
+                            (
+                                "channel",
+                                Line::default().push_themed(Role::Channel, channel_name.as_str()),
+                            ),
+                            ("reason", reason),
+                        ],
+                    );
+
+                    state.add_line(Target::Channel(channel_name), line);
                 }
             }
-            IrcCommand::Invite { .. } => {
-                self.unhandled(state);
+            IrcCommand::Topic(channel, topic) => {
+                let setter = self
+                    .source
+                    .as_ref()
+                    .map(Source::get_name)
+                    .unwrap_or("the server")
+                    .to_string();
+                let Some(channel_name) = ChannelName::new(channel.as_str(), casemapping) else {
+                    state.warn_in(
+                        &Target::Status,
+                        format!("TOPIC for invalid channel {}", channel),
+                    );
+                    return Ok(());
+                };
+                let text = topic.clone().unwrap_or_default();
+
+                if let Some(channel) = channel_mut(state, &channel_name) {
+                    channel.set_topic(text.clone());
+                    channel.set_topic_who_time(setter.clone(), Some(time));
+                }
+
+                let line = if text.is_empty() {
+                    util::line_at(time)
+                        .push_themed(Role::Info, "topic cleared by ")
+                        .join(util::nick_line(&state.config.nick_colors, &setter, false))
+                } else {
+                    util::line_at(time)
+                        .push_themed(Role::Info, "topic changed by ")
+                        .join(util::nick_line(&state.config.nick_colors, &setter, false))
+                        .push_unstyled(": ")
+                        .join(format::parse(&text, state.config.formatting))
+                };
+                state.add_line(Target::Channel(channel_name), line);
             }
-            IrcCommand::Kick { .. } => {
-                self.unhandled(state);
+            IrcCommand::Invite { nick, channel } => {
+                let Some(inviter) = self.source.as_ref().map(Source::get_name) else {
+                    bail!("INVITE msg had no source");
+                };
+                invite::received(state, inviter, nick, channel);
+            }
+            IrcCommand::Kick {
+                channel,
+                user,
+                comment,
+            } => {
+                let kicker = self
+                    .source
+                    .as_ref()
+                    .map(Source::get_name)
+                    .unwrap_or("the server")
+                    .to_string();
+                let Some(channel_name) = ChannelName::new(channel.as_str(), casemapping) else {
+                    state.warn_in(
+                        &Target::Status,
+                        format!("KICK for invalid channel {}", channel),
+                    );
+                    return Ok(());
+                };
+                let ConnectedState {
+                    nick,
+                    channel_keys,
+                    messages_state,
+                    ..
+                } = expect_connected_state!(state, self)?;
+                let ours = casemapping.same(user, nick);
+                let key = channel_keys.get(&channel_name).cloned();
+                if ours {
+                    messages_state.who_queue.remove(&channel_name);
+                }
+
+                if let Some(channel) = channel_mut(state, &channel_name) {
+                    if ours {
+                        // the window stays open so that the reason can be seen
+                        channel.joined = false;
+                        channel.users.clear();
+                    } else if let Some(nick) = Nickname::new(user.as_str(), casemapping) {
+                        channel.users.remove(&nick);
+                    }
+                }
+
+                let reason = match comment {
+                    Some(comment) => Line::default()
+                        .push_unstyled(": ")
+                        .join(format::parse(comment, state.config.formatting)),
+                    None => Line::default(),
+                };
+                let line = if ours {
+                    util::line_at(time)
+                        .push_themed(Role::Kick, format!("you were kicked by {}", kicker))
+                        .join(reason)
+                } else {
+                    template::render(
+                        Kind::Kick,
+                        time,
+                        vec![
+                            (
+                                "nick",
+                                util::nick_line(&state.config.nick_colors, user.as_str(), false),
+                            ),
+                            (
+                                "kicker",
+                                util::nick_line(&state.config.nick_colors, &kicker, false),
+                            ),
+                            (
+                                "channel",
+                                Line::default().push_themed(Role::Channel, channel_name.as_str()),
+                            ),
+                            ("reason", reason),
+                        ],
+                    )
+                };
+                state.add_line(Target::Channel(channel_name.clone()), line);
+
+                if ours && state.config.auto_rejoin {
+                    state.send_msg(ClientMessage::from_command(ClientIrcCommand::Join(vec![(
+                        channel_name,
+                        key,
+                    )])));
+                }
             }
             IrcCommand::Mode { target, mode } => {
                 let Some(mode) = mode else {
@@ -134,22 +433,37 @@ impl IrcMessage {
                     );
                     return Ok(());
                 };
-
-                let ClientState {
-                    conn_state: ConnectionState::Connected(ConnectedState { channels, .. }),
-                    ..
-                } = state
-                else {
+                let Some(target) = Target::new(target.as_str(), casemapping) else {
                     state.warn_in(
                         &Target::Status,
-                        String::from("must be connected to handle MODE"),
+                        format!("server sent MODE for invalid target {}", target),
                     );
                     return Ok(());
                 };
+                let setter = self
+                    .source
+                    .as_ref()
+                    .map(Source::get_name)
+                    .unwrap_or("the server")
+                    .to_string();
+                let (modestring, params) = mode.split_once(' ').unwrap_or((mode.as_str(), ""));
+                let params = params
+                    .split(' ')
+                    .filter(|p| !p.is_empty())
+                    .collect::<Vec<_>>();
+
+                let ConnectedState {
+                    nick,
+                    user_modes,
+                    isupport,
+                    ..
+                } = expect_connected_state!(state, self)?;
+                let rules = ModeRules::new(isupport);
+                let changes = mode::parse_changes(&rules, modestring, &params);
 
-                match target {
+                match &target {
                     Target::Channel(channel_name) => {
-                        let Some(channel) = channels.get_mut(channel_name) else {
+                        let Some(channel) = channel_mut(state, channel_name) else {
                             state.warn_in(
                                 &Target::Status,
                                 format!(
@@ -160,14 +474,39 @@ impl IrcMessage {
                             return Ok(());
                         };
 
-                        channel.modes = mode.to_string();
-
-                        // TODO: mode messages
+                        for change in changes.iter() {
+                            channel.apply_mode(&rules, change, Some(setter.as_str()));
+                        }
+                        for change in changes.iter() {
+                            state.add_line(
+                                target.clone(),
+                                util::line_at(time)
+                                    .join(util::nick_line(
+                                        &state.config.nick_colors,
+                                        &setter,
+                                        false,
+                                    ))
+                                    .push_unstyled(format!(" {}", mode::describe(change))),
+                            );
+                        }
+                    }
+                    Target::Nickname(target_nick)
+                        if casemapping.same(target_nick.as_str(), nick) =>
+                    {
+                        mode::apply_user_changes(user_modes, &changes);
+                        let line = util::line_at(time)
+                            .join(util::nick_line(&state.config.nick_colors, &setter, false))
+                            .push_unstyled(format!(" sets your modes {}", modestring))
+                            .push_unstyled(format!(", your modes are now +{}", user_modes));
+                        state.add_line(Target::Status, line);
                     }
                     Target::Nickname(_) => {
-                        state.warn_in(&Target::Status, String::from("MODE for nicknames NYI"));
+                        state.warn_in(
+                            &Target::Status,
+                            String::from("MODE for other users cannot be tracked"),
+                        );
                     }
-                    _ => {
+                    Target::Status | Target::List => {
                         state.warn_in(
                             &Target::Status,
                             String::from("could not determine target for MODE"),
@@ -176,24 +515,32 @@ impl IrcMessage {
                 }
             }
             IrcCommand::Privmsg { targets, msg } => {
+                let targets = targets
+                    .iter()
+                    .filter_map(|target| Target::new(target.as_str(), casemapping));
                 for target in targets {
                     msg::handle_message(
                         state,
                         msg::MessageKind::Privmsg,
-                        &self.source,
+                        self,
                         &target,
                         msg.as_str(),
+                        false,
                     );
                 }
             }
             IrcCommand::Notice { targets, msg } => {
+                let targets = targets
+                    .iter()
+                    .filter_map(|target| Target::new(target.as_str(), casemapping));
                 for target in targets {
                     msg::handle_message(
                         state,
                         msg::MessageKind::Notice,
-                        &self.source,
+                        self,
                         &target,
                         msg.as_str(),
+                        false,
                     );
                 }
             }
@@ -215,7 +562,11 @@ impl IrcMessage {
                 args,
             } => {
                 let ClientState {
-                    conn_state: ConnectionState::Registration(RegistrationState { requested_nick }),
+                    conn_state:
+                        ConnectionState::Registration(RegistrationState {
+                            requested_nick,
+                            caps,
+                        }),
                     ..
                 } = state
                 else {
@@ -226,6 +577,7 @@ impl IrcMessage {
                     return Ok(());
                 };
                 let requested_nick = requested_nick.clone();
+                let caps = core::mem::take(caps);
 
                 let [nick, text, ..] = args.as_slice() else {
                     bail!("RPL_001 had no nick and msg arg");
@@ -244,8 +596,10 @@ impl IrcMessage {
                     );
                 }
 
-                state.conn_state =
-                    ConnectionState::Connected(ConnectedState::new(nick.to_string()));
+                state.conn_state = ConnectionState::Connected(ConnectedState {
+                    caps,
+                    ..ConnectedState::new(nick.to_string())
+                });
                 state.add_line(Target::Status, Line::from(text.to_string()));
             }
 
@@ -289,9 +643,15 @@ impl IrcMessage {
             }
             IrcCommand::Numeric {
                 num: RPL_ISUPPORT,
-                args: _,
+                args,
             } => {
-                //TODO: do we care about this?
+                let ConnectedState { isupport, .. } = expect_connected_state!(state, self)?;
+                isupport.update(args);
+                // the names that are already known were compared with the casemapping before
+                let new_casemapping = isupport.casemapping();
+                if new_casemapping != casemapping {
+                    state.rekey(new_casemapping);
+                }
             }
 
             IrcCommand::Numeric {
@@ -400,23 +760,31 @@ impl IrcMessage {
             } => {
                 state.add_line(
                     Target::Status,
-                    Line::default().push(
+                    Line::default().push_themed(
+                        Role::Warning,
                         format!(
                             "no MOTD: {}",
                             args.get(2).and_then(|p| p.as_str()).unwrap_or("<MISSING>")
-                        )
-                        .yellow(),
+                        ),
                     ),
                 );
+                // the MOTD is the end of registration, so ISUPPORT has been received
+                notify::start(state);
+                history::start(state);
             }
 
             IrcCommand::Numeric {
-                num: RPL_MOTDSTART | RPL_MOTD | RPL_ENDOFMOTD,
+                num: num @ (RPL_MOTDSTART | RPL_MOTD | RPL_ENDOFMOTD),
                 args,
             } => {
                 // display the MOTD to the user
                 if let Some(msg) = args.last().and_then(|p| p.as_str()) {
-                    state.add_line(Target::Status, Line::from(msg.to_string()));
+                    let line = format::parse(msg, state.config.formatting);
+                    state.add_line(Target::Status, line);
+                }
+                if *num == RPL_ENDOFMOTD {
+                    notify::start(state);
+                    history::start(state);
                 }
             }
 
@@ -447,10 +815,52 @@ impl IrcMessage {
                     .or_insert_with(|| NamesState { names: Vec::new() });
                 names.extend(
                     names_list
-                        .into_iter()
-                        .filter_map(|p| p.as_str().map(str::to_string)),
+                        .iter()
+                        .filter_map(Param::as_str)
+                        .map(str::to_string),
                 );
             }
+            IrcCommand::Numeric {
+                num: RPL_LISTSTART, ..
+            } => {}
+            IrcCommand::Numeric {
+                num: RPL_LIST,
+                args,
+            } => {
+                // replies after the LIST window was closed are ignored
+                let ConnectedState {
+                    list_window: Some(ListWindow::Channels(channel_list)),
+                    ..
+                } = expect_connected_state!(state, self)?
+                else {
+                    return Ok(());
+                };
+                let [_, channel, users, topic @ ..] = args.as_slice() else {
+                    state.warn_in(&Target::Status, String::from("RPL_LIST missing args"));
+                    return Ok(());
+                };
+                let users = users
+                    .as_str()
+                    .and_then(|users| users.parse().ok())
+                    .unwrap_or(0);
+                // the topic may have been split into a list because of commas
+                let topic = topic.first().map(Param::to_irc_string).unwrap_or_default();
+                // NOTE: the window isn't rendered for every reply, since there can be tens of
+                // thousands of them. the client re-renders it regularly while it is loading.
+                channel_list.push(ListedChannel::new(channel.to_irc_string(), users, topic));
+            }
+            IrcCommand::Numeric {
+                num: RPL_LISTEND, ..
+            } => {
+                if let ConnectedState {
+                    list_window: Some(ListWindow::Channels(channel_list)),
+                    ..
+                } = expect_connected_state!(state, self)?
+                {
+                    channel_list.complete = true;
+                    state.render()?;
+                }
+            }
             IrcCommand::Numeric {
                 num: RPL_ENDOFNAMES,
                 args,
@@ -458,8 +868,11 @@ impl IrcMessage {
                 let ConnectedState {
                     messages_state,
                     channels,
+                    isupport,
+                    nick: our_nick,
                     ..
                 } = expect_connected_state!(state, self)?;
+                let rules = ModeRules::new(isupport);
                 let [_, channel, ..] = args.as_slice() else {
                     state.warn_in(&Target::Status, String::from("RPL_ENDOFNAMES missing args"));
                     return Ok(());
@@ -472,7 +885,7 @@ impl IrcMessage {
                     return Ok(());
                 };
 
-                let Some(channel_name) = ChannelName::new(name) else {
+                let Some(channel_name) = ChannelName::new(name, casemapping) else {
                     state.warn_in(
                         &Target::Status,
                         format!("RPL_ENDOFNAMES invalid channel {:?}", name),
@@ -499,18 +912,368 @@ impl IrcMessage {
                     return Ok(());
                 };
 
-                channel.users.extend(names.iter().cloned());
+                for names in names.iter() {
+                    channel.add_names(names, &rules);
+                }
+
+                // only the nick is coloured, not its prefixes or the host from userhost-in-names
+                let mut names_line = Line::default().push_unstyled(" -");
+                for name in names.iter() {
+                    let prefix_len = name
+                        .find(|c| !rules.prefix_chars().any(|prefix| prefix == c))
+                        .unwrap_or(name.len());
+                    let (prefixes, rest) = name.split_at(prefix_len);
+                    let nick_len = rest.find('!').unwrap_or(rest.len());
+                    let (nick, userhost) = rest.split_at(nick_len);
+                    names_line = names_line
+                        .push_unstyled(format!(" {}", prefixes))
+                        .join(util::nick_line(
+                            &state.config.nick_colors,
+                            nick,
+                            casemapping.same(nick, our_nick),
+                        ))
+                        .push_unstyled(userhost);
+                }
 
                 state.add_line(
                     Target::Channel(channel_name.clone()),
                     Line::default()
-                        .push("NAMES".green())
+                        .push_themed(Role::Info, "NAMES")
                         .push_unstyled(" for ")
-                        .push(name.blue()),
+                        .push_themed(Role::Channel, name),
                 );
+                state.add_line(Target::Channel(channel_name), names_line);
+            }
+
+            // =======================
+            // topic
+            // =======================
+            IrcCommand::Numeric {
+                num: num @ (RPL_NOTOPIC | RPL_TOPIC),
+                args,
+            } => {
+                let [_, channel, topic, ..] = args.as_slice() else {
+                    state.warn_in(&Target::Status, format!("{} missing params", num));
+                    return Ok(());
+                };
+                let (Some(channel_name), Some(topic)) = (
+                    channel
+                        .as_str()
+                        .and_then(|name| ChannelName::new(name, casemapping)),
+                    topic.as_str(),
+                ) else {
+                    state.warn_in(&Target::Status, format!("{} malformed params", num));
+                    return Ok(());
+                };
+                // RPL_NOTOPIC has human readable text in place of the topic
+                let topic = if *num == RPL_TOPIC { topic } else { "" };
+
+                if let Some(channel) = channel_mut(state, &channel_name) {
+                    channel.set_topic(topic.to_string());
+                }
+
+                let line = if topic.is_empty() {
+                    util::line_at(time).push_themed(Role::Info, "no topic is set")
+                } else {
+                    util::line_at(time)
+                        .push_themed(Role::Info, "topic: ")
+                        .join(format::parse(topic, state.config.formatting))
+                };
+                state.add_line(Target::Channel(channel_name), line);
+            }
+            IrcCommand::Numeric {
+                num: RPL_TOPICWHOTIME,
+                args,
+            } => {
+                let [_, channel, setter, set_at, ..] = args.as_slice() else {
+                    state.warn_in(
+                        &Target::Status,
+                        String::from("RPL_TOPICWHOTIME missing params"),
+                    );
+                    return Ok(());
+                };
+                let (Some(channel_name), Some(setter)) = (
+                    channel
+                        .as_str()
+                        .and_then(|name| ChannelName::new(name, casemapping)),
+                    setter.as_str(),
+                ) else {
+                    state.warn_in(
+                        &Target::Status,
+                        String::from("RPL_TOPICWHOTIME malformed params"),
+                    );
+                    return Ok(());
+                };
+                let set_at = set_at
+                    .as_str()
+                    .and_then(|set_at| set_at.parse().ok())
+                    .and_then(|set_at| DateTime::from_timestamp(set_at, 0));
+
+                if let Some(channel) = channel_mut(state, &channel_name) {
+                    channel.set_topic_who_time(setter.to_string(), set_at);
+                }
+
+                let mut line = util::line_at(time)
+                    .push_themed(Role::Info, "topic set by ")
+                    .join(util::nick_line(&state.config.nick_colors, setter, false));
+                if let Some(set_at) = set_at {
+                    line = line.push_unstyled(format!(
+                        " at {}",
+                        set_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+                    ));
+                }
+                state.add_line(Target::Channel(channel_name), line);
+            }
+
+            // =======================
+            // whois
+            // =======================
+            IrcCommand::Numeric {
+                num:
+                    num @ (RPL_WHOISUSER | RPL_WHOWASUSER | RPL_WHOISSERVER | RPL_WHOISOPERATOR
+                    | RPL_WHOISIDLE | RPL_WHOISCHANNELS | RPL_WHOISACCOUNT | RPL_WHOISSECURE
+                    | RPL_WHOISCERTFP | RPL_WHOISREGNICK | RPL_WHOISSPECIAL | RPL_WHOISACTUALLY
+                    | RPL_WHOISHOST | RPL_WHOISMODES | RPL_AWAY),
+                args,
+            } => {
+                // RPL_AWAY outside of a WHOIS is a reply to a message that we sent
+                if !whois::add_reply(state, *num, args) {
+                    away::reply(state, args);
+                }
+            }
+            IrcCommand::Numeric {
+                num: RPL_INVITING,
+                args,
+            } => {
+                invite::inviting(state, args);
+            }
+
+            // =======================
+            // notify list
+            // =======================
+            IrcCommand::Numeric {
+                num: num @ (RPL_MONONLINE | RPL_MONOFFLINE),
+                args,
+            } => {
+                notify::monitor_reply(state, *num == RPL_MONONLINE, args);
+            }
+            IrcCommand::Numeric {
+                num: RPL_MONLIST,
+                args,
+            } => {
+                notify::monitor_list(state, args);
+            }
+            IrcCommand::Numeric {
+                num: RPL_ENDOFMONLIST,
+                ..
+            } => {
+                notify::end_of_monitor_list(state);
+            }
+            IrcCommand::Numeric {
+                num: ERR_MONLISTFULL,
+                args,
+            } => {
+                let text = args.last().and_then(Param::as_str).unwrap_or_default();
+                state.warn_in(&Target::Status, format!("notify list is full: {}", text));
+            }
+            IrcCommand::Numeric {
+                num: RPL_ISON,
+                args,
+            } => {
+                notify::ison_reply(state, args);
+            }
+            IrcCommand::Numeric {
+                num: num @ (RPL_UNAWAY | RPL_NOWAWAY),
+                args,
+            } => {
+                let ConnectedState { away, .. } = expect_connected_state!(state, self)?;
+                *away = *num == RPL_NOWAWAY;
+                let text = args.get(1).and_then(Param::as_str).unwrap_or_default();
+                state.add_line(Target::Status, util::line_at(time).push_unstyled(text));
+            }
+            IrcCommand::Numeric {
+                num: RPL_WHOREPLY,
+                args,
+            } => {
+                who::handle_reply(state, args);
+            }
+            IrcCommand::Numeric {
+                num: RPL_WHOSPCRPL,
+                args,
+            } => {
+                who::handle_whox_reply(state, args);
+            }
+            IrcCommand::Numeric {
+                num: RPL_ENDOFWHO,
+                args,
+            } => {
+                who::handle_end(state, args);
+            }
+            IrcCommand::Numeric {
+                num: RPL_ENDOFWHOIS | RPL_ENDOFWHOWAS,
+                args,
+            } => {
+                whois::finish(state, args);
+            }
+            IrcCommand::Numeric {
+                num: num @ (ERR_NOSUCHNICK | ERR_WASNOSUCHNICK),
+                args,
+            } => {
+                let [_, nick, rest @ ..] = args.as_slice() else {
+                    state.warn_in(&Target::Status, format!("{} missing params", num));
+                    return Ok(());
+                };
+                let nick = nick.to_irc_string();
+                whois::cancel(state, nick.as_str());
+
+                let text = if *num == ERR_NOSUCHNICK {
+                    "no such nick or channel"
+                } else {
+                    "there was no such nick"
+                };
+                let text = rest.last().and_then(Param::as_str).unwrap_or(text);
+                let line = util::line_at(time)
+                    .join(util::nick_line(&state.config.nick_colors, &nick, false))
+                    .push_themed(Role::Error, format!(": {}", text));
+                state.add_line(state.current_target().clone(), line);
+            }
+
+            // =======================
+            // list modes
+            // =======================
+            IrcCommand::Numeric {
+                num: num @ (RPL_BANLIST | RPL_EXCEPTLIST | RPL_INVEXLIST),
+                args,
+            } => {
+                let ConnectedState { messages_state, .. } = expect_connected_state!(state, self)?;
+                let mode = match *num {
+                    RPL_BANLIST => 'b',
+                    RPL_EXCEPTLIST => 'e',
+                    _ => 'I',
+                };
+                let [_, channel, mask, rest @ ..] = args.as_slice() else {
+                    state.warn_in(&Target::Status, format!("{} missing params", num));
+                    return Ok(());
+                };
+                let (Some(channel_name), Some(mask)) = (
+                    channel
+                        .as_str()
+                        .and_then(|name| ChannelName::new(name, casemapping)),
+                    mask.as_str(),
+                ) else {
+                    state.warn_in(&Target::Status, format!("{} malformed params", num));
+                    return Ok(());
+                };
+                // the setter and time are optional
+                let setter = rest.first().and_then(Param::as_str).map(str::to_string);
+                let time = rest
+                    .get(1)
+                    .and_then(Param::as_str)
+                    .and_then(|time| time.parse().ok())
+                    .and_then(|time| DateTime::from_timestamp(time, 0));
+
+                messages_state
+                    .active_lists
+                    .entry((channel_name, mode))
+                    .or_default()
+                    .push(ListEntry {
+                        mask: mask.to_string(),
+                        setter,
+                        time,
+                    });
+            }
+            IrcCommand::Numeric {
+                num: num @ (RPL_ENDOFBANLIST | RPL_ENDOFEXCEPTLIST | RPL_ENDOFINVEXLIST),
+                args,
+            } => {
+                let ConnectedState { messages_state, .. } = expect_connected_state!(state, self)?;
+                let mode = match *num {
+                    RPL_ENDOFBANLIST => 'b',
+                    RPL_ENDOFEXCEPTLIST => 'e',
+                    _ => 'I',
+                };
+                let Some(channel_name) = args
+                    .get(1)
+                    .and_then(Param::as_str)
+                    .and_then(|name| ChannelName::new(name, casemapping))
+                else {
+                    state.warn_in(&Target::Status, format!("{} missing channel", num));
+                    return Ok(());
+                };
+                // an empty list has no entries before the end
+                let entries = messages_state
+                    .active_lists
+                    .remove(&(channel_name.clone(), mode))
+                    .unwrap_or_default();
+
+                if entries.is_empty() {
+                    let text = format!(
+                        "the {} for {} is empty",
+                        mode::list_name(mode),
+                        channel_name.as_str()
+                    );
+                    state.add_line(
+                        Target::Channel(channel_name.clone()),
+                        util::line_at(time).push_themed(Role::Info, text),
+                    );
+                } else {
+                    // lists can be long, so they're shown in the LIST window where they can be
+                    // scrolled and searched
+                    state.ensure_target_exists(Target::List);
+                    state.select_target(&Target::List);
+                    if let ConnectionState::Connected(ConnectedState { list_window, .. }) =
+                        &mut state.conn_state
+                    {
+                        *list_window = Some(ListWindow::Modes(ModeList::new(
+                            channel_name.clone(),
+                            mode,
+                            entries.clone(),
+                        )));
+                    }
+                    state.render()?;
+                }
+
+                if let Some(channel) = channel_mut(state, &channel_name) {
+                    channel.modes.lists.insert(mode, entries);
+                }
+            }
+
+            // =======================
+            // join errors
+            // =======================
+            IrcCommand::Numeric {
+                num:
+                    num @ (ERR_BADCHANNELKEY | ERR_INVITEONLYCHAN | ERR_CHANNELISFULL
+                    | ERR_BANNEDFROMCHAN | ERR_TOOMANYCHANNELS),
+                args,
+            } => {
+                let ConnectedState { channel_keys, .. } = expect_connected_state!(state, self)?;
+                let [_, channel, rest @ ..] = args.as_slice() else {
+                    state.warn_in(&Target::Status, format!("{} missing channel", num));
+                    return Ok(());
+                };
+                let Some(channel_name) = channel
+                    .as_str()
+                    .and_then(|name| ChannelName::new(name, casemapping))
+                else {
+                    state.warn_in(&Target::Status, format!("{} had invalid channel", num));
+                    return Ok(());
+                };
+                // the key that was remembered is wrong, so don't keep using it
+                if *num == ERR_BADCHANNELKEY {
+                    channel_keys.remove(&channel_name);
+                }
+
+                let text = rest
+                    .last()
+                    .and_then(Param::as_str)
+                    .unwrap_or("cannot join channel");
                 state.add_line(
-                    Target::Channel(channel_name),
-                    Line::from(format!(" - {}", names.join(" "))),
+                    Target::Channel(channel_name.clone()),
+                    util::line_at(time)
+                        .push_themed(Role::Error, "cannot join ")
+                        .push_themed(Role::Channel, channel_name.as_str())
+                        .push_themed(Role::Error, format!(": {}", text)),
                 );
             }
 
@@ -518,9 +1281,73 @@ impl IrcMessage {
             // modes
             // =======================
             IrcCommand::Numeric {
-                num: RPL_UMODEIS, ..
+                num: RPL_UMODEIS,
+                args,
             } => {
-                self.unhandled(state);
+                let ConnectedState { user_modes, .. } = expect_connected_state!(state, self)?;
+                let Some(modes) = args.get(1).and_then(Param::as_str) else {
+                    state.warn_in(&Target::Status, String::from("RPL_UMODEIS missing modes"));
+                    return Ok(());
+                };
+                *user_modes = modes.trim_start_matches('+').to_string();
+
+                let line = util::line_at(time)
+                    .push_themed(Role::Info, "your modes are ")
+                    .push_unstyled(format!("+{}", user_modes));
+                state.add_line(Target::Status, line);
+            }
+            IrcCommand::Numeric {
+                num: RPL_CHANNELMODEIS,
+                args,
+            } => {
+                let ConnectedState { isupport, .. } = expect_connected_state!(state, self)?;
+                let rules = ModeRules::new(isupport);
+                let [_, channel, modestring, params @ ..] = args.as_slice() else {
+                    state.warn_in(
+                        &Target::Status,
+                        String::from("RPL_CHANNELMODEIS missing params"),
+                    );
+                    return Ok(());
+                };
+                let (Some(channel_name), Some(modestring)) = (
+                    channel
+                        .as_str()
+                        .and_then(|name| ChannelName::new(name, casemapping)),
+                    modestring.as_str(),
+                ) else {
+                    state.warn_in(
+                        &Target::Status,
+                        String::from("RPL_CHANNELMODEIS malformed params"),
+                    );
+                    return Ok(());
+                };
+                let params = params.iter().map(Param::to_irc_string).collect::<Vec<_>>();
+                let params = params.iter().map(String::as_str).collect::<Vec<_>>();
+                let changes = mode::parse_changes(&rules, modestring, &params);
+
+                let Some(channel) = channel_mut(state, &channel_name) else {
+                    state.warn_in(
+                        &Target::Status,
+                        format!(
+                            "RPL_CHANNELMODEIS for unknown channel {}",
+                            channel_name.as_str()
+                        ),
+                    );
+                    return Ok(());
+                };
+                // this is the full set of modes, so any that were known before may be outdated
+                channel.modes.settings.clear();
+                for change in changes.iter() {
+                    channel.apply_mode(&rules, change, None);
+                }
+                let modes = channel.modes.settings_string();
+
+                state.add_line(
+                    Target::Channel(channel_name),
+                    util::line_at(time)
+                        .push_themed(Role::Info, "modes are ")
+                        .push_unstyled(modes),
+                );
             }
 
             // =========================================
@@ -532,16 +1359,37 @@ impl IrcMessage {
             // =========================================
             // =========================================
             // =========================================
+            // errors that are still shown, but may be the reply to something that is waiting
+            IrcCommand::Numeric {
+                num: ERR_UNKNOWNCOMMAND | ERR_NEEDMOREPARAMS | ERR_NOSUCHCHANNEL | ERR_NOSUCHSERVER,
+                args,
+            } => {
+                // the second param is the command or target that the error is for
+                if let Some(param) = args.get(1).and_then(Param::as_str) {
+                    who::handle_error(state, param);
+                    history::handle_error(state, param);
+                }
+                self.unhandled(state);
+            }
             IrcCommand::Numeric { .. } => {
                 self.unhandled(state);
             }
 
+            IrcCommand::Unknown(name, params)
+                if name == "FAIL"
+                    && params.first().and_then(Param::as_str) == Some("CHATHISTORY") =>
+            {
+                history::failed(state, params);
+            }
+            // labelled commands that had no other reply
+            IrcCommand::Unknown(name, _) if name == "ACK" => {}
             IrcCommand::Unknown { .. } => {
                 state.warn_in(&Target::Status, format!("unhandled unknown msg {:?}", self));
             }
 
             // fatal error, the connection will be terminated
             IrcCommand::Error(err) => {
+                echo::disconnected(state);
                 state.ui.error(err)?;
             }
 
@@ -566,9 +1414,6 @@ impl IrcMessage {
             IrcCommand::Oper => {
                 state.warn(String::from("client received OPER"));
             }
-            IrcCommand::Topic { .. } => {
-                state.warn(String::from("client received TOPIC"));
-            }
             IrcCommand::Names { .. } => {
                 state.warn(String::from("client received NAMES"));
             }
@@ -621,8 +1466,11 @@ impl IrcMessage {
             IrcCommand::SQuit { .. } => {
                 state.warn(String::from("client received SQUIT"));
             }
-            IrcCommand::Away { .. } => {
-                state.warn(String::from("client received AWAY"));
+            IrcCommand::Away { message } => {
+                let Some(nick) = self.source.as_ref().map(Source::get_name) else {
+                    bail!("AWAY msg had no source");
+                };
+                away::notify(state, nick, message.as_deref());
             }
             IrcCommand::Links => {
                 state.warn(String::from("client received LINKS"));
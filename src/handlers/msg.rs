@@ -1,12 +1,15 @@
-use crossterm::style::Stylize;
 use log::*;
 
 use crate::{
-    handlers::ctcp::{self, CtcpCommand, CtcpReply, CTCP_DELIM},
-    irc::Source,
-    state::ClientState,
+    channel::Nickname,
+    handlers::{
+        ctcp::{self, CtcpCommand, CtcpReply, CTCP_DELIM},
+        echo,
+    },
+    irc::{IrcMessage, Source},
+    state::{ClientState, ConnectedState, ConnectionState},
     targets::Target,
-    ui::text::Line,
+    ui::{format, template::Kind, text::Line, theme::Role},
     util,
 };
 
@@ -16,21 +19,69 @@ pub enum MessageKind {
     Privmsg,
 }
 
+/// whether a nick is ours
+fn is_us(state: &ClientState, name: &str) -> bool {
+    matches!(&state.conn_state, ConnectionState::Connected(ConnectedState { nick, .. }) if state.casemapping().same(nick, name))
+}
+
+/// whether a message mentions our nick as a whole word
+fn mentions_us(state: &ClientState, msg: &str) -> bool {
+    let ConnectionState::Connected(ConnectedState { nick, .. }) = &state.conn_state else {
+        return false;
+    };
+    let casemapping = state.casemapping();
+    format::strip(msg)
+        .split(|c: char| !(c.is_alphanumeric() || "-[]\\`^{}|_".contains(c)))
+        .any(|word| casemapping.same(word, nick))
+}
+
+/// sets the msgid of a line to the one that the server gave the message, if it gave one
+fn with_msgid(line: Line<'static>, message: &IrcMessage) -> Line<'static> {
+    match message.tags.get("msgid") {
+        Some(msgid) => line.with_msgid(msgid),
+        None => line,
+    }
+}
+
+/// the sender's nick, which is highlighted if the message mentions us
+fn sender_line(state: &ClientState, name: &str, highlight: bool) -> Line<'static> {
+    if highlight {
+        Line::default().push_themed(Role::Highlight, name)
+    } else {
+        util::nick_line(&state.config.nick_colors, name, is_us(state, name))
+    }
+}
+
 pub(super) fn handle_message(
     state: &mut ClientState,
     kind: MessageKind,
-    source: &Option<Source>,
+    message: &IrcMessage,
     target: &Target,
     msg: &str,
+    backlog: bool,
 ) {
+    let time = message.time();
+    let source = &message.source;
+    // the echo of our own message replaces the line that was shown while it was being sent
+    if let Some(label) = message.tags.get("label") {
+        echo::echoed(state, label);
+    }
+    let from_us = source
+        .as_ref()
+        .is_some_and(|source| is_us(state, source.get_name()));
     let mut target = target.clone();
     // adjust nickname targets to be the *sender* of the message instead of the
-    // receiver (which is always the current user)
+    // receiver (which is always the current user). messages that we sent, like those in
+    // history, stay in the window of the user that they were sent to.
     if matches!(target, Target::Nickname(_)) {
         let Some(Source::Nick(nick, _, _)) = source else {
             return;
         };
-        target = Target::Nickname(nick.clone());
+        if !is_us(state, nick)
+            && let Some(nick) = Nickname::new(nick.as_str(), state.casemapping())
+        {
+            target = Target::Nickname(nick);
+        }
     }
 
     // handle CTCP messages specially
@@ -41,32 +92,57 @@ pub(super) fn handle_message(
         debug!("{:#?}", ctcp);
         match ctcp {
             CtcpCommand::Action(action) => {
-                let mut line = util::line_now().push("* ".magenta());
-                if let Some(source) = source.as_ref() {
-                    line = line.join(util::nick_line(source.get_name(), false));
-                }
-                let line = line.push_unstyled(" ").push_unstyled(action);
-                state.add_line(target.clone(), line);
+                let highlight = source
+                    .as_ref()
+                    .is_some_and(|source| !is_us(state, source.get_name()))
+                    && mentions_us(state, &action);
+                let nick = source
+                    .as_ref()
+                    .map(|source| sender_line(state, source.get_name(), highlight))
+                    .unwrap_or_default();
+                let line = util::message_line(
+                    Kind::Action,
+                    time,
+                    nick,
+                    format::parse(&action, state.config.formatting),
+                );
+                state.add_line(target.clone(), with_msgid(line, message));
             }
+            // queries in backlog were already answered, or not worth answering now, and our own
+            // queries are echoed back to us
+            CtcpCommand::Clientinfo if backlog || from_us => {}
             CtcpCommand::Clientinfo => {
-                let Some(Source::Nick(nick, _, _)) = source else {
+                let Some(nick) = source.as_ref().and_then(|source| match source {
+                    Source::Nick(nick, _, _) => Nickname::new(nick.as_str(), state.casemapping()),
+                    Source::Server(_) => None,
+                }) else {
                     warn!("CTCP CLIENTINFO without nick source");
                     return;
                 };
-                state.send_msg(CtcpReply::CLIENTINFO.to_msg(nick));
+                state.send_msg(CtcpReply::CLIENTINFO.to_msg(&nick));
             }
         }
     } else {
-        let mut line = util::line_now();
-        if let Some(source) = source.as_ref() {
-            line = line.join(util::message_nick_line(source.get_name(), false));
-        }
-        match kind {
-            MessageKind::Notice => line = line.join(Line::default().push("NOTICE ".green())),
-            MessageKind::Privmsg => {}
-        }
-        line = line.join(Line::from(msg.to_string()));
+        let highlight = kind == MessageKind::Privmsg
+            && source
+                .as_ref()
+                .is_some_and(|source| !is_us(state, source.get_name()))
+            && mentions_us(state, msg);
+        let nick = source
+            .as_ref()
+            .map(|source| sender_line(state, source.get_name(), highlight))
+            .unwrap_or_default();
+        let template = match kind {
+            MessageKind::Notice => Kind::Notice,
+            MessageKind::Privmsg => Kind::Message,
+        };
+        let line = util::message_line(
+            template,
+            time,
+            nick,
+            format::parse(msg, state.config.formatting),
+        );
 
-        state.add_line(target.clone(), line);
+        state.add_line(target.clone(), with_msgid(line, message));
     }
 }
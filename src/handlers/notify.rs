@@ -0,0 +1,440 @@
+use core::time::Duration;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
+
+use crossterm::style::Stylize;
+use log::*;
+
+use crate::{
+    casemap::Casemapping,
+    channel::Nickname,
+    irc::{
+        client::{ClientIrcCommand, ClientMessage},
+        Param,
+    },
+    isupport::ISupport,
+    state::{ClientState, ConnectedState, ConnectionState},
+    targets::Target,
+    ui::theme::Role,
+    util,
+};
+
+/// how often to check the notify list with ISON when the server doesn't support MONITOR
+const ISON_INTERVAL: Duration = Duration::from_secs(60);
+/// the longest list of nicks to send in one MONITOR or ISON, leaving room for the rest of the 512
+/// byte message
+const MAX_TARGETS_LEN: usize = 400;
+
+/// the presence of the nicks in the notify list
+#[derive(Debug)]
+pub struct NotifyState {
+    /// whether the notify list has been sent to the server, which happens once registration has
+    /// finished and ISUPPORT has been received
+    started: bool,
+    /// whether the server supports MONITOR, otherwise the list is polled with ISON
+    monitor: bool,
+    /// whether each nick is online. nicks are missing until they are checked.
+    online: HashMap<Nickname, bool>,
+    /// the nicks in each ISON that hasn't been replied to, since the reply only has the nicks that
+    /// are online
+    pending_ison: VecDeque<Vec<String>>,
+    next_ison: Instant,
+    /// the nicks from RPL_MONLIST replies that haven't ended yet
+    monitor_list: Vec<String>,
+}
+
+impl Default for NotifyState {
+    fn default() -> Self {
+        Self {
+            started: false,
+            monitor: false,
+            online: HashMap::new(),
+            pending_ison: VecDeque::new(),
+            next_ison: Instant::now(),
+            monitor_list: Vec::new(),
+        }
+    }
+}
+
+impl NotifyState {
+    /// whether a nick is online, if it's in the notify list and has been checked
+    pub fn presence(&self, nick: &Nickname) -> Option<bool> {
+        self.online.get(nick).copied()
+    }
+
+    /// compares the nicks with a different casemapping
+    pub fn rekey(&mut self, casemapping: Casemapping) {
+        self.online = self
+            .online
+            .drain()
+            .map(|(nick, online)| (nick.rekey(casemapping), online))
+            .collect();
+    }
+}
+
+/// the most nicks that the server allows to be monitored, if it has a limit
+fn monitor_limit(isupport: &ISupport) -> Option<usize> {
+    isupport
+        .value("MONITOR")
+        .and_then(|limit| limit.parse().ok())
+}
+
+/// splits nicks into groups that fit in one message
+fn batches(nicks: &[String]) -> Vec<Vec<String>> {
+    let mut batches: Vec<Vec<String>> = Vec::new();
+    let mut len = 0;
+    for nick in nicks {
+        match batches.last_mut() {
+            Some(batch) if len + nick.len() < MAX_TARGETS_LEN => {
+                batch.push(nick.clone());
+                len += nick.len() + 1;
+            }
+            _ => {
+                batches.push(vec![nick.clone()]);
+                len = nick.len();
+            }
+        }
+    }
+    batches
+}
+
+fn send_monitor(state: &ClientState, subcommand: char, nicks: &[String]) {
+    // C, L and S don't have any targets, but still need to be sent once
+    let batches = match nicks {
+        [] => vec![Vec::new()],
+        nicks => batches(nicks),
+    };
+    for targets in batches {
+        state.send_msg(ClientMessage::from_command(ClientIrcCommand::Monitor {
+            subcommand,
+            targets,
+        }));
+    }
+}
+
+/// sends the notify list to the server once registration has finished
+pub(super) fn start(state: &mut ClientState) {
+    let ConnectionState::Connected(ConnectedState {
+        notify, isupport, ..
+    }) = &mut state.conn_state
+    else {
+        return;
+    };
+    if notify.started {
+        return;
+    }
+    notify.started = true;
+    notify.monitor = isupport.has("MONITOR");
+    if !notify.monitor {
+        debug!("server does not support MONITOR, using ISON");
+        return;
+    }
+
+    let mut nicks = state.config.notify.clone();
+    // MONITOR has a maximum number of targets, if the server gives one
+    if let Some(limit) = monitor_limit(isupport)
+        && nicks.len() > limit
+    {
+        state.warn_in(
+            &Target::Status,
+            format!(
+                "the server only allows {} nicks in the notify list, ignoring the rest",
+                limit
+            ),
+        );
+        nicks.truncate(limit);
+    }
+
+    // clear anything that the server still has from before
+    send_monitor(state, 'C', &[]);
+    if !nicks.is_empty() {
+        send_monitor(state, '+', &nicks);
+    }
+}
+
+/// polls the notify list with ISON, if the server doesn't support MONITOR
+pub fn poll(state: &mut ClientState) {
+    let ConnectionState::Connected(ConnectedState { notify, .. }) = &mut state.conn_state else {
+        return;
+    };
+    if !notify.started || notify.monitor || Instant::now() < notify.next_ison {
+        return;
+    }
+    notify.next_ison = Instant::now() + ISON_INTERVAL;
+
+    let batches = batches(&state.config.notify);
+    notify.pending_ison.extend(batches.iter().cloned());
+    for batch in batches {
+        state.send_msg(ClientMessage::from_command(ClientIrcCommand::Ison(batch)));
+    }
+}
+
+/// adds nicks to the notify list
+pub fn add(state: &mut ClientState, nicks: &[Nickname]) -> eyre::Result<()> {
+    let casemapping = state.casemapping();
+    // nicks past the server's MONITOR limit wouldn't be monitored
+    let limit = match &state.conn_state {
+        ConnectionState::Connected(ConnectedState {
+            notify, isupport, ..
+        }) if notify.monitor => monitor_limit(isupport),
+        _ => None,
+    };
+
+    let mut added = Vec::new();
+    let mut over_limit = Vec::new();
+    for nick in nicks {
+        let exists = state
+            .config
+            .notify
+            .iter()
+            .any(|n| casemapping.same(n, nick.as_str()));
+        if exists {
+            continue;
+        }
+        if limit.is_some_and(|limit| state.config.notify.len() >= limit) {
+            over_limit.push(nick.as_str().to_string());
+            continue;
+        }
+        state.config.notify.push(nick.as_str().to_string());
+        added.push(nick.as_str().to_string());
+    }
+    state.config.save("notify")?;
+
+    if let Some(limit) = limit
+        && !over_limit.is_empty()
+    {
+        let target = state.current_target().clone();
+        state.warn_in(
+            &target,
+            format!(
+                "the server only allows {} nicks in the notify list, not adding {}",
+                limit,
+                over_limit.join(" ")
+            ),
+        );
+    }
+
+    let line =
+        util::line_now().push_unstyled(format!("added to the notify list: {}", added.join(" ")));
+    state.add_line(state.current_target().clone(), line);
+
+    if let ConnectionState::Connected(ConnectedState { notify, .. }) = &mut state.conn_state
+        && notify.started
+        && !added.is_empty()
+    {
+        if notify.monitor {
+            send_monitor(state, '+', &added);
+        } else {
+            notify.next_ison = Instant::now();
+        }
+    }
+    Ok(())
+}
+
+/// removes nicks from the notify list
+pub fn remove(state: &mut ClientState, nicks: &[Nickname]) -> eyre::Result<()> {
+    let casemapping = state.casemapping();
+    let mut removed = Vec::new();
+    state.config.notify.retain(|n| {
+        let matches = nicks.iter().any(|nick| casemapping.same(n, nick.as_str()));
+        if matches {
+            removed.push(n.clone());
+        }
+        !matches
+    });
+    state.config.save("notify")?;
+
+    let line = util::line_now().push_unstyled(format!(
+        "removed from the notify list: {}",
+        removed.join(" ")
+    ));
+    state.add_line(state.current_target().clone(), line);
+
+    if let ConnectionState::Connected(ConnectedState { notify, .. }) = &mut state.conn_state {
+        for nick in nicks {
+            notify.online.remove(nick);
+        }
+        if notify.started && notify.monitor && !removed.is_empty() {
+            send_monitor(state, '-', &removed);
+        }
+    }
+    Ok(())
+}
+
+/// shows the notify list with the presence of each nick, and asks the server what it is
+/// monitoring
+pub fn list(state: &mut ClientState) {
+    let casemapping = state.casemapping();
+    let target = state.current_target().clone();
+    let ConnectionState::Connected(ConnectedState { notify, .. }) = &state.conn_state else {
+        return;
+    };
+    let monitor = notify.monitor;
+    let lines = state
+        .config
+        .notify
+        .iter()
+        .map(|nick| {
+            let presence =
+                Nickname::new(nick.as_str(), casemapping).and_then(|nick| notify.presence(&nick));
+            let (role, presence) = match presence {
+                Some(true) => (Role::Info, "online"),
+                Some(false) => (Role::Dim, "offline"),
+                None => (Role::Dim, "unknown"),
+            };
+            util::line_now()
+                .push_unstyled("  ")
+                .join(util::nick_line(&state.config.nick_colors, nick, false))
+                .push_unstyled(" ")
+                .push_themed(role, presence)
+        })
+        .collect::<Vec<_>>();
+
+    let header = util::line_now().push_themed(
+        Role::Info,
+        format!("notify list ({} nicks)", state.config.notify.len()),
+    );
+    state.add_line(target.clone(), header);
+    for line in lines {
+        state.add_line(target.clone(), line);
+    }
+    if monitor {
+        send_monitor(state, 'L', &[]);
+    }
+}
+
+/// checks the presence of every nick in the notify list now
+pub fn refresh(state: &mut ClientState) {
+    let ConnectionState::Connected(ConnectedState { notify, .. }) = &mut state.conn_state else {
+        return;
+    };
+    if notify.monitor {
+        send_monitor(state, 'S', &[]);
+    } else {
+        notify.next_ison = Instant::now();
+    }
+}
+
+/// updates whether a nick is online, telling the user if it changed
+fn set_presence(state: &mut ClientState, nick: &str, online: bool, hostmask: Option<&str>) {
+    let casemapping = state.casemapping();
+    let ConnectionState::Connected(ConnectedState {
+        notify,
+        user_messages,
+        ..
+    }) = &mut state.conn_state
+    else {
+        return;
+    };
+    let Some(nickname) = Nickname::new(nick, casemapping) else {
+        return;
+    };
+    let previous = notify.online.insert(nickname.clone(), online);
+    // nicks that are offline when first checked aren't worth a notification
+    if previous == Some(online) || (previous.is_none() && !online) {
+        return;
+    }
+
+    let color = state.config.nick_colors.color(nick);
+    let make_line = || {
+        let line = util::line_now().push(nick.to_string().with(color));
+        match (online, hostmask) {
+            (true, Some(hostmask)) => line
+                .push_themed(Role::Info, " is online")
+                .push_themed(Role::Dim, format!(" ({})", hostmask)),
+            (true, None) => line.push_themed(Role::Info, " is online"),
+            (false, _) => line.push_themed(Role::Dim, " went offline"),
+        }
+    };
+    let query = user_messages
+        .contains_key(&nickname)
+        .then_some(Target::Nickname(nickname));
+    state.add_line(Target::Status, make_line());
+    if let Some(query) = query {
+        state.add_line(query, make_line());
+    }
+}
+
+/// handles RPL_MONONLINE and RPL_MONOFFLINE, which have a list of `nick!user@host` or nicks
+pub(super) fn monitor_reply(state: &mut ClientState, online: bool, args: &[Param]) {
+    let Some(targets) = args.get(1).map(Param::to_irc_string) else {
+        state.warn_in(
+            &Target::Status,
+            String::from("MONITOR reply missing targets"),
+        );
+        return;
+    };
+    for target in targets.split(',').filter(|target| !target.is_empty()) {
+        let (nick, hostmask) = match target.split_once('!') {
+            Some((nick, rest)) => (nick, Some(rest)),
+            None => (target, None),
+        };
+        set_presence(state, nick, online, hostmask);
+    }
+}
+
+/// handles RPL_MONLIST, which is part of the reply to `MONITOR L`
+pub(super) fn monitor_list(state: &mut ClientState, args: &[Param]) {
+    let ConnectionState::Connected(ConnectedState { notify, .. }) = &mut state.conn_state else {
+        return;
+    };
+    if let Some(targets) = args.get(1).map(Param::to_irc_string) {
+        notify.monitor_list.extend(
+            targets
+                .split(',')
+                .filter(|target| !target.is_empty())
+                .map(str::to_string),
+        );
+    }
+}
+
+/// handles RPL_ENDOFMONLIST, showing the nicks that the server is monitoring
+pub(super) fn end_of_monitor_list(state: &mut ClientState) {
+    let ConnectionState::Connected(ConnectedState { notify, .. }) = &mut state.conn_state else {
+        return;
+    };
+    let nicks = core::mem::take(&mut notify.monitor_list);
+    let line = util::line_now()
+        .push_themed(
+            Role::Info,
+            format!("the server is monitoring {} nicks: ", nicks.len()),
+        )
+        .push_unstyled(nicks.join(" "));
+    state.add_line(state.current_target().clone(), line);
+}
+
+/// handles RPL_ISON, where any nick in the matching ISON that isn't in the reply is offline
+pub(super) fn ison_reply(state: &mut ClientState, args: &[Param]) {
+    let casemapping = state.casemapping();
+    let ConnectionState::Connected(ConnectedState { notify, .. }) = &mut state.conn_state else {
+        return;
+    };
+    let online = args
+        .get(1)
+        .map(Param::to_irc_string)
+        .unwrap_or_default()
+        .split(' ')
+        .filter(|nick| !nick.is_empty())
+        .map(|nick| casemapping.lower(nick))
+        .collect::<Vec<_>>();
+    // a reply with nicks that weren't asked about is for an ISON that wasn't for the notify list
+    let Some(queried) = notify.pending_ison.front() else {
+        return;
+    };
+    let queried = queried
+        .iter()
+        .map(|nick| casemapping.lower(nick))
+        .collect::<Vec<_>>();
+    if !online.iter().all(|nick| queried.contains(nick)) {
+        return;
+    }
+    // UNWRAP: checked that there is an entry above
+    let queried = notify.pending_ison.pop_front().unwrap();
+
+    for nick in queried {
+        let is_online = online.contains(&casemapping.lower(&nick));
+        set_presence(state, &nick, is_online, None);
+    }
+}
@@ -0,0 +1,299 @@
+use core::time::Duration;
+use std::{collections::VecDeque, time::Instant};
+
+use log::*;
+
+use crate::{
+    channel::{ChannelName, Nickname},
+    irc::{
+        client::{ClientIrcCommand, ClientMessage},
+        Param,
+    },
+    isupport::ISupport,
+    state::{ClientState, ConnectedState, ConnectionState},
+    ui::{text::Line, theme::Role},
+    util,
+};
+
+/// the WHOX fields to request: token, channel, user, host, nick, flags, account and realname. the
+/// fields in the reply are always in this order.
+const WHOX_FIELDS: &str = "tcuhnfar";
+/// the token for WHOX queries sent automatically when joining a channel, whose replies aren't
+/// shown
+const AUTO_TOKEN: &str = "1";
+/// the token for WHOX queries sent by the user with `/who`
+const USER_TOKEN: &str = "2";
+
+/// the shortest time to wait between automatic WHO queries
+const MIN_WHO_DELAY: Duration = Duration::from_secs(1);
+/// how much longer to wait before the next automatic WHO for each reply to the last one, so that
+/// big channels don't flood the connection
+const WHO_DELAY_PER_REPLY: Duration = Duration::from_millis(20);
+/// how long to wait for an automatic WHO to end before sending the next one anyway
+const WHO_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// channels waiting to be sent an automatic WHO. only one is sent at a time, so joining many
+/// channels at once doesn't cause a flood of replies.
+#[derive(Debug)]
+pub struct WhoQueue {
+    channels: VecDeque<ChannelName>,
+    /// the WHO in progress, if there is one
+    active: Option<ActiveWho>,
+    /// the next WHO can't be sent before this time
+    next_at: Instant,
+}
+
+/// an automatic WHO that was sent and hasn't ended yet
+#[derive(Debug)]
+struct ActiveWho {
+    channel: ChannelName,
+    /// how many replies it's had
+    replies: usize,
+    /// when to stop waiting for it to end
+    deadline: Instant,
+}
+
+impl Default for WhoQueue {
+    fn default() -> Self {
+        Self {
+            channels: VecDeque::new(),
+            active: None,
+            next_at: Instant::now(),
+        }
+    }
+}
+
+impl WhoQueue {
+    /// queues an automatic WHO for a channel, if the server supports WHOX
+    pub fn push(&mut self, isupport: &ISupport, channel: ChannelName) {
+        if isupport.has("WHOX") && !self.channels.contains(&channel) {
+            self.channels.push_back(channel);
+        }
+    }
+
+    /// forgets the WHO for a channel that was left, whether it is queued or in progress
+    pub fn remove(&mut self, channel: &ChannelName) {
+        self.channels.retain(|queued| queued != channel);
+        if self
+            .active
+            .as_ref()
+            .is_some_and(|active| active.channel == *channel)
+        {
+            self.finish(0);
+        }
+    }
+
+    /// ends the WHO in progress, waiting longer before the next one if this one had more replies
+    fn finish(&mut self, replies: usize) {
+        let replies = u32::try_from(replies).unwrap_or(u32::MAX);
+        self.next_at = Instant::now() + MIN_WHO_DELAY.max(WHO_DELAY_PER_REPLY * replies);
+        self.active = None;
+    }
+}
+
+/// creates a WHO for a mask, using WHOX if the server supports it
+pub fn query(isupport: &ISupport, mask: String, auto: bool) -> ClientIrcCommand {
+    let token = if auto { AUTO_TOKEN } else { USER_TOKEN };
+    ClientIrcCommand::Who {
+        mask,
+        whox: isupport
+            .has("WHOX")
+            .then(|| format!("%{},{}", WHOX_FIELDS, token)),
+    }
+}
+
+/// sends the next automatic WHO, if one is queued and the last one has finished
+pub fn send_queued(state: &mut ClientState) {
+    let ConnectionState::Connected(ConnectedState {
+        messages_state,
+        isupport,
+        ..
+    }) = &mut state.conn_state
+    else {
+        return;
+    };
+    let queue = &mut messages_state.who_queue;
+    // a WHO that never ends would stop the rest of the queue
+    if let Some(active) = &queue.active
+        && Instant::now() >= active.deadline
+    {
+        warn!("automatic WHO for {} timed out", active.channel.as_str());
+        let replies = active.replies;
+        queue.finish(replies);
+    }
+    if queue.active.is_some() || Instant::now() < queue.next_at {
+        return;
+    }
+    let Some(channel) = queue.channels.pop_front() else {
+        return;
+    };
+
+    debug!("sending automatic WHO for {}", channel.as_str());
+    let msg = ClientMessage::from_command(query(isupport, channel.as_str().to_string(), true));
+    queue.active = Some(ActiveWho {
+        channel,
+        replies: 0,
+        deadline: Instant::now() + WHO_TIMEOUT,
+    });
+    state.send_msg(msg);
+}
+
+/// a reply to WHO, from either RPL_WHOREPLY or RPL_WHOSPCRPL
+#[derive(Debug)]
+struct WhoReply {
+    channel: String,
+    user: String,
+    host: String,
+    nick: String,
+    flags: String,
+    account: Option<String>,
+    realname: String,
+}
+
+/// handles RPL_WHOREPLY, which is always shown since only `/who` sends WHO without WHOX
+pub(super) fn handle_reply(state: &mut ClientState, args: &[Param]) {
+    let strs = args.iter().map(Param::to_irc_string).collect::<Vec<_>>();
+    let [_, channel, user, host, _server, nick, flags, rest, ..] = strs.as_slice() else {
+        state.warn_in(
+            &crate::targets::Target::Status,
+            String::from("RPL_WHOREPLY missing params"),
+        );
+        return;
+    };
+    // the last param is the hopcount followed by the realname
+    let realname = rest.split_once(' ').map_or("", |(_, realname)| realname);
+
+    let reply = WhoReply {
+        channel: channel.clone(),
+        user: user.clone(),
+        host: host.clone(),
+        nick: nick.clone(),
+        flags: flags.clone(),
+        account: None,
+        realname: realname.to_string(),
+    };
+    update_member(state, &reply);
+    show(state, &reply);
+}
+
+/// handles RPL_WHOSPCRPL, which is only shown if it was for `/who`
+pub(super) fn handle_whox_reply(state: &mut ClientState, args: &[Param]) {
+    let strs = args.iter().map(Param::to_irc_string).collect::<Vec<_>>();
+    let [_, token, channel, user, host, nick, flags, account, realname, ..] = strs.as_slice()
+    else {
+        state.warn_in(
+            &crate::targets::Target::Status,
+            String::from("RPL_WHOSPCRPL missing params"),
+        );
+        return;
+    };
+
+    let reply = WhoReply {
+        channel: channel.clone(),
+        user: user.clone(),
+        host: host.clone(),
+        nick: nick.clone(),
+        flags: flags.clone(),
+        // an account of `0` means that the user isn't logged in
+        account: (account != "0").then(|| account.clone()),
+        realname: realname.clone(),
+    };
+    update_member(state, &reply);
+
+    if token == AUTO_TOKEN {
+        if let ConnectionState::Connected(ConnectedState { messages_state, .. }) =
+            &mut state.conn_state
+            && let Some(active) = &mut messages_state.who_queue.active
+        {
+            active.replies += 1;
+        }
+    } else {
+        show(state, &reply);
+    }
+}
+
+/// handles RPL_ENDOFWHO, finishing an automatic WHO or showing the end of one from `/who`
+pub(super) fn handle_end(state: &mut ClientState, args: &[Param]) {
+    let casemapping = state.casemapping();
+    let ConnectionState::Connected(ConnectedState { messages_state, .. }) = &mut state.conn_state
+    else {
+        return;
+    };
+    let Some(mask) = args.get(1).map(Param::to_irc_string) else {
+        return;
+    };
+
+    let queue = &mut messages_state.who_queue;
+    if let Some(active) = &queue.active
+        && casemapping.same(active.channel.as_str(), &mask)
+    {
+        let replies = active.replies;
+        queue.finish(replies);
+        return;
+    }
+
+    let line = util::line_now().push_themed(Role::Info, format!("end of WHO for {}", mask));
+    state.add_line(state.current_target().clone(), line);
+}
+
+/// handles an error that may be the reply to an automatic WHO, which names either the command
+/// or the channel that it's for
+pub(super) fn handle_error(state: &mut ClientState, param: &str) {
+    let casemapping = state.casemapping();
+    let ConnectionState::Connected(ConnectedState { messages_state, .. }) = &mut state.conn_state
+    else {
+        return;
+    };
+    let queue = &mut messages_state.who_queue;
+    if let Some(active) = &queue.active
+        && (param.eq_ignore_ascii_case("WHO") || casemapping.same(active.channel.as_str(), param))
+    {
+        debug!("automatic WHO for {} failed", active.channel.as_str());
+        queue.finish(0);
+    }
+}
+
+fn update_member(state: &mut ClientState, reply: &WhoReply) {
+    let casemapping = state.casemapping();
+    let ConnectionState::Connected(ConnectedState { channels, .. }) = &mut state.conn_state else {
+        return;
+    };
+    let (Some(channel_name), Some(nick)) = (
+        ChannelName::new(reply.channel.as_str(), casemapping),
+        Nickname::new(reply.nick.as_str(), casemapping),
+    ) else {
+        return;
+    };
+    let Some(member) = channels
+        .get_mut(&channel_name)
+        .and_then(|channel| channel.users.get_mut(&nick))
+    else {
+        return;
+    };
+
+    member.user = Some(reply.user.clone());
+    member.host = Some(reply.host.clone());
+    member.realname = Some(reply.realname.clone());
+    // `G` means gone, `H` means here
+    member.away = reply.flags.starts_with('G');
+    if reply.account.is_some() {
+        member.account = reply.account.clone();
+    }
+}
+
+fn show(state: &mut ClientState, reply: &WhoReply) {
+    let mut line = Line::default()
+        .push_unstyled(format!("  {} ", reply.channel))
+        .join(util::nick_line(
+            &state.config.nick_colors,
+            &reply.nick,
+            false,
+        ))
+        .push_unstyled(format!("!{}@{} ", reply.user, reply.host))
+        .push_themed(Role::Dim, reply.flags.clone());
+    if let Some(account) = &reply.account {
+        line = line.push_unstyled(format!(" [{}]", account));
+    }
+    line = line.push_unstyled(format!(" {}", reply.realname));
+    state.add_line(state.current_target().clone(), line);
+}
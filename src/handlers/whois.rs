@@ -0,0 +1,219 @@
+use core::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::{
+    constants::numerics::*,
+    irc::Param,
+    state::{ClientState, ConnectedState, ConnectionState},
+    ui::{nick_colors::NickColors, text::Line, theme::Role},
+    util,
+};
+
+/// everything that a WHOIS or WHOWAS reply said about a user
+#[derive(Debug, Default)]
+pub struct WhoisInfo {
+    pub nick: String,
+    pub user: Option<String>,
+    pub host: Option<String>,
+    pub realname: Option<String>,
+    pub server: Option<String>,
+    pub server_info: Option<String>,
+    /// the text of RPL_WHOISOPERATOR, since servers describe their operators differently
+    pub operator: Option<String>,
+    pub idle: Option<Duration>,
+    pub signon: Option<DateTime<Utc>>,
+    pub channels: Vec<String>,
+    pub account: Option<String>,
+    pub away: Option<String>,
+    pub secure: bool,
+    pub certfp: Option<String>,
+    /// the text of any other replies, which are shown as they are
+    pub other: Vec<String>,
+}
+
+/// which command a reply is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhoisKind {
+    Whois,
+    Whowas,
+}
+
+/// adds a reply to the WHOIS or WHOWAS that it's part of. returns false if the reply is RPL_AWAY
+/// and there is no WHOIS for the nick, since it was sent for a message instead.
+pub(super) fn add_reply(state: &mut ClientState, num: u16, args: &[Param]) -> bool {
+    let casemapping = state.casemapping();
+    let ConnectionState::Connected(ConnectedState { messages_state, .. }) = &mut state.conn_state
+    else {
+        return false;
+    };
+    let [_, nick, rest @ ..] = args else {
+        return false;
+    };
+    let Some(nick) = nick.as_str() else {
+        return false;
+    };
+    let key = casemapping.lower(nick);
+
+    let kind = if num == RPL_WHOWASUSER {
+        WhoisKind::Whowas
+    } else {
+        WhoisKind::Whois
+    };
+    if num == RPL_AWAY && !messages_state.active_whois.contains_key(&key) {
+        return false;
+    }
+    let (_, replies) = messages_state
+        .active_whois
+        .entry(key)
+        .or_insert_with(|| (kind, Vec::new()));
+    // each user in a WHOWAS starts with RPL_WHOWASUSER
+    if replies.is_empty() || num == RPL_WHOWASUSER {
+        replies.push(WhoisInfo {
+            nick: nick.to_string(),
+            ..WhoisInfo::default()
+        });
+    }
+    // UNWRAP: there is always an entry after the push above
+    let info = replies.last_mut().unwrap();
+
+    let text = |idx: usize| rest.get(idx).and_then(Param::as_str).map(str::to_string);
+    match num {
+        RPL_WHOISUSER | RPL_WHOWASUSER => {
+            // the third param is unused and always `*`
+            info.user = text(0);
+            info.host = text(1);
+            info.realname = text(3);
+        }
+        RPL_WHOISSERVER => {
+            info.server = text(0);
+            info.server_info = text(1);
+        }
+        RPL_WHOISOPERATOR => info.operator = text(0),
+        RPL_WHOISIDLE => {
+            let number = |idx: usize| text(idx).and_then(|n| n.parse::<i64>().ok());
+            info.idle = number(0).map(|secs| Duration::from_secs(secs.unsigned_abs()));
+            info.signon = number(1).and_then(|time| DateTime::from_timestamp(time, 0));
+        }
+        RPL_WHOISCHANNELS => {
+            // the channels may be split into a list because of commas in channel names
+            info.channels.extend(
+                rest.first()
+                    .map(Param::to_irc_string)
+                    .unwrap_or_default()
+                    .split(' ')
+                    .filter(|channel| !channel.is_empty())
+                    .map(str::to_string),
+            );
+        }
+        RPL_WHOISACCOUNT => info.account = text(0),
+        RPL_AWAY => info.away = text(0),
+        RPL_WHOISSECURE => info.secure = true,
+        RPL_WHOISCERTFP => {
+            // the fingerprint is at the end of the human readable text
+            info.certfp = text(0).and_then(|text| text.split(' ').next_back().map(str::to_string));
+        }
+        _ => {
+            let other = rest
+                .iter()
+                .map(Param::to_irc_string)
+                .collect::<Vec<_>>()
+                .join(" ");
+            info.other.push(other);
+        }
+    }
+
+    true
+}
+
+/// finishes the WHOIS or WHOWAS for a nick, showing everything that was collected in the current
+/// window
+pub(super) fn finish(state: &mut ClientState, args: &[Param]) {
+    let casemapping = state.casemapping();
+    let ConnectionState::Connected(ConnectedState { messages_state, .. }) = &mut state.conn_state
+    else {
+        return;
+    };
+    let Some(nick) = args.get(1).and_then(Param::as_str) else {
+        return;
+    };
+    // if there were no replies, then an error was already shown
+    let Some((kind, replies)) = messages_state.active_whois.remove(&casemapping.lower(nick)) else {
+        return;
+    };
+
+    let target = state.current_target().clone();
+    for info in replies {
+        for line in card(&state.config.nick_colors, &info, kind) {
+            state.add_line(target.clone(), line);
+        }
+    }
+}
+
+/// forgets a WHOIS or WHOWAS that failed, so that nothing is shown at the end
+pub(super) fn cancel(state: &mut ClientState, nick: &str) {
+    let casemapping = state.casemapping();
+    if let ConnectionState::Connected(ConnectedState { messages_state, .. }) = &mut state.conn_state
+    {
+        messages_state.active_whois.remove(&casemapping.lower(nick));
+    }
+}
+
+fn card(colors: &NickColors, info: &WhoisInfo, kind: WhoisKind) -> Vec<Line<'static>> {
+    let title = match kind {
+        WhoisKind::Whois => "WHOIS ",
+        WhoisKind::Whowas => "WHOWAS ",
+    };
+    let mut lines = vec![util::line_now()
+        .push_themed(Role::Info, title)
+        .join(util::nick_line(colors, &info.nick, false))];
+    let field = |name: &str, value: String| {
+        Line::default()
+            .push_themed(Role::Dim, format!("  {:>9}: ", name))
+            .push_unstyled(value)
+    };
+
+    if let (Some(user), Some(host)) = (&info.user, &info.host) {
+        lines.push(field("host", format!("{}!{}@{}", info.nick, user, host)));
+    }
+    if let Some(realname) = &info.realname {
+        lines.push(field("real name", realname.clone()));
+    }
+    if let Some(account) = &info.account {
+        lines.push(field("account", account.clone()));
+    }
+    if !info.channels.is_empty() {
+        lines.push(field("channels", info.channels.join(" ")));
+    }
+    if let Some(server) = &info.server {
+        let value = match &info.server_info {
+            Some(server_info) => format!("{} ({})", server, server_info),
+            None => server.clone(),
+        };
+        lines.push(field("server", value));
+    }
+    if let Some(operator) = &info.operator {
+        lines.push(field("operator", operator.clone()));
+    }
+    if let Some(away) = &info.away {
+        lines.push(field("away", away.clone()));
+    }
+    if let Some(idle) = info.idle {
+        lines.push(field("idle", humantime::format_duration(idle).to_string()));
+    }
+    if let Some(signon) = info.signon {
+        let signon = signon.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S");
+        lines.push(field("signed on", signon.to_string()));
+    }
+    if info.secure {
+        lines.push(field("secure", String::from("using a secure connection")));
+    }
+    if let Some(certfp) = &info.certfp {
+        lines.push(field("certfp", certfp.clone()));
+    }
+    for other in info.other.iter() {
+        lines.push(field("info", other.clone()));
+    }
+
+    lines
+}
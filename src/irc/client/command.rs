@@ -5,9 +5,20 @@ use crate::{
     irc::{client::message::ClientMessageToStringErr, target::Target},
 };
 
+/// a capability negotiation command
+#[derive(Debug)]
+pub enum CapCommand {
+    /// list the capabilities that the server supports, using version 302 of negotiation
+    Ls,
+    /// enable capabilities
+    Req(Vec<String>),
+    /// finish negotiation, so that registration can continue
+    End,
+}
+
 #[derive(Debug)]
 pub enum ClientIrcCommand {
-    Cap, // FIXME: args
+    Cap(CapCommand),
     Authenticate,
     Pass(String),
     Nick(String),
@@ -20,7 +31,8 @@ pub enum ClientIrcCommand {
     Part(Vec<ChannelName>, Option<String>),
     Topic(ChannelName, Option<String>),
     Names(Vec<ChannelName>),
-    List, // FIXME: weird optional args
+    /// list channels, optionally only those matching the channels, masks and ELIST conditions
+    List(Vec<String>),
     Invite {
         nick: Nickname,
         channel: ChannelName,
@@ -72,6 +84,8 @@ pub enum ClientIrcCommand {
     },
     Who {
         mask: String,
+        /// the fields and token of a WHOX query, like `%tcuhnfar,1`
+        whox: Option<String>,
     },
     Whois {
         target: Option<String>,
@@ -95,6 +109,20 @@ pub enum ClientIrcCommand {
         message: Option<String>,
     },
     Links,
+    /// check which of the nicks are online
+    Ison(Vec<String>),
+    /// change or query the nicks that the server notifies us about, where the subcommand is one of
+    /// `+`, `-`, `C`, `L` or `S`
+    Monitor {
+        subcommand: char,
+        targets: Vec<String>,
+    },
+    /// asks for the history of a target, where the subcommand is like `LATEST` or `BEFORE` and
+    /// the params are the target, `*` or `msgid=` and `timestamp=` selectors, and the limit
+    Chathistory {
+        subcommand: &'static str,
+        params: Vec<String>,
+    },
 
     /// the client wants to send the following text directly to the server. this is typically used
     /// because there does not yet exist a nice interface for the IRC command in question.
@@ -107,7 +135,14 @@ impl ClientIrcCommand {
         #[allow(unused)]
         //errors are returned early
         let msg = match self {
-            ClientIrcCommand::Cap => todo!("CAP"),
+            ClientIrcCommand::Cap(CapCommand::Ls) => String::from("CAP LS 302"),
+            ClientIrcCommand::Cap(CapCommand::Req(caps)) => {
+                if caps.is_empty() {
+                    return Err(ClientMessageToStringErr::InvalidParams);
+                }
+                format!("CAP REQ :{}", caps.join(" "))
+            }
+            ClientIrcCommand::Cap(CapCommand::End) => String::from("CAP END"),
             ClientIrcCommand::Authenticate => String::from("AUTHENTICATE"),
             ClientIrcCommand::Pass(pass) => format!("PASS :{}", pass),
             ClientIrcCommand::Nick(nick) => format!("NICK :{}", nick),
@@ -126,14 +161,13 @@ impl ClientIrcCommand {
                 format!("QUIT{}", reason)
             }
             ClientIrcCommand::Join(channels) => {
-                if channels.len() == 0 {
-                    todo!()
+                if channels.is_empty() {
+                    return Err(ClientMessageToStringErr::InvalidParams);
                 }
 
                 // sort channels such that all channels that have a key are first.
                 // since keys are associated with channels based on their index, a gap in keys would
                 // cause keys to be incorrectly associated.
-                // FIXME: check if servers tend to accept an empty key?
                 let mut channels = channels.clone();
                 channels.sort_by(|(_, key1), (_, key2)| match (key1, key2) {
                     (None, None) => cmp::Ordering::Equal,
@@ -142,36 +176,78 @@ impl ClientIrcCommand {
                     (Some(_), Some(_)) => cmp::Ordering::Equal,
                 });
 
-                let mut channels_str = String::new();
-                let keys_str = String::new();
+                let channels_str = channels
+                    .iter()
+                    .map(|(channel, _)| channel.as_str())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let keys_str = channels
+                    .iter()
+                    .filter_map(|(_, key)| key.as_deref())
+                    .collect::<Vec<_>>()
+                    .join(",");
 
-                for idx in 0..channels.len() - 1 {
-                    let (channel, key) = &channels[idx];
-                    channels_str.push_str(channel.as_str());
-                    channels_str.push(',');
-                    if let Some(_key) = key {
-                        todo!("key formatting not yet supported");
-                    }
+                if keys_str.is_empty() {
+                    format!("JOIN {}", channels_str)
+                } else {
+                    format!("JOIN {} {}", channels_str, keys_str)
+                }
+            }
+            ClientIrcCommand::Part(channels, reason) => {
+                if channels.is_empty() {
+                    return Err(ClientMessageToStringErr::InvalidParams);
                 }
-                // append final list element without a comma
-                let (channel, key) = &channels[channels.len() - 1];
-                channels_str.push_str(channel.as_str());
-                if let Some(_key) = key {
-                    todo!("key formatting not yet supported");
+                let channels = channels
+                    .iter()
+                    .map(ChannelName::as_str)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                match reason {
+                    Some(reason) => format!("PART {} :{}", channels, reason),
+                    None => format!("PART {}", channels),
                 }
-
-                format!("JOIN {} {}", channels_str, keys_str)
             }
-            ClientIrcCommand::Part(_, _) => todo!(),
-            ClientIrcCommand::Topic(_, _) => todo!(),
-            ClientIrcCommand::Names(_) => todo!(),
-            ClientIrcCommand::List => todo!(),
-            ClientIrcCommand::Invite { nick, channel } => todo!(),
+            ClientIrcCommand::Topic(channel, topic) => match topic {
+                // NOTE: an empty topic clears the topic, so the `:` must always be sent
+                Some(topic) => format!("TOPIC {} :{}", channel.as_str(), topic),
+                None => format!("TOPIC {}", channel.as_str()),
+            },
+            ClientIrcCommand::Names(channels) => {
+                let channels = channels
+                    .iter()
+                    .map(ChannelName::as_str)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!("NAMES {}", channels)
+            }
+            ClientIrcCommand::List(filters) => {
+                if filters.is_empty() {
+                    String::from("LIST")
+                } else {
+                    format!("LIST {}", filters.join(","))
+                }
+            }
+            ClientIrcCommand::Invite { nick, channel } => {
+                format!("INVITE {} {}", nick.as_str(), channel.as_str())
+            }
             ClientIrcCommand::Kick {
                 channel,
                 users,
                 comment,
-            } => todo!(),
+            } => {
+                if users.is_empty() {
+                    return Err(ClientMessageToStringErr::InvalidParams);
+                }
+                let users = users
+                    .iter()
+                    .map(Nickname::as_str)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                match comment {
+                    Some(comment) => format!("KICK {} {} :{}", channel.as_str(), users, comment),
+                    None => format!("KICK {} {}", channel.as_str(), users),
+                }
+            }
             ClientIrcCommand::Motd { server } => todo!(),
             ClientIrcCommand::Version { server } => todo!(),
             ClientIrcCommand::Admin { server } => todo!(),
@@ -181,7 +257,11 @@ impl ClientIrcCommand {
             ClientIrcCommand::Stats { query, server } => todo!(),
             ClientIrcCommand::Help { subject } => todo!(),
             ClientIrcCommand::Info => todo!(),
-            ClientIrcCommand::Mode { target, mode } => todo!(),
+            ClientIrcCommand::Mode { target, mode } => match mode {
+                // the mode string includes its params, so it is sent as-is
+                Some(mode) => format!("MODE {} {}", target.as_str(), mode),
+                None => format!("MODE {}", target.as_str()),
+            },
             ClientIrcCommand::Privmsg { targets, msg } => {
                 let mut target_str = String::new();
                 match targets.as_slice() {
@@ -216,14 +296,45 @@ impl ClientIrcCommand {
 
                 format!("NOTICE {} :{}", target_str, msg)
             }
-            ClientIrcCommand::Who { mask } => todo!(),
-            ClientIrcCommand::Whois { target, nick } => todo!(),
-            ClientIrcCommand::WhoWas { nick, count } => todo!(),
+            ClientIrcCommand::Who { mask, whox } => match whox {
+                Some(whox) => format!("WHO {} {}", mask, whox),
+                None => format!("WHO {}", mask),
+            },
+            ClientIrcCommand::Whois { target, nick } => match target {
+                Some(target) => format!("WHOIS {} {}", target, nick.as_str()),
+                None => format!("WHOIS {}", nick.as_str()),
+            },
+            ClientIrcCommand::WhoWas { nick, count } => match count {
+                Some(count) => format!("WHOWAS {} {}", nick.as_str(), count),
+                None => format!("WHOWAS {}", nick.as_str()),
+            },
             ClientIrcCommand::Kill { nick, comment } => todo!(),
             ClientIrcCommand::Rehash => todo!(),
             ClientIrcCommand::Restart => todo!(),
             ClientIrcCommand::SQuit { server, comment } => todo!(),
-            ClientIrcCommand::Away { message } => todo!(),
+            ClientIrcCommand::Ison(nicks) => {
+                if nicks.is_empty() {
+                    return Err(ClientMessageToStringErr::InvalidParams);
+                }
+                format!("ISON {}", nicks.join(" "))
+            }
+            ClientIrcCommand::Monitor {
+                subcommand,
+                targets,
+            } => {
+                if targets.is_empty() {
+                    format!("MONITOR {}", subcommand)
+                } else {
+                    format!("MONITOR {} {}", subcommand, targets.join(","))
+                }
+            }
+            ClientIrcCommand::Chathistory { subcommand, params } => {
+                format!("CHATHISTORY {} {}", subcommand, params.join(" "))
+            }
+            ClientIrcCommand::Away { message } => match message {
+                Some(message) => format!("AWAY :{}", message),
+                None => String::from("AWAY"),
+            },
             ClientIrcCommand::Links => todo!(),
 
             ClientIrcCommand::Raw(text) => text.to_string(),
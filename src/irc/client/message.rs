@@ -17,9 +17,18 @@ impl ClientMessage {
         }
     }
 
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key, value);
+        self
+    }
+
     pub fn irc_str(&self) -> Result<String, ClientMessageToStringErr> {
         let mut s = String::new();
-        // FIXME: send tags
+        if !self.tags.is_empty() {
+            s.push('@');
+            s.push_str(&self.tags.irc_str());
+            s.push(' ');
+        }
         s.push_str(self.cmd.irc_str()?.as_str());
         s.push_str("\r\n");
         Ok(s)
@@ -1,5 +1,5 @@
 mod command;
 mod message;
 
-pub use command::ClientIrcCommand;
+pub use command::{CapCommand, ClientIrcCommand};
 pub use message::{ClientMessage, ClientMessageToStringErr};
@@ -2,10 +2,7 @@ use core::{cmp, fmt::Write as _};
 
 use thiserror::Error;
 
-use crate::{
-    irc::{param, Param},
-    targets::Target,
-};
+use crate::irc::{param, Param};
 
 // expects a parameter to be a string parameter, and extracts it, otherwise returns an invalid param
 // err.
@@ -23,7 +20,14 @@ macro_rules! expect_string_param {
 #[allow(unused)]
 #[derive(Debug, Clone)]
 pub enum IrcCommand {
-    Cap, // FIXME: missing args
+    /// a capability negotiation reply, like `CAP * LS * :away-notify`
+    Cap {
+        subcommand: String,
+        /// whether more replies for the same subcommand follow, marked with a `*`
+        more: bool,
+        /// the capabilities, which may have values like `sasl=PLAIN`
+        caps: Vec<String>,
+    },
     Authenticate,
     Pass(String),
     Nick(String),
@@ -80,17 +84,17 @@ pub enum IrcCommand {
     },
     Info,
     Mode {
-        target: Target,
+        target: String,
         mode: Option<String>,
     },
 
     // messages
     Privmsg {
-        targets: Vec<Target>,
+        targets: Vec<String>,
         msg: String,
     },
     Notice {
-        targets: Vec<Target>,
+        targets: Vec<String>,
         msg: String,
     },
 
@@ -124,6 +128,15 @@ pub enum IrcCommand {
         message: Option<String>,
     },
     Links,
+    /// `BATCH +ref type params...` starts a batch of messages, and `BATCH -ref` ends it
+    Batch {
+        reference: String,
+        /// whether the batch is starting rather than ending
+        start: bool,
+        /// the type of a batch that is starting, like `netsplit`
+        kind: Option<String>,
+        params: Vec<String>,
+    },
     // FIXME: ADD USERHOST, WALLOPS
     Numeric {
         num: u16,
@@ -150,7 +163,26 @@ impl IrcCommand {
 
         match command {
             "CAP" => {
-                todo!()
+                let (subcommand, more, caps) = match args.as_slice() {
+                    [_, subcommand, more, caps, ..] if more.as_str() == Some("*") => {
+                        (subcommand, true, caps)
+                    }
+                    [_, subcommand, caps, ..] => (subcommand, false, caps),
+                    _ => return Err(IrcCommandParseErr::MissingParams(s.to_string())),
+                };
+                let subcommand = expect_string_param!(subcommand).to_uppercase();
+                // capabilities with values like `sasl=PLAIN,EXTERNAL` are parsed as lists
+                let caps = caps
+                    .to_irc_string()
+                    .split(' ')
+                    .filter(|cap| !cap.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                Ok(IrcCommand::Cap {
+                    subcommand,
+                    more,
+                    caps,
+                })
             }
             "AUTHENTICATE" => Ok(IrcCommand::Authenticate),
             "PASS" => {
@@ -250,10 +282,28 @@ impl IrcCommand {
                 todo!()
             }
             "INVITE" => {
-                todo!()
+                let [nick, channel, ..] = args.as_slice() else {
+                    return Err(IrcCommandParseErr::MissingParams(s.to_string()));
+                };
+                let nick = expect_string_param!(nick);
+                let channel = expect_string_param!(channel);
+                Ok(IrcCommand::Invite { nick, channel })
             }
             "KICK" => {
-                todo!()
+                let [channel, user, rest @ ..] = args.as_slice() else {
+                    return Err(IrcCommandParseErr::MissingParams(s.to_string()));
+                };
+                let channel = expect_string_param!(channel);
+                let user = expect_string_param!(user);
+                let comment = match rest.first() {
+                    Some(param) => Some(expect_string_param!(param)),
+                    None => None,
+                };
+                Ok(IrcCommand::Kick {
+                    channel,
+                    user,
+                    comment,
+                })
             }
             "MOTD" => {
                 todo!()
@@ -286,9 +336,7 @@ impl IrcCommand {
                 let [target, rest @ ..] = args.as_slice() else {
                     return Err(IrcCommandParseErr::MissingParams(s.to_string()));
                 };
-                let Some(target) = Target::new(expect_string_param!(target)) else {
-                    return Err(IrcCommandParseErr::InvalidParams);
-                };
+                let target = expect_string_param!(target);
 
                 let mode = match rest {
                     [] => None,
@@ -309,11 +357,7 @@ impl IrcCommand {
                 let [targets, msg, ..] = args.as_slice() else {
                     return Err(IrcCommandParseErr::MissingParams(s.to_string()));
                 };
-                let targets = targets
-                    .optional_list()
-                    .into_iter()
-                    .filter_map(Target::new)
-                    .collect();
+                let targets = targets.optional_list();
                 let msg = expect_string_param!(msg);
                 Ok(IrcCommand::Privmsg { targets, msg })
             }
@@ -321,11 +365,7 @@ impl IrcCommand {
                 let [targets, msg, ..] = args.as_slice() else {
                     return Err(IrcCommandParseErr::MissingParams(s.to_string()));
                 };
-                let targets = targets
-                    .optional_list()
-                    .into_iter()
-                    .filter_map(Target::new)
-                    .collect();
+                let targets = targets.optional_list();
                 let msg = expect_string_param!(msg);
                 Ok(IrcCommand::Notice { targets, msg })
             }
@@ -351,11 +391,38 @@ impl IrcCommand {
                 todo!()
             }
             "AWAY" => {
-                todo!()
+                // no message means that the user is no longer away
+                let message = match args.first() {
+                    Some(param) => Some(expect_string_param!(param)),
+                    None => None,
+                };
+                Ok(IrcCommand::Away { message })
             }
             "LINKS" => {
                 todo!()
             }
+            "BATCH" => {
+                let Some((reference, rest)) = args.split_first() else {
+                    return Err(IrcCommandParseErr::MissingParams(s.to_string()));
+                };
+                let reference = expect_string_param!(reference);
+                let (start, reference) = match reference.split_at_checked(1) {
+                    Some(("+", reference)) if !reference.is_empty() => (true, reference),
+                    Some(("-", reference)) if !reference.is_empty() => (false, reference),
+                    _ => return Err(IrcCommandParseErr::InvalidParams),
+                };
+                let mut rest = rest.iter().map(Param::to_irc_string);
+                let kind = if start { rest.next() } else { None };
+                if start && kind.is_none() {
+                    return Err(IrcCommandParseErr::MissingParams(s.to_string()));
+                }
+                Ok(IrcCommand::Batch {
+                    reference: reference.to_string(),
+                    start,
+                    kind,
+                    params: rest.collect(),
+                })
+            }
             other => match other.parse::<u16>() {
                 // numerics may only be 3 digits
                 Ok(num) if num <= 999 => Ok(IrcCommand::Numeric { num, args }),
@@ -369,7 +436,9 @@ impl IrcCommand {
         #[allow(unused)]
         //errors are returned early
         let msg = match self {
-            IrcCommand::Cap => todo!("CAP"),
+            IrcCommand::Cap {
+                subcommand, caps, ..
+            } => format!("CAP {} :{}", subcommand, caps.join(" ")),
             IrcCommand::Authenticate => String::from("AUTHENTICATE"),
             IrcCommand::Pass(pass) => format!("PASS :{}", pass),
             IrcCommand::Nick(nick) => format!("NICK :{}", nick),
@@ -488,8 +557,16 @@ impl IrcCommand {
             IrcCommand::Rehash => todo!(),
             IrcCommand::Restart => todo!(),
             IrcCommand::SQuit { server, comment } => todo!(),
-            IrcCommand::Away { message } => todo!(),
+            IrcCommand::Away { message } => match message {
+                Some(message) => format!("AWAY :{}", message),
+                None => String::from("AWAY"),
+            },
             IrcCommand::Links => todo!(),
+            IrcCommand::Batch { .. } => {
+                return Err(IrcCommandToStringErr::ClientMayNotCreate(String::from(
+                    "BATCH",
+                )));
+            }
 
             IrcCommand::Numeric { num, .. } => {
                 return Err(IrcCommandToStringErr::ClientMayNotCreate(num.to_string()));
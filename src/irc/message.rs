@@ -1,5 +1,6 @@
 use core::fmt::Debug;
 
+use chrono::{DateTime, Utc};
 use log::*;
 use thiserror::Error;
 
@@ -27,6 +28,15 @@ impl IrcMessage {
         }
     }
 
+    /// when the message was sent, from the `server-time` tag, or now if the server didn't say
+    pub fn time(&self) -> DateTime<Utc> {
+        self.tags
+            .get("time")
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now)
+    }
+
     /// parses a message from a string. the string must contain only a single message. the string
     /// must not contain CRLF.
     pub fn parse(s: &str) -> Result<Self, IrcParseErr> {
@@ -1,32 +1,26 @@
 use core::fmt::Display;
 
-use crate::channel::Nickname;
-
 #[derive(Debug, Clone)]
 pub enum Source {
     Server(String),
-    Nick(Nickname, Option<String>, Option<String>),
+    /// a user, with their nick, user and host
+    Nick(String, Option<String>, Option<String>),
 }
 
 impl Source {
     pub(super) fn parse(s: &str) -> Source {
         match s.split_once('!') {
             Some((nick, rest)) => match rest.split_once('@') {
-                Some((user, host)) => {
-                    let nick = Nickname::new(nick).unwrap();
-                    Source::Nick(nick, Some(user.to_string()), Some(host.to_string()))
-                }
-                None => {
-                    let nick = Nickname::new(nick).unwrap();
-                    Source::Nick(nick, Some(rest.to_string()), None)
-                }
+                Some((user, host)) => Source::Nick(
+                    nick.to_string(),
+                    Some(user.to_string()),
+                    Some(host.to_string()),
+                ),
+                None => Source::Nick(nick.to_string(), Some(rest.to_string()), None),
             },
             // may be only hostname, but it could just be `nick@host`
             None => match s.split_once('@') {
-                Some((nick, host)) => {
-                    let nick = Nickname::new(nick).unwrap();
-                    Source::Nick(nick, None, Some(host.to_string()))
-                }
+                Some((nick, host)) => Source::Nick(nick.to_string(), None, Some(host.to_string())),
                 None => Source::Server(s.to_string()),
             },
         }
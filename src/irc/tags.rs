@@ -12,7 +12,152 @@ impl Tags {
         }
     }
 
+    /// parses the tags section of a message, with or without the leading `@`. tags with an empty
+    /// value are the same as tags without one, and when a tag appears more than once the last
+    /// value is used.
     pub fn parse(s: &str) -> Option<Self> {
-        todo!()
+        let s = s.strip_prefix('@').unwrap_or(s);
+        if s.is_empty() {
+            return None;
+        }
+
+        let mut tags = HashMap::new();
+        for tag in s.split(';').filter(|tag| !tag.is_empty()) {
+            let (key, value) = match tag.split_once('=') {
+                Some((key, value)) => (key, Some(unescape(value)).filter(|v| !v.is_empty())),
+                None => (tag, None),
+            };
+            if key.is_empty() || key == "+" {
+                return None;
+            }
+            tags.insert(key.to_string(), value);
+        }
+        Some(Self { tags })
+    }
+
+    /// the value of a tag, which is None if the tag is missing or has no value
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).and_then(|value| value.as_deref())
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.tags.insert(key.into(), Some(value.into()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// the tags section of a message, without the leading `@`
+    pub fn irc_str(&self) -> String {
+        let mut tags = self
+            .tags
+            .iter()
+            .map(|(key, value)| match value {
+                Some(value) => format!("{}={}", key, escape(value)),
+                None => key.clone(),
+            })
+            .collect::<Vec<_>>();
+        // NOTE: the order doesn't matter to servers, but it's easier to read logs that are stable
+        tags.sort();
+        tags.join(";")
+    }
+}
+
+/// escapes a tag value, which is the opposite of `unescape`
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' => out.push_str("\\:"),
+            ' ' => out.push_str("\\s"),
+            '\\' => out.push_str("\\\\"),
+            '\r' => out.push_str("\\r"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// unescapes a tag value, where `\:` is `;`, `\s` is a space, and `\r`, `\n` and `\\` are what
+/// they would be in Rust. a backslash before any other character is dropped, as is one at the end.
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => out.push(';'),
+            Some('s') => out.push(' '),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// sends a value through `irc_str` and `parse`, like a tag that we send and get echoed back
+    fn round_trip(value: &str) -> Option<String> {
+        let mut tags = Tags::empty();
+        tags.insert("key", value);
+        let text = tags.irc_str();
+        Tags::parse(&text)?.get("key").map(str::to_string)
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        for value in [";", " ", "\\", "\r", "\n", "a;b c\\d\r\ne", ";;  \\\\"] {
+            assert_eq!(round_trip(value).as_deref(), Some(value), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn escapes() {
+        let mut tags = Tags::empty();
+        tags.insert("key", "a;b c\\d\r\n");
+        assert_eq!(tags.irc_str(), r"key=a\:b\sc\\d\r\n");
+    }
+
+    #[test]
+    fn unescapes() {
+        let tags = Tags::parse(r"@a=\:;b=\s;c=\\;d=\r;e=\n").unwrap();
+        assert_eq!(tags.get("a"), Some(";"));
+        assert_eq!(tags.get("b"), Some(" "));
+        assert_eq!(tags.get("c"), Some("\\"));
+        assert_eq!(tags.get("d"), Some("\r"));
+        assert_eq!(tags.get("e"), Some("\n"));
+    }
+
+    #[test]
+    fn trailing_backslash() {
+        let tags = Tags::parse(r"key=value\").unwrap();
+        assert_eq!(tags.get("key"), Some("value"));
+        // an escaped backslash at the end is kept
+        assert_eq!(round_trip("value\\").as_deref(), Some("value\\"));
+    }
+
+    #[test]
+    fn unknown_escape() {
+        let tags = Tags::parse(r"key=\b\").unwrap();
+        assert_eq!(tags.get("key"), Some("b"));
+    }
+
+    #[test]
+    fn empty_value() {
+        let tags = Tags::parse(r"a=;b;c=\").unwrap();
+        assert_eq!(tags.get("a"), None);
+        assert_eq!(tags.get("b"), None);
+        assert_eq!(tags.get("c"), None);
+        assert_eq!(round_trip(""), None);
     }
 }
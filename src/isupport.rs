@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use crate::{casemap::Casemapping, irc::Param};
+
+/// the features that the server advertised with RPL_ISUPPORT
+#[derive(Debug, Default)]
+pub struct ISupport {
+    tokens: HashMap<String, Option<String>>,
+}
+
+impl ISupport {
+    /// updates the supported features from the params of a RPL_ISUPPORT reply
+    pub fn update(&mut self, args: &[Param]) {
+        // the first param is the client and the last is human readable text
+        let [_, tokens @ .., _] = args else {
+            return;
+        };
+
+        // tokens with a `,` in their value are parsed as lists, so join them back together
+        for token in tokens.iter().map(Param::to_irc_string) {
+            let token = token.as_str();
+            // a `-` prefix means that the server no longer supports the feature
+            if let Some(name) = token.strip_prefix('-') {
+                self.tokens.remove(name);
+                continue;
+            }
+
+            match token.split_once('=') {
+                Some((name, value)) => {
+                    self.tokens.insert(name.to_string(), Some(unescape(value)));
+                }
+                None => {
+                    self.tokens.insert(token.to_string(), None);
+                }
+            }
+        }
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.tokens.contains_key(name)
+    }
+
+    /// the value of a feature, if it was advertised with one
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.tokens.get(name).and_then(|value| value.as_deref())
+    }
+
+    pub fn casemapping(&self) -> Casemapping {
+        Casemapping::parse(self.value("CASEMAPPING"))
+    }
+
+    /// the maximum number of targets that `command` accepts in a single message, or None if there
+    /// is no limit
+    pub fn targmax(&self, command: &str) -> Option<usize> {
+        self.value("TARGMAX")?
+            .split(',')
+            .filter_map(|entry| entry.split_once(':'))
+            .find(|(cmd, _)| cmd.eq_ignore_ascii_case(command))
+            // an empty limit means there is no limit
+            .and_then(|(_, max)| max.parse().ok())
+    }
+
+    /// the maximum number of modes with a param that can be changed in a single MODE, or None if
+    /// there is no limit. servers that don't advertise MODES are required to accept 3.
+    pub fn modes(&self) -> Option<usize> {
+        match self.tokens.get("MODES") {
+            // MODES without a value means there is no limit
+            Some(value) => value.as_deref().and_then(|max| max.parse().ok()),
+            None => Some(DEFAULT_MODES),
+        }
+    }
+
+    /// the prefixes that share a limit with the prefix `kind` and the maximum number of channels
+    /// with any of them that can be joined at once, or None if there is no limit
+    pub fn chanlimit(&self, kind: char) -> Option<(&str, usize)> {
+        self.value("CHANLIMIT")?
+            .split(',')
+            .filter_map(|entry| entry.split_once(':'))
+            .find(|(prefixes, _)| prefixes.contains(kind))
+            .and_then(|(prefixes, max)| Some((prefixes, max.parse().ok()?)))
+    }
+}
+
+/// the number of modes with a param that servers accept in a single MODE if they don't say
+const DEFAULT_MODES: usize = 3;
+
+/// values may use `\xHH` escapes for characters that are not allowed in a param
+fn unescape(value: &str) -> String {
+    // escaped bytes may be part of a multi-byte character, so they're decoded together
+    let mut out = Vec::new();
+    let mut rest = value;
+    while let Some(idx) = rest.find("\\x") {
+        out.extend_from_slice(&rest.as_bytes()[..idx]);
+        rest = &rest[idx..];
+        match rest
+            .get(2..4)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            Some(byte) => {
+                out.push(byte);
+                rest = &rest[4..];
+            }
+            None => {
+                out.extend_from_slice(b"\\x");
+                rest = &rest[2..];
+            }
+        }
+    }
+    out.extend_from_slice(rest.as_bytes());
+    String::from_utf8_lossy(&out).into_owned()
+}
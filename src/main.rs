@@ -8,7 +8,7 @@
     round_char_boundary
 )]
 
-use std::{panic::set_hook, sync::mpsc::Sender};
+use std::{panic::set_hook, path::PathBuf, sync::mpsc::Sender};
 
 use clap::Parser;
 use crossterm::{execute, terminal};
@@ -17,18 +17,29 @@ use log::*;
 
 use crate::{
     client::ExitReason,
-    irc::client::{ClientIrcCommand, ClientMessage},
+    config::Config,
+    irc::client::{CapCommand, ClientIrcCommand, ClientMessage},
+    ui::{
+        template,
+        theme::{self, Theme},
+    },
 };
 
+mod alias;
+mod caps;
+mod casemap;
 mod channel;
 mod client;
 mod command;
+mod config;
 mod constants;
 mod ext;
 mod handlers;
 mod irc;
+mod isupport;
 mod logging;
 mod net;
+mod script;
 mod state;
 mod targets;
 mod ui;
@@ -48,6 +59,10 @@ struct Cli {
 
     #[arg(long)]
     twitch_token: Option<String>,
+
+    /// the directory that configuration and scripts are loaded from
+    #[arg(long, default_value = "./config/")]
+    config_dir: PathBuf,
 }
 
 fn main() -> eyre::Result<()> {
@@ -68,6 +83,7 @@ fn main() -> eyre::Result<()> {
         tls,
         nick,
         twitch_token,
+        config_dir,
     } = Cli::parse();
 
     let Some((name, _)) = addr.split_once(':') else {
@@ -75,9 +91,19 @@ fn main() -> eyre::Result<()> {
     };
 
     logging::init(name, LevelFilter::Debug).map_err(|_| eyre!("failed to init logger"))?;
+    let config = Config::load(config_dir)?;
+    if let Some(name) = config.theme.as_deref() {
+        theme::set(Theme::load(config.dir(), name, config.color_depth())?);
+    }
+    template::set(config.templates.clone());
 
     //code to run upon starting.
     let client_on_start = |sender: &Sender<ClientMessage>| {
+        // registration waits for CAP END if the server supports capability negotiation, otherwise
+        // the CAP is ignored
+        sender.send(ClientMessage::from_command(ClientIrcCommand::Cap(
+            CapCommand::Ls,
+        )))?;
         if let Some(token) = twitch_token.as_ref() {
             sender.send(ClientMessage::from_command(ClientIrcCommand::Pass(
                 token.to_string(),
@@ -95,7 +121,7 @@ fn main() -> eyre::Result<()> {
         Ok(())
     };
 
-    match client::start(addr.as_str(), nick.as_str(), tls, client_on_start) {
+    match client::start(addr.as_str(), nick.as_str(), tls, config, client_on_start) {
         // client.start() never returns Ok
         Ok(_) => unreachable!(),
         // no need to report anything on a requsted quit
@@ -0,0 +1,888 @@
+//! scripts are [rhai](https://rhai.rs) files loaded from the script directory. they can use:
+//! - `on(kind, |event| ...)` to handle incoming messages. `kind` is a command like `"PRIVMSG"`, a
+//!   numeric like `"001"` or `"RPL_WELCOME"`, or `"*"` for every message. every event has a `kind`
+//!   and a `source`, and messages without fields of their own have their `params`.
+//! - `command(name, |args| ...)` to define a new `/name` command
+//! - `timer(ms, || ...)` and `every(ms, || ...)` to run code later or repeatedly
+//! - `add_line(target, text)`, `send(target, text)`, `raw(line)`, and `run("/command ...")`
+//! - `print(text)` to add a line to the Status window
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use log::*;
+use rhai::{Array, Dynamic, Engine, FnPtr, FuncArgs, Map, AST};
+
+use crate::{
+    client,
+    constants::numerics::ALL_NUMERICS,
+    irc::{
+        self,
+        client::{ClientIrcCommand, ClientMessage},
+        IrcCommand, IrcMessage, Param,
+    },
+    state::ClientState,
+    targets::Target,
+    ui::theme::Role,
+    util,
+};
+
+/// the file extension of scripts that are loaded from the script directory
+const SCRIPT_EXTENSION: &str = "rhai";
+/// the maximum number of operations a single script call may perform before it is stopped. this
+/// keeps a script with an infinite loop from locking up the client.
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// the maximum nesting of script commands that run other script commands
+const MAX_COMMAND_DEPTH: u8 = 8;
+
+/// something a script asked the client to do. scripts cannot touch the client state directly,
+/// they queue actions that are applied once the script returns.
+#[derive(Debug)]
+pub enum ScriptAction {
+    /// add a line of text to a target window
+    AddLine { target: String, text: String },
+    /// send a PRIVMSG to a target
+    Send { target: String, text: String },
+    /// send raw text to the IRC server
+    Raw(String),
+    /// run a line of input as if the user typed it
+    Command(String),
+    /// a script failed, the error is reported in the Status window
+    Error(String),
+}
+
+struct Hook {
+    script: usize,
+    kind: String,
+    callback: FnPtr,
+}
+
+struct Timer {
+    script: usize,
+    due: Instant,
+    /// the interval to re-schedule the timer with, or None for one shot timers
+    repeat: Option<Duration>,
+    callback: FnPtr,
+}
+
+#[derive(Default)]
+struct Registry {
+    /// the index of the script that is currently running, used to associate anything registered
+    /// with the script that registered it
+    current_script: usize,
+    hooks: Vec<Hook>,
+    commands: HashMap<String, (usize, FnPtr)>,
+    timers: Vec<Timer>,
+    actions: Vec<ScriptAction>,
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+}
+
+pub struct ScriptHost {
+    dir: PathBuf,
+    engine: Engine,
+    scripts: Vec<Script>,
+    registry: Arc<Mutex<Registry>>,
+    /// the nesting depth of script commands that are currently being applied
+    depth: u8,
+}
+
+impl ScriptHost {
+    /// creates a script host that loads scripts from `dir`. no scripts are loaded until `reload`
+    /// is called.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let registry = Arc::new(Mutex::new(Registry::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        engine.on_print({
+            let registry = Arc::clone(&registry);
+            move |text| {
+                lock(&registry).actions.push(ScriptAction::AddLine {
+                    target: String::new(),
+                    text: text.to_string(),
+                })
+            }
+        });
+        engine.on_debug(|text, source, pos| {
+            debug!("script {:?} {}: {}", source, pos, text);
+        });
+
+        engine.register_fn("on", {
+            let registry = Arc::clone(&registry);
+            move |kind: &str, callback: FnPtr| {
+                let registry = &mut *lock(&registry);
+                registry.hooks.push(Hook {
+                    script: registry.current_script,
+                    kind: kind.to_uppercase(),
+                    callback,
+                });
+            }
+        });
+        engine.register_fn("command", {
+            let registry = Arc::clone(&registry);
+            move |name: &str, callback: FnPtr| {
+                let registry = &mut *lock(&registry);
+                let script = registry.current_script;
+                registry
+                    .commands
+                    .insert(name.to_lowercase(), (script, callback));
+            }
+        });
+        engine.register_fn("timer", {
+            let registry = Arc::clone(&registry);
+            move |ms: i64, callback: FnPtr| schedule(&registry, ms, false, callback)
+        });
+        engine.register_fn("every", {
+            let registry = Arc::clone(&registry);
+            move |ms: i64, callback: FnPtr| schedule(&registry, ms, true, callback)
+        });
+        engine.register_fn("add_line", {
+            let registry = Arc::clone(&registry);
+            move |target: &str, text: &str| {
+                lock(&registry).actions.push(ScriptAction::AddLine {
+                    target: target.to_string(),
+                    text: text.to_string(),
+                })
+            }
+        });
+        engine.register_fn("send", {
+            let registry = Arc::clone(&registry);
+            move |target: &str, text: &str| {
+                lock(&registry).actions.push(ScriptAction::Send {
+                    target: target.to_string(),
+                    text: text.to_string(),
+                })
+            }
+        });
+        engine.register_fn("raw", {
+            let registry = Arc::clone(&registry);
+            move |text: &str| {
+                lock(&registry)
+                    .actions
+                    .push(ScriptAction::Raw(text.to_string()))
+            }
+        });
+        engine.register_fn("run", {
+            let registry = Arc::clone(&registry);
+            move |input: &str| {
+                lock(&registry)
+                    .actions
+                    .push(ScriptAction::Command(input.to_string()))
+            }
+        });
+
+        Self {
+            dir: dir.into(),
+            engine,
+            scripts: Vec::new(),
+            registry,
+            depth: 0,
+        }
+    }
+
+    /// unloads all scripts, and then loads every script in the script directory again
+    pub fn reload(&mut self) -> Vec<ScriptAction> {
+        {
+            let registry = &mut *lock(&self.registry);
+            registry.hooks.clear();
+            registry.commands.clear();
+            registry.timers.clear();
+        }
+        self.scripts.clear();
+
+        // a missing script directory just means that there are no scripts
+        let mut paths = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == SCRIPT_EXTENSION))
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                debug!("not loading scripts from {:?}: {}", self.dir, e);
+                Vec::new()
+            }
+        };
+        // load in a consistent order so that scripts can rely on it
+        paths.sort();
+
+        let mut actions = Vec::new();
+        for path in paths {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let ast = match self.engine.compile_file(path) {
+                Ok(ast) => ast,
+                Err(e) => {
+                    actions.push(ScriptAction::Error(format!("{}: {}", name, e)));
+                    continue;
+                }
+            };
+
+            lock(&self.registry).current_script = self.scripts.len();
+            match self.engine.run_ast(&ast) {
+                Ok(()) => self.scripts.push(Script { name, ast }),
+                Err(e) => {
+                    // drop anything that the script registered before it failed
+                    self.unregister(self.scripts.len());
+                    actions.push(ScriptAction::Error(format!("{}: {}", name, e)));
+                }
+            }
+        }
+
+        info!("loaded {} scripts from {:?}", self.scripts.len(), self.dir);
+        actions.extend(self.take_actions());
+        actions
+    }
+
+    pub fn script_names(&self) -> impl Iterator<Item = &str> {
+        self.scripts.iter().map(|script| script.name.as_str())
+    }
+
+    /// runs the hooks that were registered for the kind of this message
+    pub fn on_message(&mut self, msg: &IrcMessage) -> Vec<ScriptAction> {
+        let events = message_events(msg);
+        if events.is_empty() {
+            return Vec::new();
+        }
+
+        let mut actions = Vec::new();
+        for (kinds, event) in events {
+            let hooks = lock(&self.registry)
+                .hooks
+                .iter()
+                .filter(|hook| kinds.contains(&hook.kind) || hook.kind == "*")
+                .map(|hook| (hook.script, hook.callback.clone()))
+                .collect::<Vec<_>>();
+            for (script, callback) in hooks {
+                self.call(script, &callback, (event.clone(),));
+            }
+            actions.extend(self.take_actions());
+        }
+        actions
+    }
+
+    pub fn has_command(&self, name: &str) -> bool {
+        lock(&self.registry)
+            .commands
+            .contains_key(&name.to_lowercase())
+    }
+
+    /// runs a command that was defined by a script, passing the rest of the input line as its
+    /// argument
+    pub fn run_command(&mut self, name: &str, args: &str) -> Vec<ScriptAction> {
+        let Some((script, callback)) = lock(&self.registry)
+            .commands
+            .get(&name.to_lowercase())
+            .cloned()
+        else {
+            return vec![ScriptAction::Error(format!("no script command {}", name))];
+        };
+
+        self.call(script, &callback, (args.to_string(),));
+        self.take_actions()
+    }
+
+    /// runs the callbacks of all timers that are due
+    pub fn poll_timers(&mut self) -> Vec<ScriptAction> {
+        let now = Instant::now();
+        let due = {
+            let registry = &mut *lock(&self.registry);
+            if !registry.timers.iter().any(|timer| timer.due <= now) {
+                return Vec::new();
+            }
+
+            let (due, pending) = core::mem::take(&mut registry.timers)
+                .into_iter()
+                .partition::<Vec<_>, _>(|timer| timer.due <= now);
+            registry.timers = pending;
+            due
+        };
+
+        for timer in due {
+            self.call(timer.script, &timer.callback, ());
+            if let Some(repeat) = timer.repeat {
+                lock(&self.registry).timers.push(Timer {
+                    due: now + repeat,
+                    ..timer
+                });
+            }
+        }
+        self.take_actions()
+    }
+
+    fn call(&mut self, script: usize, callback: &FnPtr, args: impl FuncArgs) {
+        let Some(Script { name, ast }) = self.scripts.get(script) else {
+            return;
+        };
+
+        lock(&self.registry).current_script = script;
+        if let Err(e) = callback.call::<Dynamic>(&self.engine, ast, args) {
+            lock(&self.registry)
+                .actions
+                .push(ScriptAction::Error(format!("{}: {}", name, e)));
+        }
+    }
+
+    fn unregister(&mut self, script: usize) {
+        let registry = &mut *lock(&self.registry);
+        registry.hooks.retain(|hook| hook.script != script);
+        registry.commands.retain(|_, (s, _)| *s != script);
+        registry.timers.retain(|timer| timer.script != script);
+    }
+
+    fn take_actions(&mut self) -> Vec<ScriptAction> {
+        core::mem::take(&mut lock(&self.registry).actions)
+    }
+}
+
+/// applies the actions that scripts requested to the client state. script errors are reported in
+/// the Status window and never interrupt the client.
+pub fn apply(state: &mut ClientState, actions: Vec<ScriptAction>) {
+    if actions.is_empty() {
+        return;
+    }
+    if state.scripts.depth >= MAX_COMMAND_DEPTH {
+        report_error(
+            state,
+            format!(
+                "script commands nested more than {} deep",
+                MAX_COMMAND_DEPTH
+            ),
+        );
+        return;
+    }
+
+    state.scripts.depth += 1;
+    for action in actions {
+        match action {
+            ScriptAction::AddLine { target, text } => {
+                // NOTE: scripts can't open windows, since a channel window would look joined. lines
+                // for windows that aren't open, including any before registration, go to status.
+                let target = match Target::new(target, state.casemapping()) {
+                    Some(target) if state.lines_for(&target).is_some() => target,
+                    _ => Target::Status,
+                };
+                state.add_line(
+                    target,
+                    util::line_now().push_unstyled(" ").push_unstyled(text),
+                );
+            }
+            ScriptAction::Send { target, text } => {
+                let target = match Target::new(target.as_str(), state.casemapping()) {
+                    Some(Target::Channel(channel)) => irc::Target::Channel(channel),
+                    Some(Target::Nickname(nick)) => irc::Target::User(nick),
+                    _ => {
+                        report_error(state, format!("cannot send to invalid target {:?}", target));
+                        continue;
+                    }
+                };
+                state.send_msg(ClientMessage::from_command(ClientIrcCommand::Privmsg {
+                    targets: vec![target],
+                    msg: text,
+                }));
+            }
+            ScriptAction::Raw(text) => {
+                state.send_msg(ClientMessage::from_command(ClientIrcCommand::Raw(text)));
+            }
+            ScriptAction::Command(input) => {
+                let sender = state.msg_sender.clone();
+                if let Err(e) = client::handle_input(state, &sender, input.as_str()) {
+                    report_error(state, e.to_string());
+                }
+            }
+            ScriptAction::Error(e) => report_error(state, e),
+        }
+    }
+    state.scripts.depth -= 1;
+}
+
+fn report_error(state: &mut ClientState, error: String) {
+    error!("script error: {}", error);
+    state.add_line(
+        Target::Status,
+        util::line_now()
+            .push_themed(Role::Error, " SCRIPT ERROR: ")
+            .push_themed(Role::Error, error),
+    );
+}
+
+fn schedule(registry: &Mutex<Registry>, ms: i64, repeat: bool, callback: FnPtr) {
+    let interval = Duration::from_millis(ms.max(0) as u64);
+    let registry = &mut *lock(registry);
+    registry.timers.push(Timer {
+        script: registry.current_script,
+        due: Instant::now() + interval,
+        repeat: repeat.then_some(interval),
+        callback,
+    });
+}
+
+fn lock(registry: &Mutex<Registry>) -> MutexGuard<'_, Registry> {
+    // a script can never panic while holding the lock, so poisoning only happens on a client bug
+    registry.lock().unwrap()
+}
+
+/// converts a message into the events that are passed to script hooks. each event has the kinds
+/// that a hook can be registered with to receive it. most messages produce one event, but messages
+/// with many targets produce one per target.
+fn message_events(msg: &IrcMessage) -> Vec<(Vec<String>, Map)> {
+    let source = msg
+        .source
+        .as_ref()
+        .map_or(String::new(), |source| source.get_name().to_string());
+    let event = |kind: &str, fields: Vec<(&str, Dynamic)>| {
+        let mut event = Map::new();
+        event.insert("kind".into(), kind.into());
+        event.insert("source".into(), source.clone().into());
+        for (name, value) in fields {
+            event.insert(name.into(), value);
+        }
+        event
+    };
+
+    match &msg.message {
+        IrcCommand::Privmsg { targets, msg } => targets
+            .iter()
+            .map(|target| {
+                (
+                    vec![String::from("PRIVMSG")],
+                    event(
+                        "PRIVMSG",
+                        vec![
+                            ("target", target.as_str().into()),
+                            ("text", msg.as_str().into()),
+                        ],
+                    ),
+                )
+            })
+            .collect(),
+        IrcCommand::Notice { targets, msg } => targets
+            .iter()
+            .map(|target| {
+                (
+                    vec![String::from("NOTICE")],
+                    event(
+                        "NOTICE",
+                        vec![
+                            ("target", target.as_str().into()),
+                            ("text", msg.as_str().into()),
+                        ],
+                    ),
+                )
+            })
+            .collect(),
+        IrcCommand::Join(channels) => channels
+            .iter()
+            .map(|(channel, _)| {
+                (
+                    vec![String::from("JOIN")],
+                    event("JOIN", vec![("channel", channel.as_str().into())]),
+                )
+            })
+            .collect(),
+        IrcCommand::Part(channels, reason) => channels
+            .iter()
+            .map(|channel| {
+                (
+                    vec![String::from("PART")],
+                    event(
+                        "PART",
+                        vec![
+                            ("channel", channel.as_str().into()),
+                            ("reason", reason.clone().unwrap_or_default().into()),
+                        ],
+                    ),
+                )
+            })
+            .collect(),
+        IrcCommand::Quit(reason) => vec![(
+            vec![String::from("QUIT")],
+            event(
+                "QUIT",
+                vec![("reason", reason.clone().unwrap_or_default().into())],
+            ),
+        )],
+        IrcCommand::Nick(nick) => vec![(
+            vec![String::from("NICK")],
+            event("NICK", vec![("nick", nick.as_str().into())]),
+        )],
+        IrcCommand::Topic(channel, topic) => vec![(
+            vec![String::from("TOPIC")],
+            event(
+                "TOPIC",
+                vec![
+                    ("channel", channel.as_str().into()),
+                    ("text", topic.clone().unwrap_or_default().into()),
+                ],
+            ),
+        )],
+        IrcCommand::Mode { target, mode } => vec![(
+            vec![String::from("MODE")],
+            event(
+                "MODE",
+                vec![
+                    ("target", target.as_str().into()),
+                    ("mode", mode.clone().unwrap_or_default().into()),
+                ],
+            ),
+        )],
+        IrcCommand::Kick {
+            channel,
+            user,
+            comment,
+        } => vec![(
+            vec![String::from("KICK")],
+            event(
+                "KICK",
+                vec![
+                    ("channel", channel.as_str().into()),
+                    ("nick", user.as_str().into()),
+                    ("reason", comment.clone().unwrap_or_default().into()),
+                ],
+            ),
+        )],
+        IrcCommand::Invite { nick, channel } => vec![(
+            vec![String::from("INVITE")],
+            event(
+                "INVITE",
+                vec![
+                    ("nick", nick.as_str().into()),
+                    ("channel", channel.as_str().into()),
+                ],
+            ),
+        )],
+        IrcCommand::Away { message } => vec![(
+            vec![String::from("AWAY")],
+            event(
+                "AWAY",
+                vec![
+                    ("away", message.is_some().into()),
+                    ("text", message.clone().unwrap_or_default().into()),
+                ],
+            ),
+        )],
+        IrcCommand::Numeric { num, args } => {
+            // numerics can be hooked by either their number or their name
+            let code = format!("{:03}", num);
+            let mut kinds = vec![code.clone()];
+            if let Some(name) = ALL_NUMERICS.get(num) {
+                kinds.push(name.to_string());
+            }
+            vec![(
+                kinds,
+                event(code.as_str(), vec![("params", params_array(args).into())]),
+            )]
+        }
+        IrcCommand::Unknown(name, args) => vec![(
+            vec![name.to_uppercase()],
+            event(name.as_str(), vec![("params", params_array(args).into())]),
+        )],
+        other => match generic_parts(other) {
+            Some((name, params)) => {
+                let params = params.into_iter().map(Dynamic::from).collect::<Array>();
+                vec![(
+                    vec![name.to_string()],
+                    event(name, vec![("params", params.into())]),
+                )]
+            }
+            None => Vec::new(),
+        },
+    }
+}
+
+/// the name and params of a message that has no fields of its own. messages with their own
+/// events have None.
+fn generic_parts(command: &IrcCommand) -> Option<(&'static str, Vec<String>)> {
+    let parts = match command {
+        IrcCommand::Cap {
+            subcommand, caps, ..
+        } => ("CAP", vec![subcommand.clone(), caps.join(" ")]),
+        IrcCommand::Authenticate => ("AUTHENTICATE", Vec::new()),
+        IrcCommand::Pass(pass) => ("PASS", vec![pass.clone()]),
+        IrcCommand::User(username, realname) => ("USER", vec![username.clone(), realname.clone()]),
+        IrcCommand::Ping(token) => ("PING", vec![token.clone()]),
+        IrcCommand::Pong(token) => ("PONG", vec![token.clone()]),
+        IrcCommand::Oper => ("OPER", Vec::new()),
+        IrcCommand::Error(reason) => ("ERROR", vec![reason.clone()]),
+        IrcCommand::Names(channels) => ("NAMES", vec![channels.join(",")]),
+        IrcCommand::List => ("LIST", Vec::new()),
+        IrcCommand::Motd { server } => ("MOTD", server.iter().cloned().collect()),
+        IrcCommand::Version { server } => ("VERSION", server.iter().cloned().collect()),
+        IrcCommand::Admin { server } => ("ADMIN", server.iter().cloned().collect()),
+        IrcCommand::Connect { server, port } => (
+            "CONNECT",
+            [server.clone()]
+                .into_iter()
+                .chain(port.map(|port| port.to_string()))
+                .collect(),
+        ),
+        IrcCommand::Lusers => ("LUSERS", Vec::new()),
+        IrcCommand::Time { server } => ("TIME", server.iter().cloned().collect()),
+        IrcCommand::Stats { query, server } => (
+            "STATS",
+            [query.to_string()]
+                .into_iter()
+                .chain(server.clone())
+                .collect(),
+        ),
+        IrcCommand::Help { subject } => ("HELP", subject.iter().cloned().collect()),
+        IrcCommand::Info => ("INFO", Vec::new()),
+        IrcCommand::Who { mask } => ("WHO", vec![mask.clone()]),
+        IrcCommand::Whois { target, nick } => {
+            ("WHOIS", target.iter().chain([nick]).cloned().collect())
+        }
+        IrcCommand::WhoWas { nick, count } => (
+            "WHOWAS",
+            [nick.clone()]
+                .into_iter()
+                .chain(count.map(|count| count.to_string()))
+                .collect(),
+        ),
+        IrcCommand::Kill { nick, comment } => ("KILL", vec![nick.clone(), comment.clone()]),
+        IrcCommand::Rehash => ("REHASH", Vec::new()),
+        IrcCommand::Restart => ("RESTART", Vec::new()),
+        IrcCommand::SQuit { server, comment } => ("SQUIT", vec![server.clone(), comment.clone()]),
+        IrcCommand::Links => ("LINKS", Vec::new()),
+        IrcCommand::Batch {
+            reference,
+            start,
+            kind,
+            params,
+        } => {
+            let sign = if *start { '+' } else { '-' };
+            (
+                "BATCH",
+                [format!("{}{}", sign, reference)]
+                    .into_iter()
+                    .chain(kind.clone())
+                    .chain(params.iter().cloned())
+                    .collect(),
+            )
+        }
+        IrcCommand::Raw(text) => ("RAW", vec![text.clone()]),
+        IrcCommand::Privmsg { .. }
+        | IrcCommand::Notice { .. }
+        | IrcCommand::Join(_)
+        | IrcCommand::Part(_, _)
+        | IrcCommand::Quit(_)
+        | IrcCommand::Nick(_)
+        | IrcCommand::Topic(_, _)
+        | IrcCommand::Mode { .. }
+        | IrcCommand::Kick { .. }
+        | IrcCommand::Invite { .. }
+        | IrcCommand::Away { .. }
+        | IrcCommand::Numeric { .. }
+        | IrcCommand::Unknown(_, _) => return None,
+    };
+    Some(parts)
+}
+
+fn params_array(params: &[Param]) -> Array {
+    params
+        .iter()
+        .map(|param| param.to_irc_string().into())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc,
+        },
+    };
+
+    use super::*;
+    use crate::{
+        config::Config,
+        state::ConnectionState,
+        ui::{
+            layout::{Direction, Layout, Section, SectionKind},
+            term::TerminalUi,
+            text::Line,
+        },
+    };
+
+    fn client_state() -> ClientState<'static> {
+        let (sender, _) = mpsc::channel();
+        // the topic bar, messages, status bar and input line, like the client's
+        let leaf = |kind| Section::Leaf { kind };
+        let layout = Layout {
+            direction: Direction::Vertical,
+            sections: vec![
+                leaf(SectionKind::Exact(1)),
+                leaf(SectionKind::Fill(1)),
+                leaf(SectionKind::Exact(1)),
+                leaf(SectionKind::Exact(1)),
+            ],
+        };
+        ClientState::new(
+            "irc.example:6697",
+            sender,
+            TerminalUi::headless(layout, io::sink()),
+            String::from("me"),
+            ScriptHost::new(std::env::temp_dir().join("no-scripts")),
+            Config::default(),
+        )
+    }
+
+    fn status_text(state: &mut ClientState) -> Vec<String> {
+        state
+            .lines_for(&Target::Status)
+            .unwrap()
+            .iter()
+            .map(Line::fmt_unstyled)
+            .collect()
+    }
+
+    #[test]
+    fn add_line_before_registration() {
+        let mut state = client_state();
+        apply(
+            &mut state,
+            vec![
+                ScriptAction::AddLine {
+                    target: String::from("#chan"),
+                    text: String::from("to a channel"),
+                },
+                ScriptAction::AddLine {
+                    target: String::from("alice"),
+                    text: String::from("to a nick"),
+                },
+            ],
+        );
+        let lines = status_text(&mut state);
+        assert!(lines[0].ends_with("to a channel"), "{:?}", lines);
+        assert!(lines[1].ends_with("to a nick"), "{:?}", lines);
+        assert!(matches!(state.conn_state, ConnectionState::Registration(_)));
+    }
+
+    /// a script host with one script, loaded from a directory of its own
+    fn host(source: &str) -> ScriptHost {
+        static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "script-test-{}-{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("test.rhai"), source).unwrap();
+        let mut host = ScriptHost::new(&dir);
+        let actions = host.reload();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            !actions
+                .iter()
+                .any(|action| matches!(action, ScriptAction::Error(_))),
+            "{:?}",
+            actions
+        );
+        host
+    }
+
+    /// the text of the lines that the actions add, with their targets
+    fn lines(actions: &[ScriptAction]) -> Vec<(&str, &str)> {
+        actions
+            .iter()
+            .map(|action| match action {
+                ScriptAction::AddLine { target, text } => (target.as_str(), text.as_str()),
+                other => panic!("expected a line, got {:?}", other),
+            })
+            .collect()
+    }
+
+    fn message(line: &str) -> IrcMessage {
+        IrcMessage::parse(line).unwrap()
+    }
+
+    #[test]
+    fn hooks() {
+        let mut host = host(
+            r##"
+            on("PRIVMSG", |e| add_line(e.target, e.source + ": " + e.text));
+            on("kick", |e| add_line(e.channel, e.nick + " kicked: " + e.reason));
+            on("RPL_WELCOME", |e| print("welcome " + e.params[0]));
+            on("PING", |e| print("ping " + e.params[0]));
+            on("*", |e| print(e.kind));
+            "##,
+        );
+
+        let actions = host.on_message(&message(":alice!a@host PRIVMSG #a,#b :hi"));
+        assert_eq!(
+            lines(&actions),
+            [
+                ("#a", "alice: hi"),
+                ("", "PRIVMSG"),
+                ("#b", "alice: hi"),
+                ("", "PRIVMSG")
+            ]
+        );
+        let actions = host.on_message(&message(":op!o@host KICK #a bob :bye"));
+        assert_eq!(lines(&actions), [("#a", "bob kicked: bye"), ("", "KICK")]);
+        let actions = host.on_message(&message(":irc.example 001 me :Welcome"));
+        assert_eq!(lines(&actions), [("", "welcome me"), ("", "001")]);
+        let actions = host.on_message(&message("PING :token"));
+        assert_eq!(lines(&actions), [("", "ping token"), ("", "PING")]);
+        // messages without a hook of their own still reach `*`
+        let actions = host.on_message(&message(":alice!a@host AWAY :lunch"));
+        assert_eq!(lines(&actions), [("", "AWAY")]);
+        let actions = host.on_message(&message(":alice!a@host INVITE me #c"));
+        assert_eq!(lines(&actions), [("", "INVITE")]);
+        let actions = host.on_message(&message("BATCH +ref netsplit a.example b.example"));
+        assert_eq!(lines(&actions), [("", "BATCH")]);
+    }
+
+    #[test]
+    fn commands() {
+        let mut host = host(
+            r##"
+            command("Greet", |args| send("#chan", "hi " + args));
+            command("fail", |args| throw "oops");
+            "##,
+        );
+        assert!(host.has_command("greet"));
+        assert!(host.has_command("GREET"));
+        assert!(!host.has_command("other"));
+
+        let actions = host.run_command("greet", "bob");
+        let [ScriptAction::Send { target, text }] = actions.as_slice() else {
+            panic!("expected a send, got {:?}", actions);
+        };
+        assert_eq!((target.as_str(), text.as_str()), ("#chan", "hi bob"));
+
+        let actions = host.run_command("fail", "");
+        assert!(
+            matches!(actions.as_slice(), [ScriptAction::Error(e)] if e.contains("oops")),
+            "{:?}",
+            actions
+        );
+        let actions = host.run_command("other", "");
+        assert!(matches!(actions.as_slice(), [ScriptAction::Error(_)]));
+    }
+
+    #[test]
+    fn timers() {
+        let mut host = host(
+            r##"
+            timer(0, || print("once"));
+            every(0, || print("again"));
+            timer(1000000, || print("later"));
+            "##,
+        );
+        let actions = host.poll_timers();
+        assert_eq!(lines(&actions), [("", "once"), ("", "again")]);
+        let actions = host.poll_timers();
+        assert_eq!(lines(&actions), [("", "again")]);
+    }
+}
@@ -1,19 +1,43 @@
-use core::fmt;
+use core::{fmt, time::Duration};
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     sync::mpsc::Sender,
+    time::Instant,
 };
 
-use crossterm::style::Stylize;
+use chrono::{DateTime, Local, Utc};
 use log::*;
 
 use crate::{
-    channel::{Channel, ChannelName, Nickname, UserMessages},
-    irc::{client::ClientMessage, IrcMessage},
+    caps::Caps,
+    casemap::Casemapping,
+    channel::{
+        list::{ChannelList, ListWindow},
+        mode::ListEntry,
+        Channel, ChannelName, Nickname, UserMessages,
+    },
+    config::Config,
+    handlers::{
+        batch::BatchState,
+        echo::EchoState,
+        history::{self, HistoryState},
+        notify::NotifyState,
+        who::WhoQueue,
+        whois::{WhoisInfo, WhoisKind},
+    },
+    irc::{
+        client::{ClientIrcCommand, ClientMessage},
+        IrcMessage,
+    },
+    isupport::ISupport,
+    script::ScriptHost,
     targets::Target,
-    ui::{keybinds::Action, term::TerminalUi, text::Line},
+    ui::{format, keybinds::Action, template, term::TerminalUi, text::Line, theme::Role},
 };
 
+/// the away message for auto-away if the config doesn't have one
+const AUTO_AWAY_MESSAGE: &str = "idle";
+
 pub struct ClientState<'a> {
     addr: String,
     pub ui: TerminalUi<'a>,
@@ -21,7 +45,13 @@ pub struct ClientState<'a> {
     all_targets: Vec<Target>,
     selected_target_idx: usize,
     status_messages: VecDeque<Line<'static>>,
+    /// the msgids of the lines in `status_messages`
+    status_msgids: HashSet<String>,
     pub msg_sender: Sender<ClientMessage>,
+    pub scripts: ScriptHost,
+    pub config: Config,
+    /// when the user last pressed a key, for auto-away
+    last_input: Instant,
 }
 
 #[derive(Debug)]
@@ -29,7 +59,11 @@ pub struct StatusInfo {
     pub addr: String,
     pub registered: bool,
     pub nick: String,
+    /// whether we are marked as away
+    pub away: bool,
     pub target: Target,
+    /// the topic of the current channel, or whether the user of a query window is online
+    pub topic: Option<String>,
 }
 
 impl<'a> ClientState<'a> {
@@ -40,15 +74,24 @@ impl<'a> ClientState<'a> {
         msg_sender: Sender<ClientMessage>,
         ui: TerminalUi<'a>,
         requested_nick: String,
+        scripts: ScriptHost,
+        config: Config,
     ) -> Self {
         Self {
             addr: addr.to_string(),
             ui,
-            conn_state: ConnectionState::Registration(RegistrationState { requested_nick }),
+            conn_state: ConnectionState::Registration(RegistrationState {
+                requested_nick,
+                caps: Caps::default(),
+            }),
             all_targets: vec![Target::Status],
             selected_target_idx: ClientState::TARGET_STATUS_IDX,
             status_messages: VecDeque::new(),
+            status_msgids: HashSet::new(),
             msg_sender,
+            scripts,
+            config,
+            last_input: Instant::now(),
         }
     }
 
@@ -57,6 +100,14 @@ impl<'a> ClientState<'a> {
     }
 
     pub fn add_line(&mut self, target: Target, line: Line<'static>) {
+        // the status window exists before registration has finished. the LIST window only shows
+        // channels, so its lines go to the status window instead.
+        if matches!(target, Target::Status | Target::List) {
+            push_line(&mut self.status_messages, &mut self.status_msgids, line);
+            let _ = self.render();
+            return;
+        }
+
         self.ensure_target_exists(target.clone());
         let ConnectionState::Connected(ConnectedState {
             channels,
@@ -70,16 +121,14 @@ impl<'a> ClientState<'a> {
             Target::Channel(channel_name) => {
                 // UNWRAP: `ensure_target_exists` called above
                 let channel = channels.get_mut(&channel_name).unwrap();
-                channel.messages.push_back(line);
+                push_line(&mut channel.messages, &mut channel.msgids, line);
             }
             Target::Nickname(nick) => {
                 // UNWRAP: `ensure_target_exists` called above
                 let user_messages = user_messages.get_mut(&nick).unwrap();
                 user_messages.add_line(line);
             }
-            Target::Status => {
-                self.status_messages.push_back(line);
-            }
+            Target::Status | Target::List => unreachable!("status lines are added above"),
         }
 
         self.render();
@@ -92,7 +141,9 @@ impl<'a> ClientState<'a> {
         let Some(lines) = self.current_lines() else {
             return;
         };
-        let line = Line::default().push("ERROR: ".red()).push(error.red());
+        let line = Line::default()
+            .push_themed(Role::Error, "ERROR: ")
+            .push_themed(Role::Error, error);
         lines.push_back(line);
         self.render();
     }
@@ -102,7 +153,9 @@ impl<'a> ClientState<'a> {
         let Some(lines) = self.current_lines() else {
             return;
         };
-        let line = Line::default().push("WARN: ".yellow()).push(msg.yellow());
+        let line = Line::default()
+            .push_themed(Role::Warning, "WARN: ")
+            .push_themed(Role::Warning, msg);
         lines.push_back(line);
         self.render();
     }
@@ -113,7 +166,9 @@ impl<'a> ClientState<'a> {
             return;
         };
         warn!("{:?} {}", target, msg);
-        let line = Line::default().push("WARN: ".yellow()).push(msg.yellow());
+        let line = Line::default()
+            .push_themed(Role::Warning, "WARN: ")
+            .push_themed(Role::Warning, msg);
         lines.push_back(line);
         self.render();
     }
@@ -126,9 +181,17 @@ impl<'a> ClientState<'a> {
             ConnectionState::Connected(ConnectedState {
                 channels,
                 user_messages,
+                list_window,
                 ..
             }) => match target {
                 Target::Status => {}
+                Target::List => {
+                    if list_window.is_none() {
+                        self.all_targets.push(Target::List);
+                        self.selected_target_idx = self.all_targets.len() - 1;
+                        *list_window = Some(ListWindow::Channels(ChannelList::default()));
+                    }
+                }
                 Target::Channel(channel_name) => {
                     if !channels.contains_key(&channel_name) {
                         self.all_targets.push(Target::Channel(channel_name.clone()));
@@ -147,26 +210,158 @@ impl<'a> ClientState<'a> {
         }
     }
 
+    /// closes the window for a target, forgetting its messages. the status window can't be closed.
+    pub fn remove_target(&mut self, target: &Target) {
+        let Some(idx) = self.all_targets.iter().position(|t| t == target) else {
+            return;
+        };
+        if idx == ClientState::TARGET_STATUS_IDX {
+            return;
+        }
+
+        self.all_targets.remove(idx);
+        // keep the same window selected, or the one before it if it was the one removed
+        if self.selected_target_idx >= idx {
+            self.selected_target_idx -= 1;
+        }
+
+        if let ConnectionState::Connected(ConnectedState {
+            channels,
+            user_messages,
+            list_window,
+            ..
+        }) = &mut self.conn_state
+        {
+            match target {
+                Target::Status => {}
+                Target::List => *list_window = None,
+                Target::Channel(channel_name) => {
+                    channels.remove(channel_name);
+                }
+                Target::Nickname(nick) => {
+                    user_messages.remove(nick);
+                }
+            }
+        }
+
+        let _ = self.render();
+    }
+
+    /// switches to the window for a target, if it exists
+    pub fn select_target(&mut self, target: &Target) {
+        if let Some(idx) = self.all_targets.iter().position(|t| t == target) {
+            self.selected_target_idx = idx;
+        }
+    }
+
     pub fn current_target(&self) -> &Target {
         &self.all_targets[self.selected_target_idx]
     }
 
+    /// how the server compares nicks and channel names, which is rfc1459 until it says otherwise
+    pub fn casemapping(&self) -> Casemapping {
+        match &self.conn_state {
+            ConnectionState::Connected(ConnectedState { isupport, .. }) => isupport.casemapping(),
+            ConnectionState::Registration(_) => Casemapping::default(),
+        }
+    }
+
+    /// compares every nick and channel name that is known with a new casemapping, since they
+    /// were compared with the one before it
+    pub fn rekey(&mut self, casemapping: Casemapping) {
+        self.all_targets = self
+            .all_targets
+            .iter()
+            .map(|target| target.rekey(casemapping))
+            .collect();
+        let ConnectionState::Connected(connected) = &mut self.conn_state else {
+            return;
+        };
+        connected.channels = connected
+            .channels
+            .drain()
+            .map(|(name, mut channel)| {
+                channel.rekey(casemapping);
+                (name.rekey(casemapping), channel)
+            })
+            .collect();
+        connected.user_messages = connected
+            .user_messages
+            .drain()
+            .map(|(nick, messages)| (nick.rekey(casemapping), messages))
+            .collect();
+        connected.channel_keys = connected
+            .channel_keys
+            .drain()
+            .map(|(name, key)| (name.rekey(casemapping), key))
+            .collect();
+        connected.last_invite = connected
+            .last_invite
+            .as_ref()
+            .map(|name| name.rekey(casemapping));
+        connected.messages_state.active_lists = connected
+            .messages_state
+            .active_lists
+            .drain()
+            .map(|((name, mode), entries)| ((name.rekey(casemapping), mode), entries))
+            .collect();
+        connected.history.rekey(casemapping);
+        connected.echo.rekey(casemapping);
+        connected.notify.rekey(casemapping);
+    }
+
     pub fn render(&mut self) -> eyre::Result<()> {
-        let (registered, nick) = match &mut self.conn_state {
-            ConnectionState::Registration(RegistrationState { requested_nick }) => {
-                (false, requested_nick.clone())
+        let (registered, nick, away) = match &mut self.conn_state {
+            ConnectionState::Registration(RegistrationState { requested_nick, .. }) => {
+                (false, requested_nick.clone(), false)
+            }
+            ConnectionState::Connected(ConnectedState { nick, away, .. }) => {
+                (true, nick.clone(), *away)
             }
-            ConnectionState::Connected(ConnectedState { nick, .. }) => (true, nick.clone()),
         };
 
         let target = self.current_target().clone();
         trace!("rendering for {:?}", target);
 
+        let topic = match (&target, &self.conn_state) {
+            (
+                Target::Channel(channel_name),
+                ConnectionState::Connected(ConnectedState { channels, .. }),
+            ) => channels
+                .get(channel_name)
+                .and_then(Channel::topic)
+                // the status bar is a single style, so any formatting is removed
+                .map(|topic| format::strip(&topic.text)),
+            // query windows show whether the user is online, if they're in the notify list
+            (
+                Target::Nickname(nick),
+                ConnectionState::Connected(ConnectedState {
+                    notify,
+                    user_messages,
+                    ..
+                }),
+            ) => notify.presence(nick).map(|online| {
+                let away_message = user_messages
+                    .get(nick)
+                    .and_then(|messages| messages.away_message.as_deref());
+                match (online, away_message) {
+                    (true, Some(message)) => {
+                        format!("{} is online, away: {}", nick.as_str(), message)
+                    }
+                    (true, None) => format!("{} is online", nick.as_str()),
+                    (false, _) => format!("{} is offline", nick.as_str()),
+                }
+            }),
+            _ => None,
+        };
+
         let status = StatusInfo {
             addr: self.addr.clone(),
             registered,
             nick,
+            away,
             target: target.clone(),
+            topic,
         };
 
         match &target {
@@ -205,6 +400,19 @@ impl<'a> ClientState<'a> {
 
                 Ok(())
             }
+            Target::List => {
+                let ConnectionState::Connected(ConnectedState {
+                    list_window: Some(list_window),
+                    ..
+                }) = &mut self.conn_state
+                else {
+                    return Ok(());
+                };
+
+                let (width, height) = self.ui.main_size()?;
+                let rows = list_window.rows(list_search(&self.ui), width, height);
+                self.ui.render_rows(&status, &rows)
+            }
         }
     }
 
@@ -213,6 +421,9 @@ impl<'a> ClientState<'a> {
         let Some(action) = self.ui.raw_input()? else {
             return Ok(None);
         };
+        if !matches!(action, Action::Resize) {
+            self.input_activity();
+        }
 
         match action {
             Action::Resize => {
@@ -240,12 +451,29 @@ impl<'a> ClientState<'a> {
                 Ok(None)
             }
             Action::PreviousLine => {
-                self.ui.scrollback = self.ui.scrollback.saturating_add(1);
+                match self.list_window() {
+                    Some(list_window) => list_window.move_selection(-1),
+                    None => {
+                        let len = self.current_lines().map_or(0, |lines| lines.len());
+                        if self.ui.scrollback + 1 < len {
+                            self.ui.scrollback += 1;
+                        } else {
+                            // the oldest line is already at the bottom, so ask for what came
+                            // before it
+                            self.ui.scrollback = len.saturating_sub(1);
+                            let target = self.current_target().clone();
+                            history::fetch_before(self, &target);
+                        }
+                    }
+                }
                 self.render()?;
                 Ok(None)
             }
             Action::NextLine => {
-                self.ui.scrollback = self.ui.scrollback.saturating_sub(1);
+                match self.list_window() {
+                    Some(list_window) => list_window.move_selection(1),
+                    None => self.ui.scrollback = self.ui.scrollback.saturating_sub(1),
+                }
                 self.render()?;
                 Ok(None)
             }
@@ -287,6 +515,90 @@ impl<'a> ClientState<'a> {
                 self.render()?;
                 Ok(None)
             }
+            Action::InsertFormat(code) => {
+                self.ui.input_buffer.insert(code);
+                self.render()?;
+                Ok(None)
+            }
+            Action::AcceptInvite => {
+                self.accept_invite();
+                Ok(None)
+            }
+            Action::CycleSort => {
+                if let Some(list_window) = self.list_window() {
+                    list_window.cycle_sort();
+                    self.render()?;
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// joins the channel that we were most recently invited to
+    pub fn accept_invite(&mut self) {
+        let ConnectionState::Connected(ConnectedState { last_invite, .. }) = &mut self.conn_state
+        else {
+            self.error(String::from("cannot accept an invite when not registered"));
+            return;
+        };
+        let Some(channel) = last_invite.take() else {
+            self.error(String::from("no invite to accept"));
+            return;
+        };
+        self.send_msg(ClientMessage::from_command(ClientIrcCommand::Join(vec![(
+            channel, None,
+        )])));
+    }
+
+    /// marks us as back if we were automatically marked as away
+    fn input_activity(&mut self) {
+        self.last_input = Instant::now();
+        if let ConnectionState::Connected(ConnectedState { auto_away, .. }) = &mut self.conn_state
+            && *auto_away
+        {
+            *auto_away = false;
+            self.send_msg(ClientMessage::from_command(ClientIrcCommand::Away {
+                message: None,
+            }));
+        }
+    }
+
+    /// marks us as away if there hasn't been any input for the configured auto-away time
+    pub fn check_idle(&mut self) {
+        let Some(minutes) = self.config.auto_away_minutes else {
+            return;
+        };
+        let ConnectionState::Connected(ConnectedState {
+            away, auto_away, ..
+        }) = &mut self.conn_state
+        else {
+            return;
+        };
+        if *away || self.last_input.elapsed() < Duration::from_secs(minutes.saturating_mul(60)) {
+            return;
+        }
+
+        *auto_away = true;
+        // set now rather than waiting for RPL_NOWAWAY, so that AWAY isn't sent again until then
+        *away = true;
+        let message = self
+            .config
+            .auto_away_message
+            .clone()
+            .unwrap_or_else(|| String::from(AUTO_AWAY_MESSAGE));
+        self.send_msg(ClientMessage::from_command(ClientIrcCommand::Away {
+            message: Some(message),
+        }));
+    }
+
+    /// what the LIST window shows, if it is the current window
+    pub fn list_window(&mut self) -> Option<&mut ListWindow> {
+        if *self.current_target() != Target::List {
+            return None;
+        }
+        match &mut self.conn_state {
+            ConnectionState::Connected(ConnectedState { list_window, .. }) => list_window.as_mut(),
+            ConnectionState::Registration(_) => None,
         }
     }
 
@@ -299,7 +611,7 @@ impl<'a> ClientState<'a> {
             }
         };
         match target {
-            Target::Status => Some(&mut self.status_messages),
+            Target::Status | Target::List => Some(&mut self.status_messages),
             Target::Channel(channel_name) => {
                 if let ConnectionState::Connected(ConnectedState { channels, .. }) =
                     &mut self.conn_state
@@ -321,9 +633,9 @@ impl<'a> ClientState<'a> {
         }
     }
 
-    fn lines_for(&mut self, target: &Target) -> Option<&mut VecDeque<Line<'static>>> {
+    pub fn lines_for(&mut self, target: &Target) -> Option<&mut VecDeque<Line<'static>>> {
         match target {
-            Target::Status => Some(&mut self.status_messages),
+            Target::Status | Target::List => Some(&mut self.status_messages),
             Target::Channel(channel_name) => {
                 if let ConnectionState::Connected(ConnectedState { channels, .. }) =
                     &mut self.conn_state
@@ -333,11 +645,79 @@ impl<'a> ClientState<'a> {
                     None
                 }
             }
-            Target::Nickname(_) => todo!(),
+            Target::Nickname(nick) => {
+                if let ConnectionState::Connected(ConnectedState { user_messages, .. }) =
+                    &mut self.conn_state
+                {
+                    user_messages.get_mut(nick).map(|c| &mut c.messages)
+                } else {
+                    None
+                }
+            }
         }
     }
 }
 
+/// adds a line to a window. lines are kept in the order of their times, so a line from before the
+/// end of the window, like backlog from a bouncer, is put before the lines that are newer than it.
+/// the first line of each day has a day change line before it. `msgids` are the msgids of the
+/// lines in the window.
+pub fn push_line(
+    lines: &mut VecDeque<Line<'static>>,
+    msgids: &mut HashSet<String>,
+    line: Line<'static>,
+) {
+    // history can overlap with the lines that are already shown
+    if let Some(msgid) = line.msgid()
+        && !msgids.insert(msgid.to_string())
+    {
+        return;
+    }
+    let Some(time) = line.time() else {
+        lines.push_back(line);
+        return;
+    };
+    let date = |time: DateTime<Utc>| time.with_timezone(&Local).date_naive();
+
+    // lines without a time, like errors, stay after the line that they were added after, so this
+    // goes before the first newer line after the last line that isn't newer
+    let start = lines
+        .iter()
+        .rposition(|line| line.time().is_some_and(|other| other <= time))
+        .map_or(0, |idx| idx + 1);
+    let idx = lines
+        .range(start..)
+        .position(|line| line.time().is_some())
+        .map_or(lines.len(), |idx| start + idx);
+    let before = lines.range(..idx).rev().find_map(Line::time);
+    let after = lines.range(idx..).find_map(Line::time);
+
+    // NOTE: a day change line already comes before the lines after this one, unless this is the
+    // first line with a time
+    if before.is_none()
+        && let Some(after) = after
+        && date(after) > date(time)
+    {
+        lines.insert(idx, template::day_changed(date(after)));
+    }
+    lines.insert(idx, line);
+    if let Some(before) = before
+        && date(before) < date(time)
+    {
+        lines.insert(idx, template::day_changed(date(time)));
+    }
+}
+
+/// the search in the LIST window, which is whatever has been typed unless it's a command
+fn list_search<'ui>(ui: &'ui TerminalUi) -> &'ui str {
+    let input = ui.input_buffer.as_str();
+    if input.starts_with('/') {
+        ""
+    } else {
+        input
+    }
+}
+
 impl<'a> fmt::Debug for ClientState<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ClientState")
@@ -357,6 +737,8 @@ pub struct RegistrationState {
     /// the nick that the user requested. the server will respond with the actual nick in the
     /// RPL_WELCOME message.
     pub requested_nick: String,
+    /// capabilities negotiated before registration finished
+    pub caps: Caps,
 }
 
 #[derive(Debug)]
@@ -367,6 +749,31 @@ pub struct ConnectedState {
     /// all users with which there exists a private message
     pub user_messages: HashMap<Nickname, UserMessages>,
     pub messages_state: MessagesState,
+    /// our own user modes, without a `+`
+    pub user_modes: String,
+    /// the features that the server supports
+    pub isupport: ISupport,
+    /// the keys that were used to join channels, so that they can be rejoined without the key
+    pub channel_keys: HashMap<ChannelName, String>,
+    /// the channels from `/list` or the entries of a list mode, while the LIST window is open
+    pub list_window: Option<ListWindow>,
+    /// the capabilities that were negotiated with the server
+    pub caps: Caps,
+    /// whether the server has marked us as away
+    pub away: bool,
+    /// whether we were marked as away automatically for being idle, so that any input marks us as
+    /// back again
+    pub auto_away: bool,
+    /// the channel that we were most recently invited to, which can be joined with `/accept`
+    pub last_invite: Option<ChannelName>,
+    /// whether the nicks in the notify list are online
+    pub notify: NotifyState,
+    /// batches of messages that haven't ended yet
+    pub batches: BatchState,
+    /// the history that has been asked for and which windows have no more of it
+    pub history: HistoryState,
+    /// our own messages that are waiting to be echoed back
+    pub echo: EchoState,
 }
 
 impl ConnectedState {
@@ -377,7 +784,22 @@ impl ConnectedState {
             user_messages: HashMap::new(),
             messages_state: MessagesState {
                 active_names: HashMap::new(),
+                active_lists: HashMap::new(),
+                active_whois: HashMap::new(),
+                who_queue: WhoQueue::default(),
             },
+            user_modes: String::new(),
+            isupport: ISupport::default(),
+            channel_keys: HashMap::new(),
+            list_window: None,
+            caps: Caps::default(),
+            away: false,
+            auto_away: false,
+            last_invite: None,
+            notify: NotifyState::default(),
+            batches: BatchState::default(),
+            history: HistoryState::default(),
+            echo: EchoState::default(),
         }
     }
 }
@@ -387,9 +809,16 @@ impl ConnectedState {
 pub struct MessagesState {
     // a list of channels with active NAMES replies
     pub active_names: HashMap<String, NamesState>,
+    // list modes with active replies, like the ban list, by channel and mode
+    pub active_lists: HashMap<(ChannelName, char), Vec<ListEntry>>,
+    // WHOIS and WHOWAS replies that haven't ended yet, by nick in the server's casemapping
+    pub active_whois: HashMap<String, (WhoisKind, Vec<WhoisInfo>)>,
+    // channels waiting for an automatic WHO after being joined
+    pub who_queue: WhoQueue,
 }
 
 #[derive(Debug)]
 pub struct NamesState {
+    /// the names params of each reply, which each have a list of nicks
     pub names: Vec<String>,
 }
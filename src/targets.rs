@@ -1,6 +1,8 @@
 use crate::{
+    casemap::Casemapping,
     channel::{ChannelName, Nickname},
     constants::names::CHANNEL_TYPES,
+    irc,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -9,10 +11,12 @@ pub enum Target {
     Channel(ChannelName),
     Nickname(Nickname),
     Status,
+    /// the window that shows the channels from `/list`
+    List,
 }
 
 impl Target {
-    pub fn new(s: impl Into<String>) -> Option<Self> {
+    pub fn new(s: impl Into<String>, casemapping: Casemapping) -> Option<Self> {
         let s: String = s.into();
         let Some(first) = s.chars().next() else {
             return None;
@@ -21,9 +25,19 @@ impl Target {
         // everything that starts with a channel type is a channel, everything else is a nick, if
         // it's valid
         if CHANNEL_TYPES.contains(&first) {
-            Some(Target::Channel(ChannelName::new(s)?))
+            Some(Target::Channel(ChannelName::new(s, casemapping)?))
         } else {
-            Nickname::new(s).map(|n| Target::Nickname(n))
+            Nickname::new(s, casemapping).map(|n| Target::Nickname(n))
+        }
+    }
+
+    /// the same target, compared with a different casemapping
+    pub fn rekey(&self, casemapping: Casemapping) -> Self {
+        match self {
+            Target::Channel(channel_name) => Target::Channel(channel_name.rekey(casemapping)),
+            Target::Nickname(nick) => Target::Nickname(nick.rekey(casemapping)),
+            Target::Status => Target::Status,
+            Target::List => Target::List,
         }
     }
 
@@ -32,6 +46,17 @@ impl Target {
             Target::Channel(channel_name) => channel_name.as_str(),
             Target::Nickname(nick) => nick.as_str(),
             Target::Status => "[STATUS]",
+            Target::List => "[LIST]",
+        }
+    }
+
+    /// the IRC target that messages for this target are sent to. the status and LIST windows have
+    /// no IRC target.
+    pub fn irc_target(&self) -> Option<irc::Target> {
+        match self {
+            Target::Channel(channel) => Some(irc::Target::Channel(channel.clone())),
+            Target::Nickname(nick) => Some(irc::Target::User(nick.clone())),
+            Target::Status | Target::List => None,
         }
     }
 }
@@ -0,0 +1,324 @@
+use core::ops::Range;
+
+use crossterm::style::{Attribute, Color, ContentStyle, StyledContent, Stylize};
+use serde::{Deserialize, Serialize};
+
+use crate::ui::text::Line;
+
+pub const BOLD: char = '\x02';
+pub const COLOR: char = '\x03';
+pub const HEX_COLOR: char = '\x04';
+pub const RESET: char = '\x0f';
+pub const MONOSPACE: char = '\x11';
+pub const REVERSE: char = '\x16';
+pub const ITALIC: char = '\x1d';
+pub const STRIKETHROUGH: char = '\x1e';
+pub const UNDERLINE: char = '\x1f';
+
+/// the colours for the codes 0 to 98. 99 means the default colour.
+const PALETTE: [u32; 99] = [
+    0xffffff, 0x000000, 0x00007f, 0x009300, 0xff0000, 0x7f0000, 0x9c009c, 0xfc7f00, 0xffff00,
+    0x00fc00, 0x009393, 0x00ffff, 0x0000fc, 0xff00ff, 0x7f7f7f, 0xd2d2d2, 0x470000, 0x472100,
+    0x474700, 0x324700, 0x004700, 0x00472c, 0x004747, 0x002747, 0x000047, 0x2e0047, 0x470047,
+    0x47002a, 0x740000, 0x743a00, 0x747400, 0x517400, 0x007400, 0x007449, 0x007474, 0x004074,
+    0x000074, 0x4b0074, 0x740074, 0x740045, 0xb50000, 0xb56300, 0xb5b500, 0x7db500, 0x00b500,
+    0x00b571, 0x00b5b5, 0x0063b5, 0x0000b5, 0x7500b5, 0xb500b5, 0xb5006b, 0xff0000, 0xff8c00,
+    0xffff00, 0xb2ff00, 0x00ff00, 0x00ffa0, 0x00ffff, 0x008cff, 0x0000ff, 0xa500ff, 0xff00ff,
+    0xff0098, 0xff5959, 0xffb459, 0xffff71, 0xcfff60, 0x6fff6f, 0x65ffc9, 0x6dffff, 0x59b4ff,
+    0x5959ff, 0xc459ff, 0xff66ff, 0xff59bc, 0xff9c9c, 0xffd39c, 0xffff9c, 0xe2ff9c, 0x9cff9c,
+    0x9cffdb, 0x9cffff, 0x9cd3ff, 0x9c9cff, 0xdc9cff, 0xff9cff, 0xff94d3, 0x000000, 0x131313,
+    0x282828, 0x363636, 0x4d4d4d, 0x656565, 0x818181, 0x9f9f9f, 0xbcbcbc, 0xe2e2e2, 0xffffff,
+];
+
+/// the closest 256 colour terminal colours to `PALETTE`. the first 16 use the terminal's own
+/// colours, so that they match its theme.
+const PALETTE_256: [u8; 99] = [
+    15, 0, 4, 2, 9, 1, 5, 3, 11, 10, 6, 14, 12, 13, 8, 7, 52, 94, 100, 58, 22, 29, 23, 24, 17, 54,
+    53, 89, 88, 130, 142, 64, 28, 35, 30, 25, 18, 91, 90, 125, 124, 166, 184, 106, 34, 49, 37, 33,
+    19, 129, 127, 161, 196, 208, 226, 154, 46, 86, 51, 75, 21, 171, 201, 198, 203, 215, 227, 191,
+    83, 122, 87, 111, 63, 177, 207, 205, 217, 223, 229, 193, 157, 158, 159, 153, 147, 183, 219,
+    212, 16, 233, 235, 237, 239, 241, 244, 247, 250, 254, 231,
+];
+
+/// how formatting codes in messages are shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Formatting {
+    /// show colours with the 256 colour palette, which most terminals support
+    #[default]
+    Ansi256,
+    /// show colours exactly, for terminals that support 24-bit colour
+    Truecolor,
+    /// remove all formatting
+    Strip,
+}
+
+impl Formatting {
+    fn palette_color(self, code: usize) -> Color {
+        match self {
+            Formatting::Truecolor => rgb(PALETTE[code]),
+            Formatting::Ansi256 | Formatting::Strip => Color::AnsiValue(PALETTE_256[code]),
+        }
+    }
+
+    fn hex_color(self, hex: u32) -> Color {
+        match self {
+            Formatting::Truecolor => rgb(hex),
+            Formatting::Ansi256 | Formatting::Strip => Color::AnsiValue(nearest_256(hex)),
+        }
+    }
+}
+
+fn rgb(hex: u32) -> Color {
+    let [_, r, g, b] = hex.to_be_bytes();
+    Color::Rgb { r, g, b }
+}
+
+/// the closest colour in the 6x6x6 colour cube of 256 colour terminals
+pub fn nearest_256(hex: u32) -> u8 {
+    let [_, r, g, b] = hex.to_be_bytes();
+    // the cube levels are 0, 95, 135, 175, 215 and 255
+    let level = |c: u8| {
+        if c < 48 {
+            0
+        } else {
+            (c.saturating_sub(35) / 40).min(5)
+        }
+    };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+/// reads up to `max` chars from the start of `s` that match `accept`
+fn take_while(s: &str, max: usize, accept: impl Fn(char) -> bool) -> &str {
+    let len = s
+        .chars()
+        .take(max)
+        .take_while(|c| accept(*c))
+        .map(char::len_utf8)
+        .sum();
+    &s[..len]
+}
+
+/// reads the `fg[,bg]` after a colour code, where each colour is up to `max` chars matching
+/// `accept`. returns the colours and the rest of the text.
+fn color_args(
+    s: &str,
+    max: usize,
+    accept: impl Fn(char) -> bool + Copy,
+) -> (Option<&str>, Option<&str>, &str) {
+    let fg = take_while(s, max, accept);
+    if fg.is_empty() {
+        return (None, None, s);
+    }
+    let rest = &s[fg.len()..];
+    // the comma is only part of the code if a background colour follows it
+    if let Some(after_comma) = rest.strip_prefix(',') {
+        let bg = take_while(after_comma, max, accept);
+        if !bg.is_empty() {
+            return (Some(fg), Some(bg), &after_comma[bg.len()..]);
+        }
+    }
+    (Some(fg), None, rest)
+}
+
+/// whether a char starts a formatting code
+fn is_code(c: char) -> bool {
+    matches!(
+        c,
+        BOLD | COLOR | HEX_COLOR | RESET | MONOSPACE | REVERSE | ITALIC | STRIKETHROUGH | UNDERLINE
+    )
+}
+
+/// applies the formatting code `code` to `style`, where `rest` is the text after the code and
+/// `base` is the style without any formatting. returns the length of the colour args that were
+/// part of the code.
+fn apply_code(
+    code: char,
+    rest: &str,
+    style: &mut ContentStyle,
+    base: ContentStyle,
+    formatting: Formatting,
+) -> usize {
+    match code {
+        BOLD => style.attributes.toggle(Attribute::Bold),
+        ITALIC => style.attributes.toggle(Attribute::Italic),
+        UNDERLINE => style.attributes.toggle(Attribute::Underlined),
+        STRIKETHROUGH => style.attributes.toggle(Attribute::CrossedOut),
+        REVERSE => style.attributes.toggle(Attribute::Reverse),
+        COLOR => {
+            let (fg, bg, after) = color_args(rest, 2, |c| c.is_ascii_digit());
+            // UNWRAP: the args are only digits. 99 is the default colour, and anything higher
+            // isn't a colour.
+            let color = |code: &str| {
+                let code = code.parse::<usize>().unwrap();
+                PALETTE.get(code).map(|_| formatting.palette_color(code))
+            };
+            match (fg, bg) {
+                // a colour code on its own resets the colours
+                (None, _) => {
+                    style.foreground_color = base.foreground_color;
+                    style.background_color = base.background_color;
+                }
+                (Some(fg), bg) => {
+                    style.foreground_color = color(fg).or(base.foreground_color);
+                    if let Some(bg) = bg {
+                        style.background_color = color(bg).or(base.background_color);
+                    }
+                }
+            }
+            return rest.len() - after.len();
+        }
+        HEX_COLOR => {
+            let (fg, bg, after) = color_args(rest, 6, |c| c.is_ascii_hexdigit());
+            // colours that aren't all 6 digits are ignored
+            let color = |s: &str| {
+                let hex = (s.len() == 6).then(|| u32::from_str_radix(s, 16).ok())?;
+                hex.map(|hex| formatting.hex_color(hex))
+            };
+            match (fg, bg) {
+                (None, _) => {
+                    style.foreground_color = base.foreground_color;
+                    style.background_color = base.background_color;
+                }
+                (Some(fg), bg) => {
+                    style.foreground_color = color(fg).or(base.foreground_color);
+                    if let Some(bg) = bg {
+                        style.background_color = color(bg).or(base.background_color);
+                    }
+                }
+            }
+            return rest.len() - after.len();
+        }
+        RESET => *style = base,
+        // NOTE: the terminal is already monospace
+        _ => {}
+    }
+    0
+}
+
+/// converts text with mIRC formatting codes into a line with the matching styles
+pub fn parse(text: &str, formatting: Formatting) -> Line<'static> {
+    if formatting == Formatting::Strip {
+        return Line::from(strip(text));
+    }
+
+    let base = ContentStyle::default();
+    let mut line = Line::default();
+    let mut style = base;
+    let mut span = String::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        if !is_code(c) {
+            span.push(c);
+            continue;
+        }
+        // the style changes here, so the text so far is finished
+        if !span.is_empty() {
+            line = line.push(StyledContent::new(style, core::mem::take(&mut span)));
+        }
+        let args_len = apply_code(c, rest, &mut style, base, formatting);
+        rest = &rest[args_len..];
+    }
+
+    if !span.is_empty() {
+        line = line.push(StyledContent::new(style, span));
+    }
+    line
+}
+
+/// the letter shown in place of a formatting code in the input line
+fn code_marker(code: char) -> char {
+    match code {
+        BOLD => 'B',
+        COLOR => 'C',
+        HEX_COLOR => 'H',
+        RESET => 'O',
+        MONOSPACE => 'M',
+        REVERSE => 'R',
+        ITALIC => 'I',
+        STRIKETHROUGH => 'S',
+        UNDERLINE => 'U',
+        c => c,
+    }
+}
+
+/// shows the `visible` part of text that is being typed with its formatting applied on top of
+/// `base`. each code is shown as a highlighted letter and colour args are shown unformatted, so
+/// that every char in the text takes up exactly one column and the cursor stays in the right
+/// place. codes before the visible part still apply to it.
+pub fn preview(text: &str, visible: Range<usize>, base: ContentStyle) -> Line<'static> {
+    let mut marker_style = base;
+    marker_style.attributes.set(Attribute::Reverse);
+    // the part of `text[start..end]` that is visible
+    let shown = |start: usize, end: usize| {
+        let clamp = |idx: usize| idx.clamp(visible.start, visible.end);
+        &text[clamp(start)..clamp(end)]
+    };
+
+    let mut line = Line::default();
+    let mut style = base;
+    let mut span = String::new();
+    let mut idx = 0;
+
+    while idx < visible.end
+        && let Some(c) = text[idx..].chars().next()
+    {
+        let start = idx;
+        idx += c.len_utf8();
+        if !is_code(c) {
+            span.push_str(shown(start, idx));
+            continue;
+        }
+        if !span.is_empty() {
+            line = line.push(StyledContent::new(style, core::mem::take(&mut span)));
+        }
+        if visible.contains(&start) {
+            line = line.push(StyledContent::new(marker_style, code_marker(c)));
+        }
+        let args_len = apply_code(c, &text[idx..], &mut style, base, Formatting::Ansi256);
+        let args = shown(idx, idx + args_len);
+        if !args.is_empty() {
+            line = line.push(StyledContent::new(base, args.to_string()));
+        }
+        idx += args_len;
+    }
+
+    if !span.is_empty() {
+        line = line.push(StyledContent::new(style, span));
+    }
+    line
+}
+
+/// a prompt for picking a colour code, showing the basic colours on themselves
+pub fn color_picker() -> Line<'static> {
+    let mut line = Line::default().push_unstyled("colour: ");
+    for (code, color) in PALETTE.iter().take(16).enumerate() {
+        let bg = Formatting::Ansi256.palette_color(code);
+        // black text on the light colours and white text on the dark ones
+        let [_, r, g, b] = color.to_be_bytes();
+        let light = u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114 > 128_000;
+        let fg = if light { Color::Black } else { Color::White };
+        line = line
+            .push(format!("{:02}", code).with(fg).on(bg))
+            .push_unstyled(" ");
+    }
+    line.push_unstyled("99 default, fg,bg")
+}
+
+/// removes all mIRC formatting codes from text
+pub fn strip(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            COLOR => rest = color_args(rest, 2, |c| c.is_ascii_digit()).2,
+            HEX_COLOR => rest = color_args(rest, 6, |c| c.is_ascii_hexdigit()).2,
+            c if is_code(c) => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -1,6 +1,10 @@
+use core::ops::Range;
+
 use log::*;
 use unicode_segmentation::UnicodeSegmentation as _;
 
+use crate::ui::format;
+
 #[derive(Debug, Clone, Default)]
 pub struct InputBuffer {
     /// the full text in the input buffer
@@ -26,6 +30,11 @@ impl InputBuffer {
         }
     }
 
+    /// the full text in the input buffer
+    pub fn as_str(&self) -> &str {
+        self.buffer.as_str()
+    }
+
     /// finish editing the current contents of the input buffer, returning them, and resetting the
     /// buffer.
     pub fn finish(&mut self) -> String {
@@ -81,16 +90,34 @@ impl InputBuffer {
         }
     }
 
+    /// whether the cursor is in the args of a colour code, like `\x0304,0`, so that the colour
+    /// picker should be shown
+    pub fn in_color_code(&self) -> bool {
+        let before = &self.buffer[..self.cursor_idx];
+        let Some(code_idx) = before.rfind(format::COLOR) else {
+            return false;
+        };
+        let args = &before[code_idx + format::COLOR.len_utf8()..];
+        let (fg, bg) = args.split_once(',').unwrap_or((args, ""));
+        let is_code = |s: &str| s.len() <= 2 && s.chars().all(|c| c.is_ascii_digit());
+        // the comma is only part of the code after a foreground colour
+        is_code(fg) && is_code(bg) && !(fg.is_empty() && args.contains(','))
+    }
+
     pub fn char_len(&self) -> usize {
         self.buffer.chars().count()
     }
 
     // TODO: this is likely slightly incorrect in the face of different widths for graphemes
-    pub fn get_visible_area(&self, width: u16) -> (&str, usize) {
+    /// the byte range of the buffer to show in `width` columns, and the column of the cursor within
+    /// it.
+    /// every grapheme is counted as one column. formatting codes are always their own grapheme,
+    /// and `format::preview` shows each of them as one letter, so they take one column too.
+    pub fn get_visible_area(&self, width: u16) -> (Range<usize>, usize) {
         // we cannot meaningfully lay out any real text in 0 width, and really the cursor doesn't
         // fit at position 0 either
         if width == 0 {
-            return ("", 0);
+            return (0..0, 0);
         }
 
         // try to place the cursor as close to the middle of the returned string as possible.
@@ -163,11 +190,6 @@ impl InputBuffer {
             cursor_pos
         );
 
-        (
-            self.buffer
-                .get(range.clone())
-                .expect("start and end should be clamped"),
-            cursor_pos,
-        )
+        (range, cursor_pos)
     }
 }
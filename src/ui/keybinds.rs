@@ -1,6 +1,8 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use log::*;
 
+use crate::ui::format;
+
 pub enum Action {
     /// terminal resized
     Resize,
@@ -27,6 +29,13 @@ pub enum Action {
 
     PreviousWindow,
     NextWindow,
+
+    /// change which column the LIST window is sorted by
+    CycleSort,
+    /// join the channel that we were most recently invited to
+    AcceptInvite,
+    /// insert a formatting code, like bold or colour, into the input buffer
+    InsertFormat(char),
 }
 
 impl Action {
@@ -45,7 +54,19 @@ impl Action {
 
         trace!("mods {:?} code {:?}", modifiers, code);
 
-        if modifiers.contains(KeyModifiers::CONTROL) {
+        if modifiers.contains(KeyModifiers::ALT) {
+            match code {
+                KeyCode::Char(KEY_BOLD_BASE) => Some(Action::InsertFormat(format::BOLD)),
+                KeyCode::Char(KEY_ITALIC_BASE) => Some(Action::InsertFormat(format::ITALIC)),
+                KeyCode::Char(KEY_STRIKETHROUGH_BASE) => {
+                    Some(Action::InsertFormat(format::STRIKETHROUGH))
+                }
+                // NOTE: terminals often report AltGr as Ctrl+Alt, so other characters are typed as
+                // usual, like `@` and `{` on many European layouts
+                KeyCode::Char(c) => Some(Action::Type(c)),
+                _ => None,
+            }
+        } else if modifiers.contains(KeyModifiers::CONTROL) {
             match code {
                 KeyCode::Char(KEY_PREV_LINE_BASE) => Some(Action::PreviousLine),
                 KeyCode::Char(KEY_NEXT_LINE_BASE) => Some(Action::NextLine),
@@ -56,6 +77,12 @@ impl Action {
                 KeyCode::Char(KEY_DELETE_BASE) => Some(Action::Delete),
                 KeyCode::Char(KEY_PREV_WINDOW_BASE) => Some(Action::PreviousWindow),
                 KeyCode::Char(KEY_NEXT_WINDOW_BASE) => Some(Action::NextWindow),
+                KeyCode::Char(KEY_CYCLE_SORT_BASE) => Some(Action::CycleSort),
+                KeyCode::Char(KEY_ACCEPT_INVITE_BASE) => Some(Action::AcceptInvite),
+                KeyCode::Char(KEY_COLOR_BASE) => Some(Action::InsertFormat(format::COLOR)),
+                KeyCode::Char(KEY_UNDERLINE_BASE) => Some(Action::InsertFormat(format::UNDERLINE)),
+                KeyCode::Char(KEY_REVERSE_BASE) => Some(Action::InsertFormat(format::REVERSE)),
+                KeyCode::Char(KEY_RESET_BASE) => Some(Action::InsertFormat(format::RESET)),
                 _ => None,
             }
         } else {
@@ -80,6 +107,18 @@ const KEY_LAST_CHAR_BASE: char = 'e';
 const KEY_DELETE_BASE: char = 'd';
 const KEY_PREV_WINDOW_BASE: char = 'q';
 const KEY_NEXT_WINDOW_BASE: char = 'j';
+const KEY_CYCLE_SORT_BASE: char = 't';
+const KEY_ACCEPT_INVITE_BASE: char = 'y';
+
+// formatting, like other clients where possible. Ctrl-B and Ctrl-I are already used for moving
+// the cursor and tab, so bold and italic use Alt instead.
+const KEY_COLOR_BASE: char = 'k';
+const KEY_UNDERLINE_BASE: char = 'u';
+const KEY_REVERSE_BASE: char = 'r';
+const KEY_RESET_BASE: char = 'o';
+const KEY_BOLD_BASE: char = 'b';
+const KEY_ITALIC_BASE: char = 'i';
+const KEY_STRIKETHROUGH_BASE: char = 's';
 
 /*
 
@@ -126,3 +165,29 @@ const KEY_NEXT_WINDOW_BASE: char = 'j';
             }
 
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        Action::create(KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn alt_characters() {
+        let altgr = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        for c in ['@', '{', '[', '|', 'q'] {
+            assert!(matches!(action(KeyCode::Char(c), altgr), Some(Action::Type(t)) if t == c));
+            assert!(matches!(
+                action(KeyCode::Char(c), KeyModifiers::ALT),
+                Some(Action::Type(t)) if t == c
+            ));
+        }
+        assert!(matches!(
+            action(KeyCode::Char(KEY_BOLD_BASE), KeyModifiers::ALT),
+            Some(Action::InsertFormat(format::BOLD))
+        ));
+        assert!(action(KeyCode::Enter, KeyModifiers::ALT).is_none());
+    }
+}
@@ -1,6 +1,10 @@
 mod input_buffer;
 
+pub mod format;
 pub mod keybinds;
 pub mod layout;
+pub mod nick_colors;
+pub mod template;
 pub mod term;
 pub mod text;
+pub mod theme;
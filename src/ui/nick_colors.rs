@@ -0,0 +1,226 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::OnceLock,
+};
+
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::{casemap::Casemapping, ui::format};
+
+/// the default colours for nicks on 16 colour terminals, leaving out the ones that are hard to
+/// read on a dark or light background
+const DEFAULT_PALETTE_16: &[Color] = &[
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+];
+
+/// the default colours for nicks on 256 colour and truecolor terminals
+const DEFAULT_PALETTE_256: &[u8] = &[
+    160, 166, 172, 178, 142, 106, 70, 34, 36, 37, 38, 32, 26, 62, 98, 134, 170, 169, 168, 203, 209,
+    215, 149, 114, 79, 80, 75, 111, 141, 177, 212,
+];
+
+/// the colours of the 16 terminal colours in xterm, for picking the closest one to an RGB colour
+const COLORS_16: [(Color, u32); 16] = [
+    (Color::Black, 0x000000),
+    (Color::DarkRed, 0xcd0000),
+    (Color::DarkGreen, 0x00cd00),
+    (Color::DarkYellow, 0xcdcd00),
+    (Color::DarkBlue, 0x0000ee),
+    (Color::DarkMagenta, 0xcd00cd),
+    (Color::DarkCyan, 0x00cdcd),
+    (Color::Grey, 0xe5e5e5),
+    (Color::DarkGrey, 0x7f7f7f),
+    (Color::Red, 0xff0000),
+    (Color::Green, 0x00ff00),
+    (Color::Yellow, 0xffff00),
+    (Color::Blue, 0x5c5cff),
+    (Color::Magenta, 0xff00ff),
+    (Color::Cyan, 0x00ffff),
+    (Color::White, 0xffffff),
+];
+
+/// how many colours the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorDepth {
+    #[serde(rename = "16")]
+    Ansi16,
+    #[serde(rename = "256")]
+    Ansi256,
+    #[serde(rename = "truecolor")]
+    Truecolor,
+}
+
+impl ColorDepth {
+    /// guesses the colour depth from the `COLORTERM` and `TERM` environment variables
+    pub fn detect() -> Self {
+        static DETECTED: OnceLock<ColorDepth> = OnceLock::new();
+        *DETECTED.get_or_init(Self::detect_uncached)
+    }
+
+    fn detect_uncached() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if matches!(colorterm.as_str(), "truecolor" | "24bit") {
+            ColorDepth::Truecolor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+/// a colour in the config, which is either a 256 colour palette index, a name like `dark_cyan`,
+/// or `#rrggbb`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColorSpec {
+    Ansi(u8),
+    Name(String),
+}
+
+impl ColorSpec {
+    /// the colour to show on a terminal with `depth`, or None if the colour is invalid
+    pub fn to_color(&self, depth: ColorDepth) -> Option<Color> {
+        let rgb = match self {
+            ColorSpec::Ansi(n) if depth != ColorDepth::Ansi16 || *n < 16 => {
+                return Some(Color::AnsiValue(*n));
+            }
+            ColorSpec::Ansi(n) => ansi_to_rgb(*n),
+            ColorSpec::Name(name) => match name.strip_prefix('#') {
+                Some(hex) if hex.len() == 6 => u32::from_str_radix(hex, 16).ok()?,
+                Some(_) => return None,
+                None => return Color::try_from(name.as_str()).ok(),
+            },
+        };
+        Some(match depth {
+            ColorDepth::Truecolor => {
+                let [_, r, g, b] = rgb.to_be_bytes();
+                Color::Rgb { r, g, b }
+            }
+            ColorDepth::Ansi256 => Color::AnsiValue(format::nearest_256(rgb)),
+            ColorDepth::Ansi16 => nearest_16(rgb),
+        })
+    }
+}
+
+/// the RGB colour of a colour from the 256 colour palette, above the first 16
+fn ansi_to_rgb(n: u8) -> u32 {
+    match n {
+        16..=231 => {
+            let n = n - 16;
+            let level = |c: u8| if c == 0 { 0 } else { 55 + 40 * u32::from(c) };
+            (level(n / 36) << 16) | (level(n / 6 % 6) << 8) | level(n % 6)
+        }
+        232..=255 => {
+            let grey = 8 + 10 * u32::from(n - 232);
+            (grey << 16) | (grey << 8) | grey
+        }
+        n => COLORS_16[usize::from(n)].1,
+    }
+}
+
+fn nearest_16(rgb: u32) -> Color {
+    let distance = |other: u32| {
+        let [_, r1, g1, b1] = rgb.to_be_bytes();
+        let [_, r2, g2, b2] = other.to_be_bytes();
+        let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+        d(r1, r2) + d(g1, g2) + d(b1, b2)
+    };
+    // UNWRAP: the list isn't empty
+    COLORS_16
+        .iter()
+        .min_by_key(|(_, other)| distance(*other))
+        .unwrap()
+        .0
+}
+
+/// the casemapping that nicks are lowercased with before they're hashed, so that the same user
+/// always has the same colour
+// NOTE: this is used even if the server's CASEMAPPING is ascii. nicks that are only equal with
+// rfc1459 sharing a colour is harmless.
+const CASEMAPPING: Casemapping = Casemapping::Rfc1459;
+
+/// a hash of the nick that is the same between runs, unlike the std hasher
+fn fnv1a(s: &str) -> u32 {
+    s.bytes().fold(0x811c9dc5, |hash, b| {
+        (hash ^ u32::from(b)).wrapping_mul(0x01000193)
+    })
+}
+
+/// how nicks are coloured
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NickColors {
+    /// the colours that nicks are picked from by a hash of the nick. the default depends on the
+    /// colour depth.
+    pub palette: Vec<ColorSpec>,
+    /// colours for specific nicks, instead of one from the palette
+    pub overrides: BTreeMap<String, ColorSpec>,
+    /// how many colours the terminal supports, guessed from the environment if this isn't set
+    pub depth: Option<ColorDepth>,
+    #[serde(skip)]
+    resolved: Resolved,
+}
+
+/// the palette and overrides as terminal colours, worked out once when the config is loaded
+#[derive(Debug, Default)]
+struct Resolved {
+    palette: Vec<Color>,
+    /// from the casemapped nick to its colour
+    overrides: HashMap<String, Color>,
+}
+
+impl NickColors {
+    /// works out the colours of the palette and overrides for the colour depth. colours that are
+    /// invalid are left out.
+    pub fn resolve(&mut self) {
+        let depth = self.depth.unwrap_or_else(ColorDepth::detect);
+        let mut palette = self
+            .palette
+            .iter()
+            .filter_map(|color| color.to_color(depth))
+            .collect::<Vec<_>>();
+        if palette.is_empty() {
+            palette = match depth {
+                ColorDepth::Ansi16 => DEFAULT_PALETTE_16.to_vec(),
+                _ => DEFAULT_PALETTE_256
+                    .iter()
+                    .map(|n| Color::AnsiValue(*n))
+                    .collect(),
+            };
+        }
+        let overrides = self
+            .overrides
+            .iter()
+            .filter_map(|(nick, color)| Some((CASEMAPPING.lower(nick), color.to_color(depth)?)))
+            .collect();
+        self.resolved = Resolved { palette, overrides };
+    }
+
+    /// the colour of someone else's nick
+    pub fn color(&self, nick: &str) -> Color {
+        let key = CASEMAPPING.lower(nick);
+        if let Some(color) = self.resolved.overrides.get(&key) {
+            return *color;
+        }
+        let palette = &self.resolved.palette;
+        // NOTE: the palette is only empty if the colours were never resolved
+        if palette.is_empty() {
+            return Color::Reset;
+        }
+        palette[fnv1a(key.as_str()) as usize % palette.len()]
+    }
+}
@@ -0,0 +1,185 @@
+use core::fmt::Write as _;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ui::{text::Line, theme::Role};
+
+const DEFAULT_TIME_FORMAT: &str = "%H:%M:%S";
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// the templates that lines are currently made with
+static CURRENT: LazyLock<RwLock<Arc<Templates>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(Templates::default())));
+
+/// a kind of line that has its own template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// any line that doesn't have its own template, which is usually just the time
+    Line,
+    /// `{nick}` and `{text}`
+    Message,
+    /// `{nick}` and `{text}`
+    Action,
+    /// `{nick}` and `{text}`
+    Notice,
+    /// `{nick}` and `{channel}`
+    Join,
+    /// `{nick}`, `{channel}` and `{reason}`
+    Part,
+    /// `{nick}` and `{reason}`
+    Quit,
+    /// `{nick}`, `{kicker}`, `{channel}` and `{reason}`
+    Kick,
+    /// `{date}`
+    DayChanged,
+}
+
+impl Kind {
+    /// the role that styles the text of the template around the fields
+    fn role(self) -> Option<Role> {
+        match self {
+            Kind::Line | Kind::Message => None,
+            Kind::Action => Some(Role::Action),
+            Kind::Notice => Some(Role::Notice),
+            Kind::Join => Some(Role::Join),
+            Kind::Part => Some(Role::Part),
+            Kind::Quit => Some(Role::Quit),
+            Kind::Kick => Some(Role::Kick),
+            Kind::DayChanged => Some(Role::Info),
+        }
+    }
+}
+
+/// how lines are laid out. fields are written like `{nick}`, and `{time}` can be used in every
+/// template. the `{reason}` of parts and kicks starts with `: ` since it's optional.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Templates {
+    /// the format of `{time}`, using chrono's strftime syntax
+    pub time_format: String,
+    /// the format of `{date}` in day change lines
+    pub date_format: String,
+    pub line: String,
+    pub message: String,
+    pub action: String,
+    pub notice: String,
+    pub join: String,
+    pub part: String,
+    pub quit: String,
+    pub kick: String,
+    /// shown before the first line of a new day in each window
+    pub day_changed: String,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self {
+            time_format: String::from(DEFAULT_TIME_FORMAT),
+            date_format: String::from(DEFAULT_DATE_FORMAT),
+            line: String::from("[{time}]"),
+            message: String::from("[{time}]<{nick}>{text}"),
+            action: String::from("[{time}]* {nick} {text}"),
+            notice: String::from("[{time}]<{nick}>NOTICE {text}"),
+            join: String::from("[{time}]{nick} joined {channel}"),
+            part: String::from("[{time}]{nick} left{reason}"),
+            quit: String::from("[{time}]{nick} quit: {reason}"),
+            kick: String::from("[{time}]{nick} was kicked by {kicker}{reason}"),
+            day_changed: String::from("--- Day changed to {date} ---"),
+        }
+    }
+}
+
+impl Templates {
+    fn get(&self, kind: Kind) -> &str {
+        match kind {
+            Kind::Line => &self.line,
+            Kind::Message => &self.message,
+            Kind::Action => &self.action,
+            Kind::Notice => &self.notice,
+            Kind::Join => &self.join,
+            Kind::Part => &self.part,
+            Kind::Quit => &self.quit,
+            Kind::Kick => &self.kick,
+            Kind::DayChanged => &self.day_changed,
+        }
+    }
+}
+
+pub fn current() -> Arc<Templates> {
+    // UNWRAP: the lock is never held while panicking
+    CURRENT.read().unwrap().clone()
+}
+
+pub fn set(templates: Templates) {
+    // UNWRAP: the lock is never held while panicking
+    *CURRENT.write().unwrap() = Arc::new(templates);
+}
+
+/// makes a line from the template for `kind`. fields that aren't given and unknown fields are left
+/// as they are, and each field is only filled in the first time that it appears.
+pub fn render(
+    kind: Kind,
+    time: DateTime<Utc>,
+    fields: Vec<(&str, Line<'static>)>,
+) -> Line<'static> {
+    let templates = current();
+    let role = kind.role();
+    let mut fields = fields
+        .into_iter()
+        .map(|(name, line)| (name, Some(line)))
+        .collect::<Vec<_>>();
+    let mut line = Line::default().with_time(time);
+
+    let push_text = |line: Line<'static>, text: &str| match (text, role) {
+        ("", _) => line,
+        (text, Some(role)) => line.push_themed(role, text),
+        (text, None) => line.push_unstyled(text),
+    };
+
+    let mut rest = templates.get(kind);
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 1..start + len];
+        line = push_text(line, &rest[..start]);
+        if name == "time" {
+            let local = time.with_timezone(&Local);
+            let mut text = String::new();
+            // NOTE: chrono fails to display invalid format strings instead of rejecting them
+            if write!(text, "{}", local.format(&templates.time_format)).is_err() {
+                text = local.format(DEFAULT_TIME_FORMAT).to_string();
+            }
+            line = line.push_themed(Role::Timestamp, text);
+        } else if let Some(field) = fields
+            .iter_mut()
+            .find(|(field, _)| *field == name)
+            .and_then(|(_, line)| line.take())
+        {
+            line = line.join(field);
+        } else {
+            line = push_text(line, &rest[start..=start + len]);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    push_text(line, rest)
+}
+
+/// the line shown before the first line of a new day. its time is the start of the day, so that
+/// lines from earlier in the day are never put before it.
+pub fn day_changed(date: NaiveDate) -> Line<'static> {
+    // NOTE: midnight doesn't exist in time zones where daylight saving starts at midnight
+    let time = date
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(|| date.and_time(NaiveTime::MIN).and_utc());
+    let mut text = String::new();
+    if write!(text, "{}", date.format(&current().date_format)).is_err() {
+        text = date.format(DEFAULT_DATE_FORMAT).to_string();
+    }
+    render(Kind::DayChanged, time, vec![("date", Line::from(text))])
+}
@@ -1,24 +1,20 @@
 use core::time::Duration;
 use std::{collections::VecDeque, io};
 
-use crossterm::{
-    cursor, event,
-    event::Event,
-    execute,
-    style::{Color, Stylize},
-    terminal,
-};
+use crossterm::{cursor, event, event::Event, execute, terminal};
 use eyre::bail;
 use log::*;
 
 use crate::{
     state::StatusInfo,
     ui::{
+        format,
         input_buffer::InputBuffer,
         keybinds::Action,
         layout::{Layout, Rect},
         text,
         text::{DrawTextConfig, Line, WrapMode},
+        theme::{self, Role},
     },
     util::unicode_width,
 };
@@ -48,11 +44,23 @@ impl<'a> TerminalUi<'a> {
         })
     }
 
+    /// a UI that draws to `writer` without taking over the terminal, for tests
+    #[cfg(test)]
+    pub fn headless<W: io::Write + 'a + Send>(layout: Layout, writer: W) -> Self {
+        Self {
+            terminal: Box::new(writer),
+            layout,
+            history: VecDeque::new(),
+            scrollback: 0,
+            input_buffer: InputBuffer::default(),
+        }
+    }
+
     pub fn error(&mut self, msg: impl Into<String>) -> eyre::Result<()> {
         let msg = msg.into();
         error!("{}", msg);
         self.history
-            .push_back(Line::default().push(format!("ERROR: {}", msg).red()));
+            .push_back(Line::default().push_themed(Role::Error, format!("ERROR: {}", msg)));
         Ok(())
     }
 
@@ -90,16 +98,40 @@ impl<'a> TerminalUi<'a> {
         &mut self,
         status: &StatusInfo,
         lines: impl DoubleEndedIterator<Item = &'lines Line<'lines>>,
+    ) -> eyre::Result<()> {
+        self.render_with(status, |ui, main_rect| ui.draw_main(main_rect, lines))
+    }
+
+    /// renders rows from the top of the main section, without wrapping or scrollback. used for
+    /// windows like LIST that pick which rows to show themselves.
+    pub fn render_rows(&mut self, status: &StatusInfo, rows: &[Line]) -> eyre::Result<()> {
+        self.render_with(status, |ui, main_rect| ui.draw_rows(main_rect, rows))
+    }
+
+    /// the size of the main section, as `(width, height)`
+    pub fn main_size(&self) -> eyre::Result<(u16, u16)> {
+        let layout = self.layout.calc(terminal::size()?);
+        let [_, main_rect, _, _] = layout.as_slice() else {
+            bail!("incorrect number of components in split layout");
+        };
+        Ok((main_rect.width, main_rect.height))
+    }
+
+    fn render_with(
+        &mut self,
+        status: &StatusInfo,
+        draw_main: impl FnOnce(&mut Self, Rect) -> eyre::Result<()>,
     ) -> eyre::Result<()> {
         let layout = self.layout.calc(terminal::size()?);
-        let [main_rect, status_rect, input_rect] = layout.as_slice() else {
+        let [topic_rect, main_rect, status_rect, input_rect] = layout.as_slice() else {
             bail!("incorrect number of components in split layout");
         };
 
         // TODO: save and restore cursor pos?
         execute!(self.terminal, terminal::Clear(terminal::ClearType::All))?;
 
-        self.draw_main(*main_rect, lines)?;
+        self.draw_topic(status, *topic_rect)?;
+        draw_main(self, *main_rect)?;
         self.draw_status(status, *status_rect)?;
         self.draw_input(input_rect)?;
 
@@ -153,36 +185,78 @@ impl<'a> TerminalUi<'a> {
         Ok(())
     }
 
+    fn draw_rows(&mut self, main_rect: Rect, rows: &[Line]) -> eyre::Result<()> {
+        for (y, row) in (main_rect.y..main_rect.y + main_rect.height).zip(rows) {
+            text::draw_text(
+                &mut self.terminal,
+                Rect {
+                    y,
+                    height: 1,
+                    ..main_rect
+                },
+                row,
+                DrawTextConfig {
+                    wrap: WrapMode::Truncate,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_topic(&mut self, status: &StatusInfo, topic_rect: Rect) -> eyre::Result<()> {
+        let topic = status.topic.as_deref().unwrap_or("");
+        let pad = usize::from(topic_rect.width).saturating_sub(unicode_width::display_width(topic));
+        let topic_line = Line::default()
+            .push_themed(Role::TopicBar, topic)
+            .push_themed(Role::TopicBar, " ".repeat(pad));
+
+        text::draw_text(
+            &mut self.terminal,
+            topic_rect,
+            &topic_line,
+            DrawTextConfig {
+                // the topic bar is only one line, so long topics are cut off
+                wrap: WrapMode::Truncate,
+            },
+        )?;
+
+        Ok(())
+    }
+
     fn draw_status(&mut self, status: &StatusInfo, status_rect: Rect) -> eyre::Result<()> {
-        const STATUS_BG: Color = Color::Rgb {
-            r: 0x61,
-            g: 0x2B,
-            b: 0x5B,
-        };
-        const ADDR: Color = Color::Rgb {
-            r: 0xF4,
-            g: 0x5B,
-            b: 0x46,
-        };
-        const NICK: Color = Color::Rgb {
-            r: 0xFC,
-            g: 0x91,
-            b: 0x00,
-        };
+        // show the colours to pick from while typing a colour code
+        if self.input_buffer.in_color_code() {
+            let picker = format::color_picker();
+            let pad = usize::from(status_rect.width)
+                .saturating_sub(unicode_width::display_width(picker.fmt_unstyled().as_str()));
+            text::draw_text(
+                &mut self.terminal,
+                status_rect,
+                &picker.push_themed(Role::StatusBar, " ".repeat(pad)),
+                DrawTextConfig {
+                    wrap: WrapMode::Truncate,
+                },
+            )?;
+            return Ok(());
+        }
 
-        let mut status_line = Line::default().push(status.addr.clone().with(ADDR).on(STATUS_BG));
+        let mut status_line = Line::default().push_themed(Role::StatusAddr, &status.addr);
         if !status.registered {
-            status_line = status_line.push(" *REGISTRATION*".on(STATUS_BG));
+            status_line = status_line.push_themed(Role::StatusBar, " *REGISTRATION*");
+        }
+        status_line = status_line.push_themed(Role::StatusNick, format!(" {}", status.nick));
+        if status.away {
+            status_line = status_line.push_themed(Role::StatusBar, " (away)");
         }
-        status_line = status_line
-            .push(format!(" {}", status.nick).with(NICK).on(STATUS_BG))
-            .push(format!(" - {}", status.target.as_str()).on(STATUS_BG));
+        status_line =
+            status_line.push_themed(Role::StatusBar, format!(" - {}", status.target.as_str()));
 
         let pad = usize::from(status_rect.width).saturating_sub(unicode_width::display_width(
             status_line.fmt_unstyled().as_str(),
         ));
 
-        status_line = status_line.push(" ".repeat(pad).on(STATUS_BG));
+        status_line = status_line.push_themed(Role::StatusBar, " ".repeat(pad));
 
         text::draw_text(
             &mut self.terminal,
@@ -202,8 +276,8 @@ impl<'a> TerminalUi<'a> {
         const INPUT_BUFFER_PAD: u16 = 1;
 
         let input_width = input_rect.width.saturating_sub(INPUT_BUFFER_PAD);
-        let (input_text, cursor_col) = self.input_buffer.get_visible_area(input_width);
-        trace!("input_text {:?}, cursor_col {}", input_text, cursor_col);
+        let (visible, cursor_col) = self.input_buffer.get_visible_area(input_width);
+        trace!("visible {:?}, cursor_col {}", visible, cursor_col);
 
         let config = DrawTextConfig {
             // note: this does not matter, we always send exactly enough characters
@@ -214,7 +288,8 @@ impl<'a> TerminalUi<'a> {
         text::draw_text(
             &mut self.terminal,
             *input_rect,
-            &Line::default().push(" ".repeat(usize::from(input_rect.width)).on_blue()),
+            &Line::default()
+                .push_themed(Role::InputLine, " ".repeat(usize::from(input_rect.width))),
             config,
         )?;
 
@@ -222,7 +297,11 @@ impl<'a> TerminalUi<'a> {
         text::draw_text(
             &mut self.terminal,
             *input_rect,
-            &Line::default().push(input_text.white().on_blue()),
+            &format::preview(
+                self.input_buffer.as_str(),
+                visible,
+                theme::current().get(Role::InputLine),
+            ),
             config,
         )?;
 
@@ -2,8 +2,9 @@ use core::{
     fmt::{Debug, Display, Write as _},
     num::NonZeroU16,
 };
-use std::io;
+use std::{borrow::Cow, io};
 
+use chrono::{DateTime, Utc};
 use crossterm::{
     cursor, execute,
     style::{ContentStyle, StyledContent},
@@ -11,36 +12,137 @@ use crossterm::{
 use log::*;
 use unicode_segmentation::UnicodeSegmentation;
 
-use crate::{ui::layout::Rect, util::unicode_width};
+use crate::{
+    ui::{
+        layout::Rect,
+        theme::{self, Role},
+    },
+    util::unicode_width,
+};
+
+/// makes text safe to write to the terminal, by showing control characters in caret notation
+/// (like `^[` for escape) instead of letting them run. almost all text comes from the server, so a
+/// raw escape could clear the screen, change the window title or write to the clipboard.
+///
+/// C1 controls are shown as the escape sequence that they are equivalent to, so `\u{9b}` (CSI)
+/// is shown as `^[[`. tabs are shown as a space.
+pub fn sanitize(text: &str) -> Cow<'_, str> {
+    let is_control = |c: char| c.is_control();
+    if !text.contains(is_control) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '\t' => out.push(' '),
+            // C0 controls and DEL
+            '\0'..='\x1f' | '\x7f' => {
+                out.push('^');
+                out.push(char::from(c as u8 ^ 0x40));
+            }
+            // C1 controls
+            '\u{80}'..='\u{9f}' => {
+                out.push_str("^[");
+                out.push(char::from(c as u8 - 0x40));
+            }
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
 
+/// a line of styled text. all of the text is passed through `sanitize`, so it can't contain
+/// control characters.
 #[derive(Default, Debug)]
 pub struct Line<'a> {
     content: Vec<DynStyledContentWrapper<'a>>,
+    /// when the event that the line shows happened, which isn't set for lines like errors
+    time: Option<DateTime<Utc>>,
+    /// the id that the server gave the message that the line shows
+    msgid: Option<String>,
+    /// the label of our own message while the server hasn't echoed it back, which dims the line
+    label: Option<String>,
 }
 
 impl<'a> Line<'a> {
     pub fn push<D: Display>(mut self, styled: StyledContent<D>) -> Self {
         self.content.push(DynStyledContentWrapper {
             style: *styled.style(),
-            content: Box::new(styled.content().to_string()),
+            role: None,
+            content: Box::new(sanitize(&styled.content().to_string()).into_owned()),
+        });
+        self
+    }
+
+    /// pushes content styled by the theme. the style is looked up when the line is drawn, so the
+    /// line changes if the theme does.
+    pub fn push_themed<D: Display>(mut self, role: Role, content: D) -> Self {
+        self.content.push(DynStyledContentWrapper {
+            style: ContentStyle::default(),
+            role: Some(role),
+            content: Box::new(sanitize(&content.to_string()).into_owned()),
         });
         self
     }
 
     pub fn push_unstyled<S: AsRef<str>>(mut self, content: S) -> Self {
-        let content = content.as_ref().replace('\r', "").replace('\n', "");
         self.content.push(DynStyledContentWrapper {
             style: ContentStyle::default(),
-            content: Box::new(content),
+            role: None,
+            content: Box::new(sanitize(content.as_ref()).into_owned()),
         });
         self
     }
 
     pub fn join<'other: 'a>(mut self, other: Line<'other>) -> Self {
         self.content.extend(other.content);
+        self.time = self.time.or(other.time);
+        self.msgid = self.msgid.or(other.msgid);
+        self.label = self.label.or(other.label);
         self
     }
 
+    pub fn with_time(mut self, time: DateTime<Utc>) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        self.time
+    }
+
+    pub fn with_msgid(mut self, msgid: impl Into<String>) -> Self {
+        self.msgid = Some(msgid.into());
+        self
+    }
+
+    pub fn msgid(&self) -> Option<&str> {
+        self.msgid.as_deref()
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn without_label(mut self) -> Self {
+        self.label = None;
+        self
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// the style of a span, which is dimmed while the line is waiting to be echoed
+    fn span_style(&self, span: &DynStyledContentWrapper) -> ContentStyle {
+        match self.label {
+            Some(_) => theme::current().get(Role::Dim),
+            None => span.style(),
+        }
+    }
+
     pub fn into_iter(self) -> impl IntoIterator<Item = DynStyledContentWrapper<'a>> {
         self.content.into_iter()
     }
@@ -84,7 +186,7 @@ impl<'a> Line<'a> {
                         .collect::<String>();
                     remaining_width = remaining_width.saturating_sub(truncated.len() as u16);
 
-                    ret.push(StyledContent::new(span.style, truncated));
+                    ret.push(StyledContent::new(self.span_style(span), truncated));
                 }
                 // this truncates to one line always
                 vec![ret]
@@ -148,7 +250,8 @@ impl<'a> Line<'a> {
                     let unstyled = span.content.to_string();
                     let mut words = unstyled.split_word_bounds().peekable();
                     while let Some(word) = words.next() {
-                        handle_word(&mut lines, width, &mut remaining_width, word, span.style);
+                        let style = self.span_style(span);
+                        handle_word(&mut lines, width, &mut remaining_width, word, style);
                     }
                 }
 
@@ -179,11 +282,19 @@ impl<'a, A: Into<DynStyledContentWrapper<'a>>> Extend<A> for Line<'a> {
 
 impl<'a> From<String> for Line<'a> {
     fn from(value: String) -> Self {
+        let value = match sanitize(&value) {
+            Cow::Borrowed(_) => value,
+            Cow::Owned(sanitized) => sanitized,
+        };
         Self {
             content: vec![DynStyledContentWrapper {
                 style: ContentStyle::default(),
+                role: None,
                 content: Box::new(value),
             }],
+            time: None,
+            msgid: None,
+            label: None,
         }
     }
 }
@@ -193,8 +304,12 @@ impl<'a> From<&'a str> for Line<'a> {
         Self {
             content: vec![DynStyledContentWrapper {
                 style: ContentStyle::default(),
-                content: Box::new(value),
+                role: None,
+                content: Box::new(sanitize(value)),
             }],
+            time: None,
+            msgid: None,
+            label: None,
         }
     }
 }
@@ -202,12 +317,23 @@ impl<'a> From<&'a str> for Line<'a> {
 /// a wrapper for content that is not generic, so that different content types can be used
 pub struct DynStyledContentWrapper<'a> {
     style: ContentStyle,
+    /// the part of the theme that styles the content instead of `style`
+    role: Option<Role>,
     content: Box<dyn Display + Send + 'a>,
 }
 
+impl<'a> DynStyledContentWrapper<'a> {
+    fn style(&self) -> ContentStyle {
+        match self.role {
+            Some(role) => theme::current().get(role),
+            None => self.style,
+        }
+    }
+}
+
 impl<'a> Display for DynStyledContentWrapper<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", StyledContent::new(self.style, &self.content))
+        write!(f, "{}", StyledContent::new(self.style(), &self.content))
     }
 }
 
@@ -215,6 +341,7 @@ impl<'a> Debug for DynStyledContentWrapper<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DynStyledContentWrapper")
             .field("style", &self.style)
+            .field("role", &self.role)
             .field("content", &self.content.to_string())
             .finish()
     }
@@ -224,7 +351,8 @@ impl<'a, D: Display + 'a> From<StyledContent<D>> for DynStyledContentWrapper<'st
     fn from(value: StyledContent<D>) -> Self {
         Self {
             style: ContentStyle::default(),
-            content: Box::new(value.content().to_string()),
+            role: None,
+            content: Box::new(sanitize(&value.content().to_string()).into_owned()),
         }
     }
 }
@@ -281,3 +409,162 @@ pub fn draw_text<'a>(
 
     Ok(line_count)
 }
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use super::*;
+    use crate::ui::format::{self, Formatting};
+
+    /// escapes that a server could use to take over the terminal, in both their C0 and C1 forms
+    const ATTACKS: &[&str] = &[
+        "\x1b[2J",
+        "\x1b[31mred",
+        "\x1b]52;c;aGVsbG8=\x07",
+        "\x1b]52;c;aGVsbG8=\x1b\\",
+        "\x1b]0;title\x07",
+        "\u{9b}2J",
+        "\u{9d}52;c;aGVsbG8=\u{9c}",
+        "\x1bc",
+        "\x7f\x08\x08",
+        "\r\n",
+        "\x03\x1b[1m\x0f",
+    ];
+
+    /// a small xorshift generator, so the random inputs are the same on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    /// text made of printable ASCII, C0 and C1 controls, DEL and pieces of the attacks
+    fn random_text(rng: &mut Rng) -> String {
+        let mut text = String::new();
+        for _ in 0..rng.below(40) {
+            match rng.below(5) {
+                0 => text.push(char::from(rng.below(0x20) as u8)),
+                1 => text.push(char::from_u32(0x80 + rng.below(0x20) as u32).unwrap()),
+                2 => text.push('\x7f'),
+                3 => text.push_str(ATTACKS[rng.below(ATTACKS.len())]),
+                _ => text.push(char::from(0x20 + rng.below(0x5f) as u8)),
+            }
+        }
+        text
+    }
+
+    /// every control char, alone and between other text
+    fn enumerated_text() -> Vec<String> {
+        let controls = (0..0x20u32)
+            .chain([0x7f])
+            .chain(0x80..0xa0)
+            .filter_map(char::from_u32);
+        let mut texts = ATTACKS.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        for c in controls {
+            texts.push(c.to_string());
+            texts.push(format!("a{}b", c));
+        }
+        texts
+    }
+
+    /// every way of making a line from untrusted text
+    fn lines(text: &str) -> Vec<Line<'_>> {
+        let mut extended: Line<'static> = Line::default();
+        extended.extend([text.to_string().bold()]);
+        vec![
+            Line::default().push(text.to_string().red()),
+            Line::default().push(StyledContent::new(ContentStyle::default(), text)),
+            Line::default().push_themed(Role::Info, text),
+            Line::default().push_unstyled(text),
+            Line::from(text.to_string()),
+            Line::from(text),
+            extended,
+            format::parse(text, Formatting::Ansi256),
+            format::parse(text, Formatting::Truecolor),
+            format::parse(text, Formatting::Strip),
+        ]
+    }
+
+    /// removes the CSI sequences that crossterm writes to move the cursor and set styles
+    fn strip_crossterm(out: &[u8]) -> Vec<u8> {
+        let mut stripped = Vec::new();
+        let mut bytes = out.iter().copied().peekable();
+        while let Some(b) = bytes.next() {
+            if b == 0x1b && bytes.peek() == Some(&b'[') {
+                bytes.next();
+                // params and intermediates, up to the final byte
+                for b in bytes.by_ref() {
+                    if (0x40..=0x7e).contains(&b) {
+                        break;
+                    }
+                }
+                continue;
+            }
+            stripped.push(b);
+        }
+        stripped
+    }
+
+    fn assert_drawn_safely(text: &str) {
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: 10_000,
+            height: 1,
+        };
+        let config = DrawTextConfig {
+            wrap: WrapMode::Truncate,
+        };
+        for (idx, line) in lines(text).iter().enumerate() {
+            let mut out = Vec::new();
+            draw_text(&mut out, rect, line, config).unwrap();
+            let out = strip_crossterm(&out);
+            // the inputs have no multi-byte chars other than C1 controls, so any of these bytes
+            // would be a C1 control that got through
+            let bad = out
+                .iter()
+                .find(|b| matches!(b, 0x00..=0x1f | 0x7f | 0x80..=0x9f));
+            assert!(
+                bad.is_none(),
+                "line {} for {:?} wrote {:#04x}: {:?}",
+                idx,
+                text,
+                bad.unwrap(),
+                String::from_utf8_lossy(&out)
+            );
+        }
+    }
+
+    #[test]
+    fn sanitize_enumerated() {
+        for text in enumerated_text() {
+            assert_drawn_safely(&text);
+        }
+    }
+
+    #[test]
+    fn sanitize_random() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2000 {
+            assert_drawn_safely(&random_text(&mut rng));
+        }
+    }
+
+    #[test]
+    fn sanitize_caret_notation() {
+        assert_eq!(sanitize("plain"), "plain");
+        assert_eq!(sanitize("\x1b[2J"), "^[[2J");
+        assert_eq!(sanitize("\u{9b}2J"), "^[[2J");
+        assert_eq!(sanitize("a\tb\x7f"), "a b^?");
+    }
+}
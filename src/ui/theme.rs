@@ -0,0 +1,348 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use crossterm::style::{Attribute, Color, ContentStyle, Stylize};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::ui::nick_colors::{ColorDepth, ColorSpec};
+
+/// the directory inside the config directory that theme files are loaded from
+pub const THEME_DIR: &str = "themes";
+/// the themes that don't need a theme file
+pub const BUILTIN_THEMES: &[&str] = &["dark", "light", "monochrome"];
+
+/// the theme that everything is currently drawn with
+static CURRENT: LazyLock<RwLock<Arc<Theme>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(Theme::dark())));
+
+#[derive(Debug, Error)]
+pub enum ThemeErr {
+    #[error("no theme named {}", .0)]
+    NotFound(String),
+    #[error("could not access {}: {}", .0.display(), .1)]
+    Io(PathBuf, io::Error),
+    #[error("could not parse {}: {}", .0.display(), .1)]
+    Parse(PathBuf, toml::de::Error),
+    #[error("invalid colour for {:?}: {:?}", .0, .1)]
+    InvalidColor(Role, ColorSpec),
+}
+
+/// a part of the UI that the theme gives a style to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Timestamp,
+    OwnNick,
+    Join,
+    Part,
+    Quit,
+    Kick,
+    Notice,
+    /// the `*` before a `/me` action
+    Action,
+    Error,
+    Warning,
+    /// things that need the user's attention, like invites and messages that mention our nick
+    Highlight,
+    /// the headings of replies from the server, like `NAMES for` or `topic:`
+    Info,
+    /// less important details, like hosts and WHOIS field names
+    Dim,
+    Channel,
+    TopicBar,
+    StatusBar,
+    StatusAddr,
+    StatusNick,
+    InputLine,
+    /// the selected row in the LIST window
+    Selected,
+}
+
+/// a style in a theme file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    fg: Option<ColorSpec>,
+    bg: Option<ColorSpec>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    reverse: bool,
+}
+
+impl Style {
+    fn to_content_style(&self, role: Role, depth: ColorDepth) -> Result<ContentStyle, ThemeErr> {
+        let color = |spec: &Option<ColorSpec>| match spec {
+            Some(spec) => spec
+                .to_color(depth)
+                .map(Some)
+                .ok_or_else(|| ThemeErr::InvalidColor(role, spec.clone())),
+            None => Ok(None),
+        };
+        let mut style = ContentStyle {
+            foreground_color: color(&self.fg)?,
+            background_color: color(&self.bg)?,
+            ..ContentStyle::default()
+        };
+        for (enabled, attribute) in [
+            (self.bold, Attribute::Bold),
+            (self.italic, Attribute::Italic),
+            (self.underline, Attribute::Underlined),
+            (self.reverse, Attribute::Reverse),
+        ] {
+            if enabled {
+                style.attributes.set(attribute);
+            }
+        }
+        Ok(style)
+    }
+}
+
+/// a theme file in the theme directory
+#[derive(Debug, Deserialize)]
+struct ThemeFile {
+    /// the built-in theme that the file changes, which is `dark` if it isn't given
+    base: Option<String>,
+    #[serde(default)]
+    styles: HashMap<Role, Style>,
+}
+
+/// the style of every part of the UI
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    styles: HashMap<Role, ContentStyle>,
+}
+
+impl Theme {
+    fn builtin(name: &str, styles: &[(Role, ContentStyle)]) -> Self {
+        Self {
+            name: name.to_string(),
+            styles: styles.iter().copied().collect(),
+        }
+    }
+
+    pub fn get(&self, role: Role) -> ContentStyle {
+        self.styles.get(&role).copied().unwrap_or_default()
+    }
+
+    /// for terminals with a dark background
+    pub fn dark() -> Self {
+        let fg = |color: Color| ContentStyle {
+            foreground_color: Some(color),
+            ..ContentStyle::default()
+        };
+        let on = |fg: Option<Color>, bg: Color| ContentStyle {
+            foreground_color: fg,
+            background_color: Some(bg),
+            ..ContentStyle::default()
+        };
+        let status_bg = Color::Rgb {
+            r: 0x61,
+            g: 0x2B,
+            b: 0x5B,
+        };
+        Self::builtin(
+            "dark",
+            &[
+                (Role::Timestamp, fg(Color::Red)),
+                (Role::OwnNick, fg(Color::Cyan)),
+                (Role::Join, fg(Color::Green)),
+                (Role::Part, fg(Color::DarkYellow)),
+                (Role::Quit, fg(Color::DarkYellow)),
+                (Role::Kick, fg(Color::Red)),
+                (Role::Notice, fg(Color::Green)),
+                (Role::Action, fg(Color::Magenta)),
+                (Role::Error, fg(Color::Red)),
+                (Role::Warning, fg(Color::Yellow)),
+                (Role::Highlight, fg(Color::Yellow).bold()),
+                (Role::Info, fg(Color::Green)),
+                (Role::Dim, fg(Color::DarkGrey)),
+                (Role::Channel, fg(Color::DarkBlue)),
+                (
+                    Role::TopicBar,
+                    on(
+                        None,
+                        Color::Rgb {
+                            r: 0x2B,
+                            g: 0x2B,
+                            b: 0x40,
+                        },
+                    ),
+                ),
+                (Role::StatusBar, on(None, status_bg)),
+                (
+                    Role::StatusAddr,
+                    on(
+                        Some(Color::Rgb {
+                            r: 0xF4,
+                            g: 0x5B,
+                            b: 0x46,
+                        }),
+                        status_bg,
+                    ),
+                ),
+                (
+                    Role::StatusNick,
+                    on(
+                        Some(Color::Rgb {
+                            r: 0xFC,
+                            g: 0x91,
+                            b: 0x00,
+                        }),
+                        status_bg,
+                    ),
+                ),
+                (Role::InputLine, on(Some(Color::White), Color::Blue)),
+                (Role::Selected, on(Some(Color::Black), Color::Grey)),
+            ],
+        )
+    }
+
+    /// for terminals with a light background, using darker colours for text
+    pub fn light() -> Self {
+        let fg = |color: Color| ContentStyle {
+            foreground_color: Some(color),
+            ..ContentStyle::default()
+        };
+        let on = |fg: Color, bg: Color| ContentStyle {
+            foreground_color: Some(fg),
+            background_color: Some(bg),
+            ..ContentStyle::default()
+        };
+        Self::builtin(
+            "light",
+            &[
+                (Role::Timestamp, fg(Color::DarkGrey)),
+                (Role::OwnNick, fg(Color::DarkCyan)),
+                (Role::Join, fg(Color::DarkGreen)),
+                (Role::Part, fg(Color::DarkYellow)),
+                (Role::Quit, fg(Color::DarkYellow)),
+                (Role::Kick, fg(Color::DarkRed)),
+                (Role::Notice, fg(Color::DarkGreen)),
+                (Role::Action, fg(Color::DarkMagenta)),
+                (Role::Error, fg(Color::DarkRed)),
+                (Role::Warning, fg(Color::DarkYellow)),
+                (Role::Highlight, fg(Color::DarkRed).bold()),
+                (Role::Info, fg(Color::DarkGreen)),
+                (Role::Dim, fg(Color::DarkGrey)),
+                (Role::Channel, fg(Color::DarkBlue)),
+                (Role::TopicBar, on(Color::Black, Color::Grey)),
+                (Role::StatusBar, on(Color::White, Color::DarkBlue)),
+                (Role::StatusAddr, on(Color::Yellow, Color::DarkBlue)),
+                (Role::StatusNick, on(Color::White, Color::DarkBlue).bold()),
+                (Role::InputLine, on(Color::Black, Color::Grey)),
+                (Role::Selected, on(Color::White, Color::DarkBlue)),
+            ],
+        )
+    }
+
+    /// no colours at all, only bold, underline and reverse
+    pub fn monochrome() -> Self {
+        let plain = ContentStyle::default();
+        let bold = plain.bold();
+        let underline = ContentStyle {
+            attributes: Attribute::Underlined.into(),
+            ..plain
+        };
+        let reverse = ContentStyle {
+            attributes: Attribute::Reverse.into(),
+            ..plain
+        };
+        Self::builtin(
+            "monochrome",
+            &[
+                (Role::Timestamp, plain),
+                (Role::OwnNick, bold),
+                (Role::Join, plain),
+                (Role::Part, plain),
+                (Role::Quit, plain),
+                (Role::Kick, bold),
+                (Role::Notice, bold),
+                (Role::Action, bold),
+                (Role::Error, bold),
+                (Role::Warning, bold),
+                (Role::Highlight, reverse),
+                (Role::Info, underline),
+                (Role::Dim, plain),
+                (Role::Channel, bold),
+                (Role::TopicBar, reverse),
+                (Role::StatusBar, reverse),
+                (Role::StatusAddr, reverse),
+                (Role::StatusNick, reverse),
+                (Role::InputLine, plain),
+                (Role::Selected, reverse),
+            ],
+        )
+    }
+
+    /// loads a built-in theme, or a theme file from `<config dir>/themes/<name>.toml`
+    pub fn load(config_dir: &Path, name: &str, depth: ColorDepth) -> Result<Self, ThemeErr> {
+        if let Some(theme) = builtin(name) {
+            return Ok(theme);
+        }
+
+        let path = config_dir.join(THEME_DIR).join(format!("{}.toml", name));
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(ThemeErr::NotFound(name.to_string()));
+            }
+            Err(e) => return Err(ThemeErr::Io(path, e)),
+        };
+        let file: ThemeFile = toml::from_str(&text).map_err(|e| ThemeErr::Parse(path, e))?;
+
+        let base = file.base.as_deref().unwrap_or("dark");
+        let mut theme = builtin(base).ok_or_else(|| ThemeErr::NotFound(base.to_string()))?;
+        theme.name = name.to_string();
+        for (role, style) in file.styles {
+            theme
+                .styles
+                .insert(role, style.to_content_style(role, depth)?);
+        }
+        Ok(theme)
+    }
+}
+
+fn builtin(name: &str) -> Option<Theme> {
+    match name {
+        "dark" => Some(Theme::dark()),
+        "light" => Some(Theme::light()),
+        "monochrome" => Some(Theme::monochrome()),
+        _ => None,
+    }
+}
+
+/// the names of the built-in themes and the theme files in the theme directory
+pub fn available(config_dir: &Path) -> Vec<String> {
+    let mut names = BUILTIN_THEMES
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    if let Ok(entries) = fs::read_dir(config_dir.join(THEME_DIR)) {
+        let mut files = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .filter(|name| !BUILTIN_THEMES.contains(&name.as_str()))
+            .collect::<Vec<_>>();
+        files.sort();
+        names.extend(files);
+    }
+    names
+}
+
+pub fn current() -> Arc<Theme> {
+    // UNWRAP: the lock is never held while panicking
+    CURRENT.read().unwrap().clone()
+}
+
+pub fn set(theme: Theme) {
+    // UNWRAP: the lock is never held while panicking
+    *CURRENT.write().unwrap() = Arc::new(theme);
+}
@@ -1,30 +1,39 @@
+use chrono::{DateTime, Utc};
 use crossterm::style::Stylize;
 
-use crate::ui::text::Line;
+use crate::ui::{
+    nick_colors::NickColors,
+    template::{self, Kind},
+    text::Line,
+    theme::Role,
+};
 
 pub mod unicode_width;
 
 pub fn line_now() -> Line<'static> {
-    const FMT: &str = "%H:%M:%S";
-    let now = chrono::Local::now();
+    line_at(Utc::now())
+}
 
-    Line::default()
-        .push_unstyled("[")
-        .push(now.format(FMT).to_string().red())
-        .push_unstyled("]")
+/// the start of a line for something that happened at `time`, from the `line` template
+pub fn line_at(time: DateTime<Utc>) -> Line<'static> {
+    template::render(Kind::Line, time, Vec::new())
 }
 
-pub fn message_nick_line(nick: &str, me: bool) -> Line<'static> {
-    Line::default()
-        .push_unstyled("<")
-        .join(nick_line(nick, me))
-        .push_unstyled(">")
+/// a message, action or notice, laid out with the template for `kind`
+pub fn message_line(
+    kind: Kind,
+    time: DateTime<Utc>,
+    nick: Line<'static>,
+    text: Line<'static>,
+) -> Line<'static> {
+    template::render(kind, time, vec![("nick", nick), ("text", text)])
 }
 
-pub fn nick_line(nick: &str, me: bool) -> Line<'static> {
+/// a nick in its colour, which comes from the theme for our own nick
+pub fn nick_line(colors: &NickColors, nick: &str, me: bool) -> Line<'static> {
     if me {
-        Line::default().push(nick.to_string().cyan())
+        Line::default().push_themed(Role::OwnNick, nick)
     } else {
-        Line::default().push(nick.to_string().magenta())
+        Line::default().push(nick.to_string().with(colors.color(nick)))
     }
 }